@@ -0,0 +1,122 @@
+use std::env;
+use std::fs::{remove_file, write};
+use std::hint::black_box;
+use std::path::{Path, PathBuf};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use hecto::bench::{Buffer, Highlighter, Location, SearchOptions, SearchPattern};
+
+const LOG_LINES: usize = 500_000;
+const RUST_LINES: usize = 200_000;
+const SCREEN_HEIGHT: usize = 50;
+
+// Generates the file to work on, so that every benchmark uses the same text
+// regardless of what's on disk.
+fn write_file(extension: &str, line_count: usize, line: impl Fn(usize) -> String) -> PathBuf {
+    let path = env::temp_dir().join(format!("hecto-bench-{}.{extension}", std::process::id()));
+    let contents: String = (0..line_count).map(|index| line(index) + "\n").collect();
+    write(&path, contents).expect("Failed to write benchmark file");
+    path
+}
+
+fn write_log_file() -> PathBuf {
+    write_file("log", LOG_LINES, |index| {
+        format!("2024-01-01T00:00:00Z INFO request {index} handled in 12ms by worker ünïcödé")
+    })
+}
+
+fn write_rust_file() -> PathBuf {
+    write_file("rs", RUST_LINES, |index| match index % 4 {
+        0 => String::from("/* A multi-line comment"),
+        1 => format!("   spanning lines {index} */"),
+        2 => format!("fn function_{index}(value: u32) -> Option<&'static str> {{ // Some"),
+        _ => format!("    if value > {index} {{ Some(\"big\") }} else {{ None }} }}"),
+    })
+}
+
+fn load_buffer(path: &Path) -> Buffer {
+    Buffer::load(path.to_str().expect("Temporary path isn't UTF-8"))
+        .expect("Failed to load benchmark file")
+}
+
+fn bench_buffer(c: &mut Criterion) {
+    let path = write_log_file();
+
+    c.bench_function("load 500k lines", |b| {
+        b.iter(|| black_box(load_buffer(&path)));
+    });
+
+    let mut buffer = load_buffer(&path);
+    let near_top = Location {
+        grapheme_index: 5,
+        line_index: 10,
+    };
+    // Splitting a line and joining it again leaves the buffer as it was.
+    c.bench_function("insert and remove line near top of 500k lines", |b| {
+        b.iter(|| {
+            buffer.insert_newline(black_box(near_top));
+            buffer.delete(black_box(near_top));
+        });
+    });
+
+    c.bench_function("insert and remove char near top of 500k lines", |b| {
+        b.iter(|| {
+            buffer.insert_char('x', black_box(near_top));
+            buffer.delete(black_box(near_top));
+        });
+    });
+
+    let pattern = SearchPattern::new("request 499999 ", SearchOptions::default())
+        .expect("Invalid search pattern");
+    c.bench_function("search forward through 500k lines", |b| {
+        b.iter(|| black_box(buffer.search_forward(&pattern, Location::default())));
+    });
+
+    let mut long_line = Buffer::default();
+    long_line.replace_range(
+        Location::default(),
+        Location::default(),
+        &"lorem ipsum ".repeat(5_000),
+    );
+    c.bench_function("insert and remove char in 60k char line", |b| {
+        b.iter(|| {
+            long_line.insert_char('x', black_box(Location::default()));
+            long_line.delete(black_box(Location::default()));
+        });
+    });
+
+    remove_file(&path).ok();
+}
+
+fn bench_highlighting(c: &mut Criterion) {
+    let path = write_rust_file();
+    let mut buffer = load_buffer(&path);
+    let last_screen = RUST_LINES.saturating_sub(SCREEN_HEIGHT)..RUST_LINES;
+    // Highlights everything above the last screen once.
+    buffer.highlight(last_screen.clone(), &mut Highlighter::default());
+
+    c.bench_function("highlight last screen of 200k Rust lines", |b| {
+        b.iter(|| buffer.highlight(black_box(last_screen.clone()), &mut Highlighter::default()));
+    });
+
+    let near_top = Location {
+        grapheme_index: 0,
+        line_index: 10,
+    };
+    c.bench_function("edit near top, then highlight last screen", |b| {
+        b.iter(|| {
+            buffer.insert_char('x', black_box(near_top));
+            buffer.delete(black_box(near_top));
+            buffer.highlight(black_box(last_screen.clone()), &mut Highlighter::default());
+        });
+    });
+
+    remove_file(&path).ok();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = bench_buffer, bench_highlighting
+}
+criterion_main!(benches);
@@ -15,16 +15,24 @@ impl<'a> Iterator for AnnotatedStringIterator<'a> {
             return None;
         }
 
-        if let Some(annotation) = self
-            .annotated_string
-            .annotations
-            .iter()
-            .filter(|annotation| {
-                annotation.start <= self.current_index && annotation.end > self.current_index
-            })
-            .last()
+        if let Some(annotation) =
+            self.annotated_string
+                .annotations
+                .iter()
+                .rev()
+                .find(|annotation| {
+                    annotation.start <= self.current_index && annotation.end > self.current_index
+                })
         {
-            let end_index = min(annotation.end, self.annotated_string.string.len());
+            // Stop early where another annotation begins, since it might
+            // take precedence over this one.
+            let end_index = self
+                .annotated_string
+                .annotations
+                .iter()
+                .map(|other| other.start)
+                .filter(|start| *start > self.current_index)
+                .fold(min(annotation.end, self.annotated_string.string.len()), min);
             let start_index = self.current_index;
             self.current_index = end_index;
             return Some(AnnotatedStringPart {
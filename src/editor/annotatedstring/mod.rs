@@ -53,7 +53,7 @@ impl AnnotatedString {
         }
         self.string.replace_range(start..end, new_string);
 
-        let replaced_range_len = end.saturating_add(start);
+        let replaced_range_len = end.saturating_sub(start);
         let shortened = new_string.len() < replaced_range_len;
         let len_difference = new_string.len().abs_diff(replaced_range_len);
         if len_difference == 0 {
@@ -3,6 +3,7 @@ use crate::prelude::ByteIdx;
 use super::AnnotationType;
 
 #[derive(Copy, Clone, Debug)]
+#[allow(clippy::struct_field_names)]
 pub struct Annotation {
     pub annotation_type: AnnotationType,
     pub start: ByteIdx,
@@ -10,4 +10,50 @@ pub enum AnnotationType {
     LifetimeSpecifier,
     Comment,
     String,
+    Selection,
+    LineNumber,
+    CurrentLineNumber,
+}
+
+impl AnnotationType {
+    pub const ALL: [Self; 13] = [
+        Self::Match,
+        Self::SelectedMatch,
+        Self::Number,
+        Self::Keyword,
+        Self::Type,
+        Self::KnownValue,
+        Self::Char,
+        Self::LifetimeSpecifier,
+        Self::Comment,
+        Self::String,
+        Self::Selection,
+        Self::LineNumber,
+        Self::CurrentLineNumber,
+    ];
+
+    /// How the type is referred to in configuration files.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Match => "match",
+            Self::SelectedMatch => "selected_match",
+            Self::Number => "number",
+            Self::Keyword => "keyword",
+            Self::Type => "type",
+            Self::KnownValue => "known_value",
+            Self::Char => "char",
+            Self::LifetimeSpecifier => "lifetime_specifier",
+            Self::Comment => "comment",
+            Self::String => "string",
+            Self::Selection => "selection",
+            Self::LineNumber => "line_number",
+            Self::CurrentLineNumber => "current_line_number",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|annotation_type| annotation_type.name() == name)
+    }
 }
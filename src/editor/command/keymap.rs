@@ -0,0 +1,440 @@
+use std::fmt::{self, Display};
+use std::mem::take;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use super::{Command, Edit};
+
+// The bindings in effect unless the configuration changes them.
+const DEFAULT_BINDINGS: &[(&str, &str)] = &[
+    ("Enter", "insert_newline"),
+    ("Tab", "insert_tab"),
+    ("Backspace", "backspace"),
+    ("Delete", "delete"),
+    ("Ctrl+Z", "undo"),
+    ("Ctrl+Y", "redo"),
+    ("Up", "move_up"),
+    ("Down", "move_down"),
+    ("Left", "move_left"),
+    ("Right", "move_right"),
+    ("PageUp", "page_up"),
+    ("PageDown", "page_down"),
+    ("Home", "line_start"),
+    ("End", "line_end"),
+    ("Shift+Up", "select_up"),
+    ("Shift+Down", "select_down"),
+    ("Shift+Left", "select_left"),
+    ("Shift+Right", "select_right"),
+    ("Shift+PageUp", "select_page_up"),
+    ("Shift+PageDown", "select_page_down"),
+    ("Shift+Home", "select_line_start"),
+    ("Shift+End", "select_line_end"),
+    ("Ctrl+S", "save"),
+    ("Ctrl+W", "save_as"),
+    ("Ctrl+O", "open"),
+    ("Ctrl+L", "reload"),
+    ("Ctrl+Q", "quit"),
+    ("Ctrl+F", "search"),
+    ("Ctrl+R", "replace"),
+    ("Ctrl+X", "cut"),
+    ("Ctrl+C", "copy"),
+    ("Ctrl+V", "paste"),
+    ("Ctrl+B", "list_buffers"),
+    ("Ctrl+P", "command_palette"),
+    ("Ctrl+PageDown", "next_buffer"),
+    ("Ctrl+PageUp", "previous_buffer"),
+    ("Alt+R", "toggle_regex"),
+    ("Alt+C", "toggle_ignore_case"),
+    ("Alt+W", "toggle_whole_word"),
+    ("Alt+S", "split_horizontal"),
+    ("Alt+V", "split_vertical"),
+    ("Alt+O", "focus_next_pane"),
+    ("Alt+=", "grow_pane"),
+    ("Alt+-", "shrink_pane"),
+    ("Alt+Q", "close_pane"),
+    ("Alt+Z", "cycle_wrap_mode"),
+    ("Alt+L", "cycle_line_numbers"),
+    ("Alt+T", "cycle_theme"),
+    ("Alt+E", "convert_line_endings"),
+    ("Esc", "dismiss"),
+];
+
+/// A single key press along with its modifiers, such as Ctrl+S.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KeyChord {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    // Terminals differ in whether they report Shift along with the character
+    // it produced, so it's folded into the character: Shift+s is `S`.
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let shifted = modifiers.contains(KeyModifiers::SHIFT);
+        match code {
+            KeyCode::Char(character) => Self {
+                code: KeyCode::Char(if shifted {
+                    character.to_ascii_uppercase()
+                } else {
+                    character
+                }),
+                modifiers: modifiers.difference(KeyModifiers::SHIFT),
+            },
+            KeyCode::Tab if shifted => Self {
+                code: KeyCode::BackTab,
+                modifiers: modifiers.difference(KeyModifiers::SHIFT),
+            },
+            KeyCode::BackTab => Self {
+                code,
+                modifiers: modifiers.difference(KeyModifiers::SHIFT),
+            },
+            _ => Self { code, modifiers },
+        }
+    }
+}
+
+impl From<KeyEvent> for KeyChord {
+    fn from(event: KeyEvent) -> Self {
+        Self::new(event.code, event.modifiers)
+    }
+}
+
+impl TryFrom<&str> for KeyChord {
+    type Error = String;
+
+    /// Parses chords like "Ctrl+Shift+PageUp" or "Alt+=". Letters are
+    /// matched regardless of their case, so "Ctrl+S" is Ctrl and the S key.
+    fn try_from(text: &str) -> Result<Self, Self::Error> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        while let Some((modifier, after)) =
+            rest.split_once('+').filter(|(_, after)| !after.is_empty())
+        {
+            modifiers |= match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("Unknown modifier '{modifier}' in '{text}'")),
+            };
+            rest = after;
+        }
+        let mut characters = rest.chars();
+        let code = match (characters.next(), characters.next()) {
+            (Some(character), None) => KeyCode::Char(character.to_ascii_lowercase()),
+            _ => match rest.to_ascii_lowercase().as_str() {
+                "space" => KeyCode::Char(' '),
+                "tab" => KeyCode::Tab,
+                "enter" | "return" => KeyCode::Enter,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" | "ins" => KeyCode::Insert,
+                "esc" | "escape" => KeyCode::Esc,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "pageup" | "pgup" => KeyCode::PageUp,
+                "pagedown" | "pgdn" => KeyCode::PageDown,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                name => name
+                    .strip_prefix('f')
+                    .and_then(|number| number.parse().ok())
+                    .filter(|number| (1..=12).contains(number))
+                    .map(KeyCode::F)
+                    .ok_or_else(|| format!("Unknown key '{rest}' in '{text}'"))?,
+            },
+        };
+        Ok(Self::new(code, modifiers))
+    }
+}
+
+impl Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(character) if character.is_ascii_uppercase() => {
+                write!(f, "Shift+{character}")
+            }
+            KeyCode::Char(character) => write!(f, "{}", character.to_ascii_uppercase()),
+            KeyCode::BackTab => write!(f, "Shift+Tab"),
+            KeyCode::Esc => write!(f, "Esc"),
+            KeyCode::F(number) => write!(f, "F{number}"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+/// Parses a sequence of chords separated by spaces, like "Ctrl+K Ctrl+S".
+pub fn parse_key_sequence(text: &str) -> Result<Vec<KeyChord>, String> {
+    let keys = text
+        .split_whitespace()
+        .map(KeyChord::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+    if keys.is_empty() {
+        return Err(String::from("Key sequences can't be empty"));
+    }
+    Ok(keys)
+}
+
+fn sequence_to_string(keys: &[KeyChord]) -> String {
+    keys.iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// What a key press amounts to.
+pub enum KeyInput {
+    Command(Command),
+    /// The keys typed so far start a longer sequence.
+    Pending(String),
+    /// The keys typed form a sequence which isn't bound to anything.
+    Unbound(String),
+    /// A single key which does nothing.
+    Ignored,
+}
+
+/// Maps key sequences to the commands they trigger.
+pub struct Keymap {
+    bindings: Vec<(Vec<KeyChord>, &'static str)>,
+    // The start of a sequence typed so far.
+    pending: Vec<KeyChord>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = DEFAULT_BINDINGS
+            .iter()
+            .filter_map(|(keys, name)| Some((parse_key_sequence(keys).ok()?, *name)))
+            .collect::<Vec<_>>();
+        debug_assert_eq!(bindings.len(), DEFAULT_BINDINGS.len());
+        Self {
+            bindings,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl Keymap {
+    /// Binds `keys` to the named command, replacing what they were bound to
+    /// before, or unbinds them if there's no command.
+    pub fn bind(&mut self, keys: Vec<KeyChord>, command_name: Option<&'static str>) {
+        self.bindings.retain(|(bound_keys, _)| *bound_keys != keys);
+        if let Some(command_name) = command_name {
+            self.bindings.push((keys, command_name));
+        }
+    }
+
+    /// Describes the bindings which can never be typed, since a shorter
+    /// binding starts the same way.
+    pub fn conflicts(&self) -> Vec<String> {
+        let mut conflicts = Vec::new();
+        for (keys, name) in &self.bindings {
+            for (longer_keys, longer_name) in &self.bindings {
+                if longer_keys.len() > keys.len() && longer_keys.starts_with(keys) {
+                    conflicts.push(format!(
+                        "{} ({longer_name}) can't be typed, as {} is bound to {name}",
+                        sequence_to_string(longer_keys),
+                        sequence_to_string(keys),
+                    ));
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// Returns the key sequences bound to the named command, like "Ctrl+S".
+    pub fn keys_for(&self, command_name: &str) -> Vec<String> {
+        self.bindings
+            .iter()
+            .filter(|(_, name)| *name == command_name)
+            .map(|(keys, _)| sequence_to_string(keys))
+            .collect()
+    }
+
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    pub fn resolve(&mut self, event: KeyEvent) -> KeyInput {
+        self.pending.push(KeyChord::from(event));
+        if let Some((_, name)) = self.bindings.iter().find(|(keys, _)| *keys == self.pending) {
+            self.pending.clear();
+            return Command::from_name(name).map_or(KeyInput::Ignored, KeyInput::Command);
+        }
+        if self
+            .bindings
+            .iter()
+            .any(|(keys, _)| keys.starts_with(&self.pending))
+        {
+            return KeyInput::Pending(sequence_to_string(&self.pending));
+        }
+        match take(&mut self.pending).as_slice() {
+            [KeyChord {
+                code: KeyCode::Char(character),
+                modifiers: KeyModifiers::NONE,
+            }] => KeyInput::Command(Command::Edit(Edit::Insert(*character))),
+            [_] => KeyInput::Ignored,
+            keys => KeyInput::Unbound(sequence_to_string(keys)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::System;
+    use super::*;
+
+    fn chord(text: &str) -> KeyChord {
+        KeyChord::try_from(text).unwrap()
+    }
+
+    fn press(keymap: &mut Keymap, text: &str) -> KeyInput {
+        let chord = chord(text);
+        keymap.resolve(KeyEvent::new(chord.code, chord.modifiers))
+    }
+
+    #[test]
+    fn parses_modifiers_and_key_names() {
+        assert_eq!(
+            chord("Ctrl+Shift+PageUp"),
+            KeyChord::new(KeyCode::PageUp, KeyModifiers::CONTROL | KeyModifiers::SHIFT)
+        );
+        assert_eq!(
+            chord("control+meta+del"),
+            KeyChord::new(KeyCode::Delete, KeyModifiers::CONTROL | KeyModifiers::ALT)
+        );
+        assert_eq!(
+            chord("F12"),
+            KeyChord::new(KeyCode::F(12), KeyModifiers::NONE)
+        );
+        assert_eq!(
+            chord("Space"),
+            KeyChord::new(KeyCode::Char(' '), KeyModifiers::NONE)
+        );
+    }
+
+    #[test]
+    fn plus_minus_and_equals_can_be_keys() {
+        assert_eq!(
+            chord("Alt+="),
+            KeyChord::new(KeyCode::Char('='), KeyModifiers::ALT)
+        );
+        assert_eq!(
+            chord("Alt+-"),
+            KeyChord::new(KeyCode::Char('-'), KeyModifiers::ALT)
+        );
+        assert_eq!(
+            chord("Alt++"),
+            KeyChord::new(KeyCode::Char('+'), KeyModifiers::ALT)
+        );
+        assert_eq!(
+            chord("+"),
+            KeyChord::new(KeyCode::Char('+'), KeyModifiers::NONE)
+        );
+    }
+
+    #[test]
+    fn letters_match_regardless_of_case_and_shift() {
+        assert_eq!(chord("Ctrl+s"), chord("Ctrl+S"));
+        assert_eq!(
+            KeyChord::from(KeyEvent::new(KeyCode::Char('S'), KeyModifiers::SHIFT)),
+            KeyChord::from(KeyEvent::new(KeyCode::Char('S'), KeyModifiers::NONE))
+        );
+        assert_ne!(chord("Shift+s"), chord("s"));
+    }
+
+    #[test]
+    fn rejects_unknown_modifiers_and_keys() {
+        assert!(KeyChord::try_from("Hyper+S").is_err());
+        assert!(KeyChord::try_from("Ctrl+Nope").is_err());
+        assert!(KeyChord::try_from("F13").is_err());
+        assert!(parse_key_sequence("  ").is_err());
+        assert!(parse_key_sequence("Ctrl+K Bogus").is_err());
+    }
+
+    #[test]
+    fn shows_chords_as_they_are_written() {
+        for text in [
+            "Ctrl+S",
+            "Ctrl+Alt+Delete",
+            "Alt+=",
+            "Space",
+            "F5",
+            "Shift+Tab",
+        ] {
+            assert_eq!(chord(text).to_string(), text);
+        }
+        let keys = parse_key_sequence("ctrl+k  ctrl+s").unwrap();
+        assert_eq!(sequence_to_string(&keys), "Ctrl+K Ctrl+S");
+    }
+
+    #[test]
+    fn all_default_bindings_parse() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.bindings.len(), DEFAULT_BINDINGS.len());
+        assert!(keymap.conflicts().is_empty());
+        for (_, name) in DEFAULT_BINDINGS {
+            assert!(Command::from_name(name).is_some(), "{name}");
+        }
+    }
+
+    #[test]
+    fn reports_sequences_hidden_by_shorter_ones() {
+        let mut keymap = Keymap::default();
+        keymap.bind(parse_key_sequence("Ctrl+S Ctrl+A").unwrap(), Some("quit"));
+        assert_eq!(
+            keymap.conflicts(),
+            ["Ctrl+S Ctrl+A (quit) can't be typed, as Ctrl+S is bound to save"]
+        );
+        keymap.bind(parse_key_sequence("Ctrl+S").unwrap(), None);
+        assert!(keymap.conflicts().is_empty());
+    }
+
+    #[test]
+    fn rebinding_replaces_the_old_command() {
+        let mut keymap = Keymap::default();
+        keymap.bind(parse_key_sequence("Ctrl+S").unwrap(), Some("quit"));
+        assert!(matches!(
+            press(&mut keymap, "Ctrl+S"),
+            KeyInput::Command(Command::System(System::Quit))
+        ));
+        assert!(matches!(
+            press(&mut keymap, "Ctrl+Q"),
+            KeyInput::Command(Command::System(System::Quit))
+        ));
+    }
+
+    #[test]
+    fn resolves_sequences_key_by_key() {
+        let mut keymap = Keymap::default();
+        keymap.bind(parse_key_sequence("Ctrl+K Ctrl+S").unwrap(), Some("save"));
+        assert!(
+            matches!(press(&mut keymap, "Ctrl+K"), KeyInput::Pending(keys) if keys == "Ctrl+K")
+        );
+        assert!(keymap.is_pending());
+        assert!(matches!(
+            press(&mut keymap, "Ctrl+S"),
+            KeyInput::Command(Command::System(System::Save))
+        ));
+        assert!(!keymap.is_pending());
+
+        press(&mut keymap, "Ctrl+K");
+        assert!(matches!(press(&mut keymap, "x"), KeyInput::Unbound(keys) if keys == "Ctrl+K X"));
+        assert!(matches!(
+            press(&mut keymap, "x"),
+            KeyInput::Command(Command::Edit(Edit::Insert('x')))
+        ));
+        assert!(matches!(press(&mut keymap, "Ctrl+G"), KeyInput::Ignored));
+    }
+}
@@ -4,33 +4,110 @@ use crate::prelude::*;
 
 mod edit;
 pub use edit::Edit;
+mod keymap;
+pub use keymap::{parse_key_sequence, KeyChord, KeyInput, Keymap};
 mod movecommand;
 pub use movecommand::Move;
 mod system;
 pub use system::System;
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub enum Command {
     Move(Move),
+    Select(Move),
     Edit(Edit),
     System(System),
 }
 
+impl Command {
+    /// Every command which can be bound to keys, by the name used for it in
+    /// configuration files.
+    pub const NAMED: &'static [(&'static str, Self)] = &[
+        ("insert_newline", Self::Edit(Edit::InsertNewLine)),
+        ("insert_tab", Self::Edit(Edit::Insert('\t'))),
+        ("backspace", Self::Edit(Edit::Backspace)),
+        ("delete", Self::Edit(Edit::Delete)),
+        ("undo", Self::Edit(Edit::Undo)),
+        ("redo", Self::Edit(Edit::Redo)),
+        ("move_up", Self::Move(Move::Up)),
+        ("move_down", Self::Move(Move::Down)),
+        ("move_left", Self::Move(Move::Left)),
+        ("move_right", Self::Move(Move::Right)),
+        ("page_up", Self::Move(Move::PageUp)),
+        ("page_down", Self::Move(Move::PageDown)),
+        ("line_start", Self::Move(Move::Home)),
+        ("line_end", Self::Move(Move::End)),
+        ("select_up", Self::Select(Move::Up)),
+        ("select_down", Self::Select(Move::Down)),
+        ("select_left", Self::Select(Move::Left)),
+        ("select_right", Self::Select(Move::Right)),
+        ("select_page_up", Self::Select(Move::PageUp)),
+        ("select_page_down", Self::Select(Move::PageDown)),
+        ("select_line_start", Self::Select(Move::Home)),
+        ("select_line_end", Self::Select(Move::End)),
+        ("save", Self::System(System::Save)),
+        ("save_as", Self::System(System::SaveAs)),
+        ("open", Self::System(System::Open)),
+        ("reload", Self::System(System::Reload)),
+        ("quit", Self::System(System::Quit)),
+        ("search", Self::System(System::Search)),
+        ("replace", Self::System(System::Replace)),
+        ("cut", Self::System(System::Cut)),
+        ("copy", Self::System(System::Copy)),
+        ("paste", Self::System(System::Paste)),
+        ("list_buffers", Self::System(System::ListBuffers)),
+        ("next_buffer", Self::System(System::NextBuffer)),
+        ("previous_buffer", Self::System(System::PreviousBuffer)),
+        ("toggle_regex", Self::System(System::ToggleRegex)),
+        ("toggle_ignore_case", Self::System(System::ToggleIgnoreCase)),
+        ("toggle_whole_word", Self::System(System::ToggleWholeWord)),
+        ("split_horizontal", Self::System(System::SplitHorizontal)),
+        ("split_vertical", Self::System(System::SplitVertical)),
+        ("focus_next_pane", Self::System(System::FocusNextPane)),
+        ("grow_pane", Self::System(System::GrowPane)),
+        ("shrink_pane", Self::System(System::ShrinkPane)),
+        ("close_pane", Self::System(System::ClosePane)),
+        ("cycle_wrap_mode", Self::System(System::CycleWrapMode)),
+        ("cycle_line_numbers", Self::System(System::CycleLineNumbers)),
+        ("cycle_theme", Self::System(System::CycleTheme)),
+        (
+            "convert_line_endings",
+            Self::System(System::ConvertLineEndings),
+        ),
+        ("command_palette", Self::System(System::CommandPalette)),
+        ("dismiss", Self::System(System::Dismiss)),
+    ];
+
+    /// Returns the command's name as it appears in [`Self::NAMED`].
+    pub fn canonical_name(name: &str) -> Option<&'static str> {
+        Self::NAMED
+            .iter()
+            .map(|(known_name, _)| *known_name)
+            .find(|known_name| *known_name == name)
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMED
+            .iter()
+            .find(|(known_name, _)| *known_name == name)
+            .map(|(_, command)| command.clone())
+    }
+}
+
+// Key events are looked up in the `Keymap` instead.
 #[allow(clippy::as_conversions)]
 impl TryFrom<Event> for Command {
     type Error = String;
 
     fn try_from(event: Event) -> Result<Self, Self::Error> {
         match event {
-            Event::Key(key_event) => Edit::try_from(key_event)
-                .map(Command::Edit)
-                .or_else(|_| Move::try_from(key_event).map(Command::Move))
-                .or_else(|_| System::try_from(key_event).map(Command::System))
-                .map_err(|_| format!("Unsupported key event: {key_event:?}")),
             Event::Resize(width_u16, height_u16) => Ok(Self::System(System::Resize(Size {
                 height: height_u16 as usize,
                 width: width_u16 as usize,
             }))),
+            Event::Paste(text) => Ok(Self::Edit(Edit::InsertText(
+                text.replace("\r\n", "\n").replace('\r', "\n"),
+            ))),
             _ => Err(format!("Unsupported event: {event:?}")),
         }
     }
@@ -0,0 +1,408 @@
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use toml::{Table, Value};
+
+use super::{
+    parse_attribute, parse_key_sequence, AnnotationType, Attribute, ColorMode, Command, KeyChord,
+    Theme,
+};
+
+const CONFIG_DIR_NAME: &str = "hecto";
+const CONFIG_FILE_NAME: &str = "config.toml";
+// Within the configuration directory, holding a file for each theme.
+const THEMES_DIR_NAME: &str = "themes";
+// Looked for in the working directory and its ancestors.
+const PROJECT_CONFIG_FILE_NAME: &str = ".hecto.toml";
+const QUIT_TIMES_RANGE: RangeInclusive<i64> = 1..=10;
+pub const TAB_WIDTH_RANGE: RangeInclusive<i64> = 1..=16;
+const MAX_MESSAGE_DURATION_SECS: f64 = 3600.0;
+
+/// Editor settings, read from the user's `~/.config/hecto/config.toml` and
+/// overridden by a `.hecto.toml` in the project.
+pub struct Config {
+    /// How often Ctrl+Q must be pressed to quit with unsaved changes.
+    pub quit_times: u8,
+    /// How long messages are shown.
+    pub message_duration: Duration,
+    /// How many columns a tab takes up.
+    pub tab_width: usize,
+    /// Styles replacing those of any theme for some annotation types.
+    pub colors: Vec<(AnnotationType, Attribute)>,
+    /// Key sequences bound to a command, or unbound by `"none"`, in the
+    /// order they're to be applied to the default key bindings.
+    pub key_bindings: Vec<(Vec<KeyChord>, Option<&'static str>)>,
+    /// Which colors the terminal can show, if not to be detected.
+    pub color_mode: Option<ColorMode>,
+    /// The name of the theme to start with.
+    pub theme: Option<String>,
+    /// The themes the user defined in addition to the built-in ones.
+    pub themes: Vec<Theme>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            quit_times: 3,
+            message_duration: Duration::from_secs(5),
+            tab_width: 1,
+            colors: Vec::new(),
+            key_bindings: Vec::new(),
+            color_mode: None,
+            theme: None,
+            themes: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads the configuration files which exist. Malformed settings are
+    /// skipped rather than keeping the editor from starting, and described
+    /// in the returned errors instead.
+    pub fn load() -> (Self, Vec<String>) {
+        let mut config = Self::default();
+        let mut errors = Vec::new();
+        let user_config_dir = user_config_dir();
+        if let Some(dir) = &user_config_dir {
+            let (themes, theme_errors) = Theme::load_dir(&dir.join(THEMES_DIR_NAME));
+            config.themes = themes;
+            errors.extend(theme_errors);
+        }
+        for path in [
+            user_config_dir.map(|dir| dir.join(CONFIG_FILE_NAME)),
+            project_config_path(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            config.apply_file(&path, &mut errors);
+        }
+        (config, errors)
+    }
+
+    fn apply_file(&mut self, path: &Path, errors: &mut Vec<String>) {
+        let file_name = path.display();
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => return,
+            Err(err) => {
+                errors.push(format!("{file_name}: {err}"));
+                return;
+            }
+        };
+        let table = match contents.parse::<Table>() {
+            Ok(table) => table,
+            Err(err) => {
+                let line = err.span().map_or(1, |span| {
+                    contents
+                        .get(..span.start)
+                        .unwrap_or_default()
+                        .matches('\n')
+                        .count()
+                        .saturating_add(1)
+                });
+                errors.push(format!("{file_name}, line {line}: {}", err.message()));
+                return;
+            }
+        };
+        for (key, value) in &table {
+            let mut report = |err| errors.push(format!("{file_name}: {err}"));
+            if key == "colors" {
+                self.apply_colors(value, &mut report);
+            } else if key == "keys" {
+                self.apply_key_bindings(value, &mut report);
+            } else if let Err(err) = self.apply_setting(key, value) {
+                errors.push(format!("{file_name}: {err}"));
+            }
+        }
+    }
+
+    fn apply_setting(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        match key {
+            "quit_times" => {
+                self.quit_times = integer_in(key, value, QUIT_TIMES_RANGE)?;
+            }
+            "tab_width" => {
+                self.tab_width = integer_in(key, value, TAB_WIDTH_RANGE)?;
+            }
+            "message_duration" => {
+                let seconds = match value {
+                    Value::Integer(seconds) => i32::try_from(*seconds).ok().map(f64::from),
+                    Value::Float(seconds) => Some(*seconds),
+                    _ => None,
+                };
+                self.message_duration = seconds
+                    .filter(|seconds| (0.0..=MAX_MESSAGE_DURATION_SECS).contains(seconds))
+                    .map(Duration::from_secs_f64)
+                    .ok_or_else(|| {
+                        format!(
+                            "{key} must be a number of seconds up to {MAX_MESSAGE_DURATION_SECS}"
+                        )
+                    })?;
+            }
+            "color_mode" => {
+                let name = value.as_str().unwrap_or_default();
+                self.color_mode = if name == "auto" {
+                    None
+                } else {
+                    Some(ColorMode::from_name(name).ok_or_else(|| {
+                        format!(
+                            "{key} must be one of \"auto\", \"truecolor\", \"256\", \"16\" or \"none\""
+                        )
+                    })?)
+                };
+            }
+            "theme" => {
+                let name = value
+                    .as_str()
+                    .ok_or_else(|| format!("{key} must be the name of a theme"))?;
+                self.theme = Some(name.to_string());
+            }
+            _ => return Err(format!("Unknown setting '{key}'")),
+        }
+        Ok(())
+    }
+
+    // Each color is applied on its own, so that one typo doesn't lose all.
+    fn apply_colors(&mut self, value: &Value, report: &mut dyn FnMut(String)) {
+        let Some(table) = value.as_table() else {
+            report(String::from("colors must be a table"));
+            return;
+        };
+        for (name, value) in table {
+            let Some(annotation_type) = AnnotationType::from_name(name) else {
+                report(format!("Unknown color '{name}'"));
+                continue;
+            };
+            match parse_attribute(value) {
+                Ok(attribute) => {
+                    self.colors
+                        .retain(|(existing, _)| *existing != annotation_type);
+                    self.colors.push((annotation_type, attribute));
+                }
+                Err(err) => report(format!("colors.{name}: {err}")),
+            }
+        }
+    }
+
+    fn apply_key_bindings(&mut self, value: &Value, report: &mut dyn FnMut(String)) {
+        let Some(table) = value.as_table() else {
+            report(String::from("keys must be a table"));
+            return;
+        };
+        // Different spellings of the same keys within one file, whereas a
+        // later file rebinding keys is an intended override.
+        let mut seen: Vec<(Vec<KeyChord>, &str)> = Vec::new();
+        for (sequence, value) in table {
+            let keys = match parse_key_sequence(sequence) {
+                Ok(keys) => keys,
+                Err(err) => {
+                    report(err);
+                    continue;
+                }
+            };
+            if let Some((_, earlier)) = seen.iter().find(|(seen_keys, _)| *seen_keys == keys) {
+                report(format!("'{earlier}' and '{sequence}' are the same keys"));
+                continue;
+            }
+            let command_name = match value.as_str() {
+                Some("none") => None,
+                Some(name) => {
+                    let Some(name) = Command::canonical_name(name) else {
+                        report(format!("Unknown command '{name}' for '{sequence}'"));
+                        continue;
+                    };
+                    Some(name)
+                }
+                None => {
+                    report(format!("'{sequence}' must be bound to a command name"));
+                    continue;
+                }
+            };
+            seen.push((keys.clone(), sequence));
+            self.key_bindings.push((keys, command_name));
+        }
+    }
+}
+
+fn user_config_dir() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join(CONFIG_DIR_NAME))
+}
+
+fn project_config_path() -> Option<PathBuf> {
+    let current_dir = env::current_dir().ok()?;
+    current_dir
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+fn integer_in<T: TryFrom<i64>>(
+    key: &str,
+    value: &Value,
+    range: RangeInclusive<i64>,
+) -> Result<T, String> {
+    value
+        .as_integer()
+        .filter(|integer| range.contains(integer))
+        .and_then(|integer| T::try_from(integer).ok())
+        .ok_or_else(|| {
+            format!(
+                "{key} must be a whole number from {} to {}",
+                range.start(),
+                range.end()
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::process;
+
+    use crossterm::style::Color;
+
+    use super::*;
+
+    // Applies each of `files` in turn, as if they were the user's and then
+    // the project's, and returns the errors.
+    fn apply(config: &mut Config, name: &str, files: &[&str]) -> Vec<String> {
+        let mut errors = Vec::new();
+        for (index, contents) in files.iter().enumerate() {
+            let path = temp_dir().join(format!("hecto-test-{}-{name}-{index}", process::id()));
+            fs::write(&path, contents).unwrap();
+            config.apply_file(&path, &mut errors);
+            let _ = fs::remove_file(&path);
+        }
+        errors
+    }
+
+    #[test]
+    fn settings_are_read_from_the_file() {
+        let mut config = Config::default();
+        let errors = apply(
+            &mut config,
+            "settings",
+            &["quit_times = 1\ntab_width = 4\nmessage_duration = 2.5\ncolor_mode = \"256\"\ntheme = \"light\"\n\
+               [colors]\nkeyword = \"#ff0000\"\n[keys]\n\"Ctrl+K\" = \"save\"\n\"Ctrl+S\" = \"none\"\n"],
+        );
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(config.quit_times, 1);
+        assert_eq!(config.tab_width, 4);
+        assert_eq!(config.message_duration, Duration::from_millis(2500));
+        assert_eq!(config.color_mode, Some(ColorMode::Ansi256));
+        assert_eq!(config.theme.as_deref(), Some("light"));
+        let (annotation_type, attribute) = config.colors.first().unwrap();
+        assert_eq!(*annotation_type, AnnotationType::Keyword);
+        assert_eq!(
+            attribute.foreground,
+            Some(Color::Rgb { r: 255, g: 0, b: 0 })
+        );
+        let commands: Vec<_> = config.key_bindings.iter().map(|(_, name)| *name).collect();
+        assert_eq!(commands, vec![Some("save"), None]);
+    }
+
+    #[test]
+    fn the_project_file_overrides_the_user_file() {
+        let mut config = Config::default();
+        let errors = apply(
+            &mut config,
+            "merge",
+            &[
+                "quit_times = 5\ntab_width = 8\ncolor_mode = \"16\"\n[colors]\ncomment = \"#00ff00\"\nstring = \"#0000ff\"\n",
+                "tab_width = 2\ncolor_mode = \"auto\"\n[colors]\ncomment = \"#ffffff\"\n",
+            ],
+        );
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(config.quit_times, 5);
+        assert_eq!(config.tab_width, 2);
+        assert_eq!(config.color_mode, None);
+        let colors: Vec<_> = config
+            .colors
+            .iter()
+            .map(|(annotation_type, attribute)| (*annotation_type, attribute.foreground))
+            .collect();
+        assert_eq!(
+            colors,
+            vec![
+                (
+                    AnnotationType::String,
+                    Some(Color::Rgb { r: 0, g: 0, b: 255 })
+                ),
+                (
+                    AnnotationType::Comment,
+                    Some(Color::Rgb {
+                        r: 255,
+                        g: 255,
+                        b: 255
+                    })
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_settings_are_reported_and_skipped() {
+        let mut config = Config::default();
+        let errors = apply(
+            &mut config,
+            "bad",
+            &["quit_times = 0\ntab_width = \"4\"\nmessage_duration = -1\ncolor_mode = \"fancy\"\n\
+               colour = 1\ntheme = 3\n[colors]\nkeyword = \"red\"\nsparkle = \"#ffffff\"\nstring = \"#00ff00\"\n\
+               [keys]\n\"Ctrl+K\" = \"fly\"\n\"Ctrl+Shift+Banana\" = \"save\"\n\"Ctrl+L\" = 1\n"],
+        );
+        assert_eq!(errors.len(), 11, "{errors:?}");
+        assert!(errors
+            .iter()
+            .any(|err| err.ends_with("Unknown setting 'colour'")));
+        assert!(errors
+            .iter()
+            .any(|err| err.ends_with("Unknown command 'fly' for 'Ctrl+K'")));
+        assert_eq!(config.quit_times, Config::default().quit_times);
+        assert_eq!(config.tab_width, Config::default().tab_width);
+        // The valid color still counts.
+        assert_eq!(config.colors.len(), 1);
+        assert!(config.key_bindings.is_empty());
+    }
+
+    #[test]
+    fn the_same_keys_spelled_differently_are_reported() {
+        let mut config = Config::default();
+        let errors = apply(
+            &mut config,
+            "same-keys",
+            &["[keys]\n\"Ctrl+K\" = \"save\"\n\"ctrl+k\" = \"quit\"\n"],
+        );
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert_eq!(config.key_bindings.len(), 1);
+    }
+
+    #[test]
+    fn syntax_errors_name_the_line() {
+        let mut config = Config::default();
+        let errors = apply(
+            &mut config,
+            "syntax",
+            &["tab_width = 2\n\nquit_times = = 1\n"],
+        );
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains(", line 3: "), "{}", errors[0]);
+        assert_eq!(config.tab_width, Config::default().tab_width);
+    }
+
+    #[test]
+    fn missing_files_are_no_error() {
+        let mut config = Config::default();
+        let mut errors = Vec::new();
+        config.apply_file(&temp_dir().join("hecto-test-missing.toml"), &mut errors);
+        assert!(errors.is_empty());
+    }
+}
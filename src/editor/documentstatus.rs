@@ -1,14 +1,20 @@
 use crate::prelude::*;
 
-use super::FileType;
+use super::{Encoding, FileType, LineEnding};
 
 #[derive(Default, Debug, PartialEq)]
 pub struct DocumentStatus {
     pub total_lines: usize,
     pub current_line_index: LineIdx,
     pub is_modified: bool,
+    pub is_read_only: bool,
     pub file_name: String,
     pub file_type: FileType,
+    pub encoding: Encoding,
+    pub line_ending: LineEnding,
+    pub has_final_newline: bool,
+    pub buffer_index: usize,
+    pub buffer_count: usize,
 }
 
 impl DocumentStatus {
@@ -24,6 +30,14 @@ impl DocumentStatus {
         }
     }
 
+    pub fn read_only_indicator_to_string(&self) -> String {
+        if self.is_read_only {
+            String::from(" [RO]")
+        } else {
+            String::new()
+        }
+    }
+
     pub fn position_indicator_to_string(&self) -> String {
         format!(
             "{}/{}",
@@ -32,7 +46,31 @@ impl DocumentStatus {
         )
     }
 
+    pub fn buffer_indicator_to_string(&self) -> String {
+        if self.buffer_count > 1 {
+            format!(
+                "[{}/{}] ",
+                self.buffer_index.saturating_add(1),
+                self.buffer_count
+            )
+        } else {
+            String::new()
+        }
+    }
+
     pub fn file_type_to_string(&self) -> String {
         self.file_type.to_string()
     }
+
+    pub fn encoding_to_string(&self) -> String {
+        self.encoding.to_string()
+    }
+
+    pub fn line_ending_to_string(&self) -> String {
+        if self.has_final_newline {
+            self.line_ending.to_string()
+        } else {
+            format!("{} (noeol)", self.line_ending)
+        }
+    }
 }
@@ -0,0 +1,284 @@
+use std::borrow::Cow;
+use std::fmt::{Display, Write};
+use std::io::{Error, ErrorKind};
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16_LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16_BE_BOM: &[u8] = &[0xFE, 0xFF];
+// How many bytes each line of a hex dump shows.
+const HEX_DUMP_WIDTH: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+    /// Not text at all. The contents are shown as a hex dump, which can't be
+    /// written back.
+    Binary,
+}
+
+impl Encoding {
+    /// Figures out how `bytes` are encoded, and returns them as text. Files
+    /// which aren't valid UTF-8 and don't look binary are taken to be Latin-1,
+    /// as any sequence of bytes is.
+    pub fn decode(bytes: &[u8]) -> (Self, String) {
+        if let Some(text) = bytes
+            .strip_prefix(UTF8_BOM)
+            .and_then(|rest| String::from_utf8(rest.to_vec()).ok())
+        {
+            return (Self::Utf8Bom, text);
+        }
+        if let Some(text) = bytes
+            .strip_prefix(UTF16_LE_BOM)
+            .and_then(|rest| decode_utf16(rest, u16::from_le_bytes))
+        {
+            return (Self::Utf16Le, text);
+        }
+        if let Some(text) = bytes
+            .strip_prefix(UTF16_BE_BOM)
+            .and_then(|rest| decode_utf16(rest, u16::from_be_bytes))
+        {
+            return (Self::Utf16Be, text);
+        }
+        if looks_binary(bytes) {
+            return (Self::Binary, hex_dump(bytes));
+        }
+        match String::from_utf8(bytes.to_vec()) {
+            Ok(text) => (Self::Utf8, text),
+            Err(_) => (
+                Self::Latin1,
+                bytes.iter().copied().map(char::from).collect(),
+            ),
+        }
+    }
+
+    /// Guesses the encoding from the first bytes of a file too large to look
+    /// at as a whole. As the sample may end in the middle of a character, it
+    /// only needs to be valid UTF-8 up to there.
+    pub fn detect(sample: &[u8]) -> Self {
+        if sample.starts_with(UTF8_BOM) {
+            Self::Utf8Bom
+        } else if sample.starts_with(UTF16_LE_BOM) {
+            Self::Utf16Le
+        } else if sample.starts_with(UTF16_BE_BOM) {
+            Self::Utf16Be
+        } else if looks_binary(sample) {
+            Self::Binary
+        } else {
+            match std::str::from_utf8(sample) {
+                Err(err) if err.error_len().is_some() => Self::Latin1,
+                _ => Self::Utf8,
+            }
+        }
+    }
+
+    /// Decodes a single line, for encodings which separate lines by the same
+    /// bytes as ASCII. Returns `None` if the line isn't valid in this encoding.
+    pub fn decode_line(self, bytes: Vec<u8>) -> Option<String> {
+        match self {
+            Self::Utf8 | Self::Utf8Bom => String::from_utf8(bytes).ok(),
+            Self::Latin1 => Some(bytes.into_iter().map(char::from).collect()),
+            Self::Utf16Le | Self::Utf16Be | Self::Binary => None,
+        }
+    }
+
+    /// What to write at the very start of the file.
+    pub const fn bom(self) -> &'static [u8] {
+        match self {
+            Self::Utf8Bom => UTF8_BOM,
+            Self::Utf16Le => UTF16_LE_BOM,
+            Self::Utf16Be => UTF16_BE_BOM,
+            Self::Utf8 | Self::Latin1 | Self::Binary => &[],
+        }
+    }
+
+    pub fn encode(self, text: &str) -> Result<Cow<'_, [u8]>, Error> {
+        match self {
+            Self::Utf8 | Self::Utf8Bom => Ok(Cow::Borrowed(text.as_bytes())),
+            Self::Utf16Le => Ok(Cow::Owned(
+                text.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+            )),
+            Self::Utf16Be => Ok(Cow::Owned(
+                text.encode_utf16().flat_map(u16::to_be_bytes).collect(),
+            )),
+            Self::Latin1 => text
+                .chars()
+                .map(|character| {
+                    u8::try_from(u32::from(character)).map_err(|_| {
+                        Error::new(
+                            ErrorKind::InvalidData,
+                            format!("'{character}' can't be encoded in Latin-1"),
+                        )
+                    })
+                })
+                .collect::<Result<Vec<u8>, Error>>()
+                .map(Cow::Owned),
+            Self::Binary => Err(Error::new(
+                ErrorKind::Unsupported,
+                "Binary files can't be written",
+            )),
+        }
+    }
+}
+
+fn decode_utf16(bytes: &[u8], to_unit: fn([u8; 2]) -> u16) -> Option<String> {
+    if bytes.len().checked_rem(2) != Some(0) {
+        return None;
+    }
+    let units = bytes
+        .chunks_exact(2)
+        .filter_map(|pair| pair.try_into().ok())
+        .map(to_unit);
+    char::decode_utf16(units)
+        .collect::<Result<String, _>>()
+        .ok()
+}
+
+// Text hardly ever contains null bytes, or many control characters other
+// than whitespace.
+fn looks_binary(bytes: &[u8]) -> bool {
+    if bytes.contains(&0) {
+        return true;
+    }
+    let control_count = bytes
+        .iter()
+        .filter(|byte| byte.is_ascii_control() && !b"\t\n\r\x0c\x1b".contains(byte))
+        .count();
+    control_count.saturating_mul(10) > bytes.len()
+}
+
+// Shows every byte as hex, next to its ASCII character where printable.
+fn hex_dump(bytes: &[u8]) -> String {
+    let mut result = String::new();
+    for (index, chunk) in bytes.chunks(HEX_DUMP_WIDTH).enumerate() {
+        if index > 0 {
+            result.push('\n');
+        }
+        let _ = write!(result, "{:08x} ", index.saturating_mul(HEX_DUMP_WIDTH));
+        for column in 0..HEX_DUMP_WIDTH {
+            if column == HEX_DUMP_WIDTH.saturating_div(2) {
+                result.push(' ');
+            }
+            match chunk.get(column) {
+                Some(byte) => {
+                    let _ = write!(result, " {byte:02x}");
+                }
+                None => result.push_str("   "),
+            }
+        }
+        result.push_str("  |");
+        result.extend(chunk.iter().map(|byte| {
+            if byte.is_ascii_graphic() || *byte == b' ' {
+                char::from(*byte)
+            } else {
+                '.'
+            }
+        }));
+        result.push('|');
+    }
+    result
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Utf8 => write!(f, "UTF-8"),
+            Self::Utf8Bom => write!(f, "UTF-8 BOM"),
+            Self::Utf16Le => write!(f, "UTF-16LE"),
+            Self::Utf16Be => write!(f, "UTF-16BE"),
+            Self::Latin1 => write!(f, "Latin-1"),
+            Self::Binary => write!(f, "Binary"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_utf8_with_and_without_bom() {
+        assert_eq!(
+            Encoding::decode("héllo".as_bytes()),
+            (Encoding::Utf8, String::from("héllo"))
+        );
+        assert_eq!(
+            Encoding::decode(b"\xEF\xBB\xBFabc"),
+            (Encoding::Utf8Bom, String::from("abc"))
+        );
+    }
+
+    #[test]
+    fn decodes_utf16_by_its_bom() {
+        assert_eq!(
+            Encoding::decode(b"\xFF\xFEa\x00\n\x00"),
+            (Encoding::Utf16Le, String::from("a\n"))
+        );
+        assert_eq!(
+            Encoding::decode(b"\xFE\xFF\x00a\x00\n"),
+            (Encoding::Utf16Be, String::from("a\n"))
+        );
+    }
+
+    #[test]
+    fn falls_back_to_latin1_for_invalid_utf8() {
+        assert_eq!(
+            Encoding::decode(b"caf\xE9"),
+            (Encoding::Latin1, String::from("café"))
+        );
+        // Odd lengths can't be UTF-16, whatever the start looks like.
+        assert_eq!(
+            Encoding::decode(b"\xFF\xFEa"),
+            (Encoding::Latin1, String::from("ÿþa"))
+        );
+    }
+
+    #[test]
+    fn recognizes_binary_files() {
+        let (encoding, dump) = Encoding::decode(b"ab\x00\x01");
+        assert_eq!(encoding, Encoding::Binary);
+        assert_eq!(
+            dump,
+            "00000000  61 62 00 01                                       |ab..|"
+        );
+        assert_eq!(Encoding::decode(&[1; 20]).0, Encoding::Binary);
+        assert_eq!(Encoding::decode(b"a\tb\r\n\x1b[0m").0, Encoding::Utf8);
+    }
+
+    #[test]
+    fn detects_from_a_sample_cut_off_mid_character() {
+        let sample = "aé".as_bytes();
+        assert_eq!(Encoding::detect(&sample[..2]), Encoding::Utf8);
+        assert_eq!(Encoding::detect(b"caf\xE9 au lait"), Encoding::Latin1);
+        assert_eq!(Encoding::detect(b"\xEF\xBB\xBFa"), Encoding::Utf8Bom);
+        assert_eq!(Encoding::detect(b"\xFE\xFF\x00a"), Encoding::Utf16Be);
+        assert_eq!(Encoding::detect(b"\x00\x00"), Encoding::Binary);
+    }
+
+    #[test]
+    fn decodes_single_lines() {
+        assert_eq!(Encoding::Utf8.decode_line(b"caf\xE9".to_vec()), None);
+        assert_eq!(
+            Encoding::Latin1.decode_line(b"caf\xE9".to_vec()),
+            Some(String::from("café"))
+        );
+    }
+
+    #[test]
+    fn encodes_back_to_the_same_bytes() {
+        for (encoding, bytes) in [
+            (Encoding::Utf8, "é\n".as_bytes()),
+            (Encoding::Utf16Le, b"\xE9\x00\n\x00"),
+            (Encoding::Utf16Be, b"\x00\xE9\x00\n"),
+            (Encoding::Latin1, b"\xE9\n"),
+        ] {
+            assert_eq!(encoding.encode("é\n").ok().as_deref(), Some(bytes));
+        }
+        assert!(Encoding::Latin1.encode("€").is_err());
+        assert!(Encoding::Binary.encode("a").is_err());
+    }
+}
@@ -7,6 +7,16 @@ pub enum FileType {
     Text,
 }
 
+impl FileType {
+    pub const ALL: [Self; 2] = [Self::Rust, Self::Text];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|file_type| file_type.to_string().eq_ignore_ascii_case(name))
+    }
+}
+
 impl Display for FileType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
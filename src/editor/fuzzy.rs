@@ -0,0 +1,77 @@
+/// Returns the indices of all `candidates` which contain the characters of
+/// `query` in order, ignoring case. Closer matches come first: those whose
+/// matched characters start earlier and are less spread out.
+pub fn fuzzy_filter<S: AsRef<str>>(query: &str, candidates: &[S]) -> Vec<usize> {
+    let mut scored: Vec<(usize, usize)> = candidates
+        .iter()
+        .enumerate()
+        .filter_map(|(index, candidate)| {
+            fuzzy_score(query, candidate.as_ref()).map(|score| (score, index))
+        })
+        .collect();
+    // Sorting is stable, so equally good matches keep their original order.
+    scored.sort_by_key(|(score, _)| *score);
+    scored.into_iter().map(|(_, index)| index).collect()
+}
+
+// Lower is better: the number of characters skipped before and between the
+// matched characters.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<usize> {
+    let mut candidate_chars = candidate.chars().flat_map(char::to_lowercase);
+    let mut score: usize = 0;
+    for query_char in query.chars().flat_map(char::to_lowercase) {
+        let skipped = candidate_chars.position(|c| c == query_char)?;
+        score = score.saturating_add(skipped);
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter<'a>(query: &str, candidates: &[&'a str]) -> Vec<&'a str> {
+        fuzzy_filter(query, candidates)
+            .into_iter()
+            .map(|index| candidates[index])
+            .collect()
+    }
+
+    #[test]
+    fn empty_queries_match_everything_in_order() {
+        assert_eq!(filter("", &["b", "a", ""]), vec!["b", "a", ""]);
+        assert!(filter("", &[]).is_empty());
+    }
+
+    #[test]
+    fn candidates_without_the_characters_in_order_are_left_out() {
+        assert!(filter("xyz", &["save", "quit"]).is_empty());
+        assert_eq!(filter("ts", &["save", "tabs", "st"]), vec!["tabs"]);
+        assert_eq!(filter("aa", &["a", "banana"]), vec!["banana"]);
+    }
+
+    #[test]
+    fn closer_matches_come_first() {
+        // Starting earlier beats starting later.
+        assert_eq!(filter("sa", &["unsaved", "save"]), vec!["save", "unsaved"]);
+        // Contiguous beats spread out.
+        assert_eq!(
+            filter("save", &["s_a_v_e", "saveas", "move_save"]),
+            vec!["saveas", "s_a_v_e", "move_save"]
+        );
+    }
+
+    #[test]
+    fn equally_close_matches_keep_their_order() {
+        assert_eq!(
+            filter("go", &["goto", "gold", "go"]),
+            vec!["goto", "gold", "go"]
+        );
+    }
+
+    #[test]
+    fn case_is_ignored() {
+        assert_eq!(filter("SAVE", &["save_as"]), vec!["save_as"]);
+        assert_eq!(filter("éa", &["Éclair", "ea"]), vec!["Éclair"]);
+    }
+}
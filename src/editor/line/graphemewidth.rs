@@ -2,14 +2,13 @@
 pub enum GraphemeWidth {
     Half,
     Full,
+    /// As wide as configured, which is what tabs are.
+    Columns(usize),
 }
 
 impl GraphemeWidth {
     pub fn saturating_add(self, other: usize) -> usize {
-        match self {
-            GraphemeWidth::Half => other.saturating_add(1),
-            GraphemeWidth::Full => other.saturating_add(2),
-        }
+        other.saturating_add(self.into())
     }
 }
 
@@ -18,6 +17,7 @@ impl From<GraphemeWidth> for usize {
         match value {
             GraphemeWidth::Half => 1,
             GraphemeWidth::Full => 2,
+            GraphemeWidth::Columns(columns) => columns,
         }
     }
 }
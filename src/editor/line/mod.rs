@@ -1,7 +1,12 @@
-use crate::{editor::annotation::Annotation, prelude::*};
+use crate::{
+    editor::{annotation::Annotation, SearchPattern},
+    prelude::*,
+};
 use std::{
+    cell::OnceCell,
     fmt,
     ops::{Deref, Range},
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
 use unicode_segmentation::UnicodeSegmentation;
@@ -14,22 +19,78 @@ use textfragment::TextFragment;
 
 use super::annotatedstring::AnnotatedString;
 
+// How many columns a tab takes up. Lines are segmented whenever they're first
+// needed, so this is shared by all of them rather than passed along.
+static TAB_WIDTH: AtomicUsize = AtomicUsize::new(1);
+
 #[derive(Default, Clone)]
 pub struct Line {
-    fragments: Vec<TextFragment>,
+    // Computed on first use, since most lines of a large file are never
+    // displayed or edited.
+    fragments: OnceCell<Vec<TextFragment>>,
     string: String,
+    // Whether the line ends in CRLF rather than the file's usual line ending,
+    // in a file which mixes them. The carriage return isn't part of the text.
+    carriage_return: bool,
 }
 
 impl Line {
     pub fn from(line_str: &str) -> Self {
         debug_assert!(line_str.is_empty() || line_str.lines().count() == 1);
-        let fragments = Self::str_to_fragments(line_str);
         Self {
-            fragments,
+            fragments: OnceCell::new(),
             string: String::from(line_str),
+            carriage_return: false,
         }
     }
 
+    /// Like `from`, for text which doesn't come from a buffer, such as file
+    /// names, and so may hold line breaks. Control characters are shown as
+    /// `▯`, as they are in the buffer.
+    pub fn from_label(text: &str) -> Self {
+        let text: String = text
+            .chars()
+            .map(|ch| if ch.is_control() { '▯' } else { ch })
+            .collect();
+        Self::from(&text)
+    }
+
+    /// Splits `text` at its line breaks, recording which are CRLF rather
+    /// than LF instead of keeping their carriage returns in the lines.
+    pub fn split_text(text: &str) -> impl Iterator<Item = Self> + '_ {
+        let line_break_count = text.matches('\n').count();
+        text.split('\n').enumerate().map(move |(index, line_str)| {
+            match line_str
+                .strip_suffix('\r')
+                .filter(|_| index < line_break_count)
+            {
+                Some(stripped) => Self {
+                    carriage_return: true,
+                    ..Self::from(stripped)
+                },
+                None => Self::from(line_str),
+            }
+        })
+    }
+
+    pub const fn has_carriage_return(&self) -> bool {
+        self.carriage_return
+    }
+
+    pub fn set_carriage_return(&mut self, carriage_return: bool) {
+        self.carriage_return = carriage_return;
+    }
+
+    /// Lines which were already shown need to be reset afterwards.
+    pub fn set_tab_width(width: usize) {
+        TAB_WIDTH.store(width.max(1), Ordering::Relaxed);
+    }
+
+    fn fragments(&self) -> &[TextFragment] {
+        self.fragments
+            .get_or_init(|| Self::str_to_fragments(&self.string))
+    }
+
     fn str_to_fragments(line_str: &str) -> Vec<TextFragment> {
         line_str
             .grapheme_indices(true)
@@ -44,13 +105,20 @@ impl Line {
                             };
                             (None, rendered_width)
                         },
-                        |replacement| (Some(replacement), GraphemeWidth::Half),
+                        |replacement| {
+                            let rendered_width = if grapheme == "\t" {
+                                GraphemeWidth::Columns(TAB_WIDTH.load(Ordering::Relaxed))
+                            } else {
+                                GraphemeWidth::Half
+                            };
+                            (Some(replacement), rendered_width)
+                        },
                     );
                 TextFragment {
-                    grapheme: grapheme.to_string(),
                     rendered_width,
                     replacement,
                     start_byx_idx: byte_idx,
+                    end_byte_idx: byte_idx.saturating_add(grapheme.len()),
                 }
             })
             .collect()
@@ -95,82 +163,150 @@ impl Line {
             }
         }
 
+        // Walk the fragments backwards so that replacing a grapheme doesn't
+        // shift the byte indices of the fragments we have yet to process.
+        let mut fragment_start = self.width();
+        for fragment in self.fragments().iter().rev() {
+            let fragment_end = fragment_start;
+            fragment_start = fragment_start.saturating_sub(fragment.rendered_width.into());
+            let grapheme_end = fragment.end_byte_idx;
+
+            if fragment_start > range.end {
+                continue;
+            }
+            if fragment_start < range.end && fragment_end > range.end {
+                result.replace(fragment.start_byx_idx, self.string.len(), "⋯");
+                continue;
+            } else if fragment_start == range.end {
+                result.truncate_right_from(fragment.start_byx_idx);
+                continue;
+            }
+
+            if fragment_end <= range.start {
+                result.truncate_left_until(grapheme_end);
+                break;
+            } else if fragment_start < range.start && fragment_end > range.start {
+                result.replace(0, grapheme_end, "⋯");
+                break;
+            }
+
+            if let Some(replacement) = fragment.replacement {
+                result.replace(
+                    fragment.start_byx_idx,
+                    grapheme_end,
+                    &replacement
+                        .to_string()
+                        .repeat(fragment.rendered_width.into()),
+                );
+            }
+        }
+
         result
     }
 
-    pub fn find_all(&self, query: &str, range: Range<ByteIdx>) -> Vec<(ByteIdx, GraphemeIdx)> {
-        let end_byte_index = range.end;
-        let start_byte_index = range.start;
-
-        self.string
-            .get(start_byte_index..end_byte_index)
-            .map_or_else(Vec::new, |substr| {
-                substr
-                    .match_indices(query)
-                    .filter_map(|(relative_start_index, _)| {
-                        let absolute_start_index =
-                            relative_start_index.saturating_add(start_byte_index);
-                        self.byte_idx_to_grapheme_idx(absolute_start_index)
-                            .map(|grapheme_idx| (absolute_start_index, grapheme_idx))
-                    })
-                    .collect()
+    // Matches are looked for in the whole line, so that anchors and word
+    // boundaries see the surrounding text, and then limited to `range`.
+    pub fn find_all(
+        &self,
+        pattern: &SearchPattern,
+        range: Range<ByteIdx>,
+    ) -> Vec<(Range<ByteIdx>, GraphemeIdx)> {
+        pattern
+            .find_all(&self.string)
+            .filter(|found| found.start >= range.start && found.end <= range.end)
+            // Matches must not split a grapheme, e.g. "e" must not match the
+            // first half of a decomposed "é".
+            .filter(|found| self.is_grapheme_boundary(found.end))
+            .filter_map(|found| {
+                self.grapheme_idx_at_boundary(found.start)
+                    .map(|grapheme_idx| (found, grapheme_idx))
             })
+            .collect()
+    }
+
+    fn grapheme_idx_at_boundary(&self, byte_index: ByteIdx) -> Option<GraphemeIdx> {
+        self.fragments()
+            .iter()
+            .position(|fragment| fragment.start_byx_idx == byte_index)
+    }
+
+    fn is_grapheme_boundary(&self, byte_index: ByteIdx) -> bool {
+        byte_index == self.string.len() || self.grapheme_idx_at_boundary(byte_index).is_some()
+    }
+
+    /// Splits the line into rows of at most `width` columns, and returns the
+    /// grapheme range of each. Breaks after whitespace where possible if
+    /// `at_words`. Ends with an empty row if the last one is full, so that
+    /// there's room for the caret.
+    pub fn wrap(&self, width: ColIdx, at_words: bool) -> Vec<Range<GraphemeIdx>> {
+        let mut rows = Vec::new();
+        let mut start: GraphemeIdx = 0;
+        let mut row_width: ColIdx = 0;
+        let mut word_break = None;
+        let mut index: GraphemeIdx = 0;
+        while let Some(fragment) = self.fragments().get(index) {
+            let fragment_width: usize = fragment.rendered_width.into();
+            // Every row gets at least one grapheme, even if it doesn't fit.
+            if row_width.saturating_add(fragment_width) > width && index > start {
+                let end = word_break.filter(|_| at_words).unwrap_or(index);
+                rows.push(start..end);
+                start = end;
+                index = end;
+                row_width = 0;
+                word_break = None;
+                continue;
+            }
+            row_width = row_width.saturating_add(fragment_width);
+            index = index.saturating_add(1);
+            let grapheme = &self.string[fragment.start_byx_idx..fragment.end_byte_idx];
+            if grapheme.chars().all(char::is_whitespace) {
+                word_break = Some(index);
+            }
+        }
+        let count = self.grapheme_count();
+        rows.push(start..count);
+        if count > 0 && row_width >= width {
+            rows.push(count..count);
+        }
+        rows
     }
 
     pub fn grapheme_count(&self) -> GraphemeIdx {
-        self.fragments.len()
+        self.fragments().len()
     }
 
     pub fn width_until(&self, grapheme_index: GraphemeIdx) -> GraphemeIdx {
-        self.fragments
+        self.fragments()
             .iter()
             .take(grapheme_index)
-            .map(|fragment| match fragment.rendered_width {
-                GraphemeWidth::Half => 1,
-                GraphemeWidth::Full => 2,
-            })
+            .map(|fragment| usize::from(fragment.rendered_width))
             .sum()
     }
 
     pub fn insert_char(&mut self, character: char, grapheme_index: GraphemeIdx) {
         debug_assert!(grapheme_index.saturating_sub(1) <= self.grapheme_count());
-        if let Some(fragment) = self.fragments.get(grapheme_index) {
+        if let Some(fragment) = self.fragments().get(grapheme_index) {
             self.string.insert(fragment.start_byx_idx, character);
         } else {
             self.string.push(character);
         }
-        self.rebuild_fragments();
+        self.reset_fragments();
     }
 
     pub fn delete(&mut self, grapheme_index: GraphemeIdx) {
         debug_assert!(grapheme_index < self.grapheme_count());
-        if let Some(fragment) = self.fragments.get(grapheme_index) {
+        if let Some(fragment) = self.fragments().get(grapheme_index) {
             let start = fragment.start_byx_idx;
-            let end = fragment
-                .start_byx_idx
-                .saturating_add(fragment.grapheme.len());
+            let end = fragment.end_byte_idx;
             self.string.drain(start..end);
-            self.rebuild_fragments();
+            self.reset_fragments();
         }
     }
 
-    pub fn append(&mut self, other: &Self) {
-        self.string.push_str(&other.to_string());
-        self.rebuild_fragments();
-    }
-
-    pub fn split(&mut self, at: GraphemeIdx) -> Self {
-        if let Some(fragment) = self.fragments.get(at) {
-            let remainder = self.string.split_off(fragment.start_byx_idx);
-            self.rebuild_fragments();
-            Self::from(&remainder)
-        } else {
-            Self::default()
-        }
-    }
-
-    fn rebuild_fragments(&mut self) {
-        self.fragments = Self::str_to_fragments(&self.string);
+    /// Forgets how the line is laid out, so that it's worked out anew, e.g.
+    /// after an edit or for a different tab width.
+    pub fn reset_fragments(&mut self) {
+        self.fragments = OnceCell::new();
     }
 
     pub fn width(&self) -> GraphemeIdx {
@@ -187,53 +323,62 @@ impl Line {
 
     pub fn search_forward(
         &self,
-        query: &str,
+        pattern: &SearchPattern,
         from_grapheme_idx: GraphemeIdx,
     ) -> Option<GraphemeIdx> {
         debug_assert!(from_grapheme_idx <= self.grapheme_count());
-        if from_grapheme_idx == self.grapheme_count() {
-            return None;
-        }
+        // Searching from the start doesn't need the line to be segmented, so
+        // that lines without a match never are.
         let start_byte_idx = self.grapheme_idx_to_byte_idx(from_grapheme_idx);
-        self.find_all(query, start_byte_idx..self.string.len())
+        self.find_all(pattern, start_byte_idx..self.string.len())
             .first()
             .map(|(_, grapheme_index)| *grapheme_index)
     }
 
     pub fn search_backward(
         &self,
-        query: &str,
+        pattern: &SearchPattern,
         from_grapheme_idx: GraphemeIdx,
     ) -> Option<GraphemeIdx> {
         debug_assert!(from_grapheme_idx <= self.grapheme_count());
         if from_grapheme_idx == 0 {
             return None;
         }
-        let end_byte_index = if from_grapheme_idx == self.grapheme_count() {
-            self.string.len()
-        } else {
-            self.grapheme_idx_to_byte_idx(from_grapheme_idx)
-        };
-        self.find_all(query, 0..end_byte_index)
+        if from_grapheme_idx == self.grapheme_count() {
+            return self.search_backward_from_end(pattern);
+        }
+        let end_byte_index = self.grapheme_idx_to_byte_idx(from_grapheme_idx);
+        self.find_all(pattern, 0..end_byte_index)
             .last()
             .map(|(_, grapheme_index)| *grapheme_index)
     }
 
-    fn byte_idx_to_grapheme_idx(&self, byte_index: ByteIdx) -> Option<GraphemeIdx> {
+    /// Like `search_backward` from the end of the line, but without having to
+    /// segment lines which don't match.
+    pub fn search_backward_from_end(&self, pattern: &SearchPattern) -> Option<GraphemeIdx> {
+        self.find_all(pattern, 0..self.string.len())
+            .last()
+            .map(|(_, grapheme_index)| *grapheme_index)
+    }
+
+    pub fn byte_idx_to_grapheme_idx(&self, byte_index: ByteIdx) -> Option<GraphemeIdx> {
         if byte_index > self.string.len() {
             return None;
         }
-        self.fragments
+        self.fragments()
             .iter()
             .position(|fragment| fragment.start_byx_idx >= byte_index)
     }
 
-    fn grapheme_idx_to_byte_idx(&self, grapheme_index: GraphemeIdx) -> ByteIdx {
+    pub fn grapheme_idx_to_byte_idx(&self, grapheme_index: GraphemeIdx) -> ByteIdx {
         debug_assert!(grapheme_index <= self.grapheme_count());
         if grapheme_index == 0 || self.grapheme_count() == 0 {
             return 0;
         }
-        self.fragments.get(grapheme_index).map_or_else(
+        if grapheme_index == self.grapheme_count() {
+            return self.string.len();
+        }
+        self.fragments().get(grapheme_index).map_or_else(
             || {
                 #[cfg(debug_assertions)]
                 {
@@ -262,3 +407,49 @@ impl Deref for Line {
         &self.string
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrap(text: &str, width: ColIdx, at_words: bool) -> Vec<Range<GraphemeIdx>> {
+        Line::from(text).wrap(width, at_words)
+    }
+
+    #[test]
+    fn labels_show_control_characters() {
+        let label = Line::from_label("a\nb\r\tc");
+        assert_eq!(label.get_visible_graphemes(0..10), "a▯b▯▯c");
+    }
+
+    #[test]
+    fn empty_lines_wrap_into_one_empty_row() {
+        assert_eq!(wrap("", 5, false), vec![0..0]);
+        assert_eq!(wrap("", 1, true), vec![0..0]);
+    }
+
+    #[test]
+    fn full_rows_are_followed_by_room_for_the_caret() {
+        assert_eq!(wrap("abcd", 5, false), vec![0..4]);
+        assert_eq!(wrap("abcde", 5, false), vec![0..5, 5..5]);
+        assert_eq!(wrap("abc", 1, false), vec![0..1, 1..2, 2..3, 3..3]);
+    }
+
+    #[test]
+    fn wide_graphemes_move_to_the_next_row_whole() {
+        assert_eq!(wrap("a世b", 2, false), vec![0..1, 1..2, 2..3]);
+        assert_eq!(wrap("ab世", 3, false), vec![0..2, 2..3]);
+        assert_eq!(wrap("ab世", 4, false), vec![0..3, 3..3]);
+        // A grapheme wider than the row still gets one of its own.
+        assert_eq!(wrap("世界", 1, false), vec![0..1, 1..2, 2..2]);
+    }
+
+    #[test]
+    fn lines_wrap_after_whitespace_where_possible() {
+        assert_eq!(wrap("ab cd ef", 5, true), vec![0..3, 3..8, 8..8]);
+        assert_eq!(wrap("ab cd ef", 5, false), vec![0..5, 5..8]);
+        // Words longer than a row are broken anywhere.
+        assert_eq!(wrap("abcdefg", 3, true), vec![0..3, 3..6, 6..7]);
+        assert_eq!(wrap("a 世界", 3, true), vec![0..2, 2..3, 3..4]);
+    }
+}
@@ -0,0 +1,79 @@
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+    /// Some lines end in CRLF and others in LF. Which ones end in CRLF is
+    /// recorded for each line, so that they're written back unchanged.
+    Mixed,
+}
+
+impl LineEnding {
+    pub fn detect(text: &str) -> Self {
+        let crlf_count = text.matches("\r\n").count();
+        if crlf_count == 0 {
+            Self::Lf
+        } else if crlf_count == text.matches('\n').count() {
+            Self::Crlf
+        } else {
+            Self::Mixed
+        }
+    }
+
+    /// What to write between lines.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf | Self::Mixed => "\n",
+            Self::Crlf => "\r\n",
+        }
+    }
+
+    /// The line ending to convert to next. Mixed line endings are unified
+    /// into LF.
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Lf => Self::Crlf,
+            Self::Crlf | Self::Mixed => Self::Lf,
+        }
+    }
+}
+
+impl Display for LineEnding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lf => write!(f, "LF"),
+            Self::Crlf => write!(f, "CRLF"),
+            Self::Mixed => write!(f, "Mixed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_the_line_ending_used() {
+        assert_eq!(LineEnding::detect(""), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a\nb\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a\r\nb\r\n"), LineEnding::Crlf);
+        assert_eq!(LineEnding::detect("a\r\nb"), LineEnding::Crlf);
+        assert_eq!(LineEnding::detect("a\r\nb\nc\r\n"), LineEnding::Mixed);
+    }
+
+    #[test]
+    fn lone_carriage_returns_are_not_line_endings() {
+        assert_eq!(LineEnding::detect("a\rb\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a\r\r\nb\r\n"), LineEnding::Crlf);
+    }
+
+    #[test]
+    fn mixed_line_endings_convert_to_lf() {
+        assert_eq!(LineEnding::Lf.next(), LineEnding::Crlf);
+        assert_eq!(LineEnding::Crlf.next(), LineEnding::Lf);
+        assert_eq!(LineEnding::Mixed.next(), LineEnding::Lf);
+        assert_eq!(LineEnding::Mixed.as_str(), "\n");
+    }
+}
@@ -1,9 +1,13 @@
+use std::collections::HashMap;
 use std::env;
 use std::io::Error;
+use std::mem::{replace, take};
 use std::panic::set_hook;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 use crate::prelude::*;
-use crossterm::event::read;
+use crossterm::event::{poll, read};
 use crossterm::event::{Event, KeyEvent, KeyEventKind};
 
 mod annotation;
@@ -13,24 +17,52 @@ mod annotatedstring;
 pub mod annotationtype;
 pub use annotationtype::AnnotationType;
 mod command;
+mod config;
+use config::{Config, TAB_WIDTH_RANGE};
 mod documentstatus;
+mod encoding;
+use encoding::Encoding;
 mod filetype;
 use filetype::FileType;
+mod fuzzy;
+use fuzzy::fuzzy_filter;
 mod line;
+mod lineending;
+use lineending::LineEnding;
+mod palette;
+use palette::Invocation;
+mod pathcompletion;
+use pathcompletion::{complete_path, expand_home};
+mod searchpattern;
+pub use searchpattern::{SearchOptions, SearchPattern};
 mod terminal;
+mod theme;
+use theme::{parse_attribute, Theme, UiElement, DEFAULT_THEME_NAME};
 mod uicomponents;
 
 use command::{
-    Command::{self, Edit, Move, System},
+    parse_key_sequence,
+    Command::{self, Edit, Move, Select, System},
+    KeyChord, KeyInput, Keymap,
     Move::{Down, Left, Right, Up},
-    System::{Dismiss, Quit, Resize, Save, Search},
+    System::{
+        ClosePane, CommandPalette, ConvertLineEndings, Copy, Cut, CycleLineNumbers, CycleTheme,
+        CycleWrapMode, Dismiss, FocusNextPane, GrowPane, ListBuffers, NextBuffer, Open, Paste,
+        PreviousBuffer, Quit, Reload, Replace, Resize, Save, SaveAs, Search, ShrinkPane,
+        SplitHorizontal, SplitVertical, ToggleIgnoreCase, ToggleRegex, ToggleWholeWord,
+    },
 };
 use documentstatus::DocumentStatus;
 use line::Line;
-use terminal::Terminal;
-use uicomponents::{CommandBar, MessageBar, StatusBar, UIComponent, View};
+use terminal::{Attribute, ColorMode, Terminal};
+pub use uicomponents::{Buffer, Highlighter};
+use uicomponents::{
+    CommandBar, FileBrowser, Layout, MessageBar, PaneId, ReadOnly, SplitDirection, StatusBar,
+    UIComponent, View,
+};
 
-const QUIT_TIMES: u8 = 3;
+// How often the unsaved changes are written to swap files.
+const SWAP_FILE_INTERVAL: Duration = Duration::from_secs(4);
 
 #[derive(Eq, PartialEq, Default)]
 enum PromptType {
@@ -38,6 +70,14 @@ enum PromptType {
     None,
     Save,
     Search,
+    Replace,
+    ReplaceWith,
+    ReplaceConfirm,
+    SwitchBuffer,
+    Open,
+    Browse,
+    Recover,
+    Palette,
 }
 
 impl PromptType {
@@ -46,17 +86,60 @@ impl PromptType {
     }
 }
 
+struct Pane {
+    view: View,
+    status_bar: StatusBar,
+}
+
+// Set on the command line.
+#[derive(Default)]
+struct Options {
+    // Whether saving keeps the previous contents in a `~` file.
+    make_backups: bool,
+    // Whether files are opened for viewing only.
+    read_only: bool,
+}
+
 #[derive(Default)]
 pub struct Editor {
     should_quit: bool,
+    // The view and status bar of the focused pane.
     view: View,
     status_bar: StatusBar,
+    // All open views not shown in any pane; `view` sits at `view_index`
+    // between them.
+    inactive_views: Vec<View>,
+    view_index: usize,
+    layout: Layout,
+    focused_pane: PaneId,
+    unfocused_panes: HashMap<PaneId, Pane>,
     message_bar: MessageBar,
     command_bar: CommandBar,
     prompt_type: PromptType,
     terminal_size: Size,
     title: String,
     quit_times: u8,
+    clipboard: String,
+    replacement: String,
+    replaced_count: usize,
+    buffer_matches: Vec<usize>,
+    selected_buffer_match: usize,
+    palette_matches: Vec<&'static str>,
+    selected_palette_match: usize,
+    file_browser: FileBrowser,
+    options: Options,
+    config: Config,
+    keymap: Keymap,
+    // The built-in and user themes, and which of them is in use.
+    themes: Vec<Theme>,
+    theme_index: usize,
+    // Whether the last command was a save which was refused because the file
+    // changed on disk, so that saving again overwrites it.
+    overwrite_warned: bool,
+    // Buffer list indices of the views whose leftover swap files still need
+    // to be recovered or discarded, and of the view showing the differences.
+    recoveries: Vec<usize>,
+    recovery_diff_view: Option<usize>,
 }
 
 impl Editor {
@@ -68,37 +151,106 @@ impl Editor {
         }));
         Terminal::initialize()?;
         let mut editor = Self::default();
+        let (mut config, mut config_errors) = Config::load();
+        Line::set_tab_width(config.tab_width);
+        editor.message_bar.set_duration(config.message_duration);
+        for (keys, command_name) in &config.key_bindings {
+            editor.keymap.bind(keys.clone(), *command_name);
+        }
+        config_errors.extend(editor.keymap.conflicts());
+        editor.themes = Theme::builtin();
+        for theme in take(&mut config.themes) {
+            // User themes replace built-in ones of the same name.
+            editor.themes.retain(|existing| existing.name != theme.name);
+            editor.themes.push(theme);
+        }
+        let theme_name = config.theme.as_deref().unwrap_or(DEFAULT_THEME_NAME);
+        if let Some(index) = editor.theme_index(theme_name) {
+            editor.theme_index = index;
+        } else {
+            config_errors.push(format!("Unknown theme '{theme_name}'"));
+            editor.theme_index = editor.theme_index(DEFAULT_THEME_NAME).unwrap_or(0);
+        }
+        Terminal::set_color_mode(config.color_mode.unwrap_or_else(ColorMode::detect));
+        editor.config = config;
+        editor.apply_theme();
+        editor.status_bar.set_focused(true);
         let size = Terminal::size().unwrap_or_default();
         editor.handle_resize_command(size);
-        editor.update_message("HELP: Ctrl+F = find | Ctrl+S = save | Ctrl+Q = quit");
-        let args: Vec<String> = env::args().collect();
-        if let Some(file_name) = args.get(1) {
+        editor.update_message(
+            "HELP: Ctrl+F = find | Ctrl+R = replace | Ctrl+S = save | Ctrl+Q = quit",
+        );
+        let args: Vec<String> = env::args().skip(1).collect();
+        // Flags apply to all files, wherever they're given.
+        let is_read_only_flag = |arg: &str| arg == "--readonly" || arg == "-R";
+        editor.options.make_backups = args.iter().any(|arg| arg == "--backup");
+        editor.options.read_only = args.iter().any(|arg| is_read_only_flag(arg));
+        for file_name in args
+            .iter()
+            .filter(|arg| *arg != "--backup" && !is_read_only_flag(arg))
+        {
             debug_assert!(!file_name.is_empty());
-            if editor.view.load(file_name).is_err() {
-                editor.update_message(&format!("ERR: Could not open file: {file_name}"));
+            if let Err(err) = editor.open_file(file_name) {
+                editor.update_message(&format!("ERR: Could not open file {file_name}: {err}"));
             }
         }
+        if let Some(first_error) = config_errors.first() {
+            let more = match config_errors.len().saturating_sub(1) {
+                0 => String::new(),
+                count => format!(" (and {count} more)"),
+            };
+            editor.update_message(&format!("ERR: {first_error}{more}"));
+        }
+        editor.switch_to_view(0);
+        editor.recoveries = editor
+            .all_views()
+            .iter()
+            .enumerate()
+            .filter(|(_, view)| view.has_leftover_swap_file())
+            .map(|(index, _)| index)
+            .collect();
+        editor.prompt_next_recovery();
         editor.refresh_status();
         Ok(editor)
     }
 
     pub fn refresh_status(&mut self) {
-        let status = self.view.get_status();
+        let status = self.status_of(&self.view);
+        let pane_statuses: Vec<(PaneId, DocumentStatus)> = self
+            .unfocused_panes
+            .iter()
+            .map(|(pane, Pane { view, .. })| (*pane, self.status_of(view)))
+            .collect();
         let title = format!("{} - {NAME}", status.file_name);
         self.status_bar.update_status(status);
+        for (pane, status) in pane_statuses {
+            if let Some(pane) = self.unfocused_panes.get_mut(&pane) {
+                pane.status_bar.update_status(status);
+            }
+        }
         if title != self.title && matches!(Terminal::set_title(&title), Ok(())) {
             self.title = title;
         }
     }
 
     pub fn run(&mut self) {
+        let mut last_swap_update = Instant::now();
         loop {
             self.refresh_screen();
             if self.should_quit {
                 break;
             }
-            match read() {
-                Ok(event) => self.evaluate_event(event),
+            // Waits no longer than until the swap files are due, so they're
+            // written even while nothing happens, and not at all while a
+            // search is going on.
+            let timeout = if self.view.is_searching() {
+                Duration::ZERO
+            } else {
+                SWAP_FILE_INTERVAL.saturating_sub(last_swap_update.elapsed())
+            };
+            match poll(timeout).and_then(|ready| ready.then(read).transpose()) {
+                Ok(Some(event)) => self.evaluate_event(event),
+                Ok(None) => {}
                 Err(err) => {
                     #[cfg(debug_assertions)]
                     {
@@ -110,21 +262,65 @@ impl Editor {
                     }
                 }
             }
+            if self.view.is_searching() {
+                self.continue_search();
+            }
+            if last_swap_update.elapsed() >= SWAP_FILE_INTERVAL {
+                self.update_swap_files();
+                last_swap_update = Instant::now();
+            }
             self.refresh_status();
         }
+        for view in self.all_views() {
+            let _ = view.remove_swap_file();
+        }
+    }
+
+    fn update_swap_files(&mut self) {
+        let errors: Vec<Error> = self
+            .all_views()
+            .into_iter()
+            .filter_map(|view| view.update_swap_file().err())
+            .collect();
+        if let Some(err) = errors.first() {
+            self.update_message(&format!("ERR: {err}"));
+        }
     }
 
     #[allow(clippy::needless_pass_by_value)]
     fn evaluate_event(&mut self, event: Event) {
         let should_process = match &event {
             Event::Key(KeyEvent { kind, .. }) => kind == &KeyEventKind::Press,
-            Event::Resize(_, _) => true,
+            Event::Resize(_, _) | Event::Paste(_) => true,
             _ => false,
         };
-        if should_process {
-            if let Ok(command) = Command::try_from(event) {
-                self.process_command(command);
+        if !should_process {
+            return;
+        }
+        let command = if let Event::Key(key_event) = event {
+            let was_pending = self.keymap.is_pending();
+            match self.keymap.resolve(key_event) {
+                KeyInput::Command(command) => {
+                    if was_pending {
+                        self.update_message("");
+                    }
+                    Some(command)
+                }
+                KeyInput::Pending(keys) => {
+                    self.update_message(&format!("{keys} ..."));
+                    None
+                }
+                KeyInput::Unbound(keys) => {
+                    self.update_message(&format!("{keys} isn't bound to anything."));
+                    None
+                }
+                KeyInput::Ignored => None,
             }
+        } else {
+            Command::try_from(event).ok()
+        };
+        if let Some(command) = command {
+            self.process_command(command);
         }
     }
 
@@ -136,14 +332,20 @@ impl Editor {
         match self.prompt_type {
             PromptType::Save => self.process_command_during_save(command),
             PromptType::Search => self.process_command_during_search(command),
+            PromptType::Replace => self.process_command_during_replace(command),
+            PromptType::ReplaceWith => self.process_command_during_replace_with(command),
+            PromptType::ReplaceConfirm => self.process_command_during_replace_confirm(command),
+            PromptType::SwitchBuffer => self.process_command_during_switch_buffer(command),
+            PromptType::Open => self.process_command_during_open(command),
+            PromptType::Browse => self.process_command_during_browse(command),
+            PromptType::Recover => self.process_command_during_recover(command),
+            PromptType::Palette => self.process_command_during_palette(command),
             PromptType::None => self.process_command_no_prompt(command),
         }
     }
 
     fn process_command_during_save(&mut self, command: Command) {
         match command {
-            // Not applicable during save prompt
-            System(Quit | Resize(_) | Save | Search) | Move(_) => {}
             System(Dismiss) => {
                 self.set_prompt(PromptType::None);
                 self.update_message("Save aborted.");
@@ -156,6 +358,8 @@ impl Editor {
             Edit(edit_command) => {
                 self.command_bar.handle_edit_command(edit_command);
             }
+            // Not applicable during save prompt
+            _ => {}
         }
     }
 
@@ -171,24 +375,655 @@ impl Editor {
             }
             Edit(edit_command) => {
                 self.command_bar.handle_edit_command(edit_command);
-                let query = self.command_bar.value();
-                self.view.search(&query);
+                self.search_for_prompt_value();
+            }
+            System(ToggleRegex) => self.toggle_search_option(|options| &mut options.regex),
+            System(ToggleIgnoreCase) => {
+                self.toggle_search_option(|options| &mut options.ignore_case);
+            }
+            System(ToggleWholeWord) => {
+                self.toggle_search_option(|options| &mut options.whole_word);
             }
             Move(Right | Down) => self.view.search_next(),
             Move(Up | Left) => self.view.search_prev(),
             // Not applicable during search prompt
-            System(Quit | Resize(_) | Save | Search) | Move(_) => {}
+            _ => {}
+        }
+    }
+
+    fn continue_search(&mut self) {
+        let progress = self
+            .view
+            .continue_search()
+            .map(|percent| format!("Searching... {percent}%"))
+            .unwrap_or_default();
+        if matches!(self.prompt_type, PromptType::Search | PromptType::Replace) {
+            if progress.is_empty() {
+                self.command_bar.set_hint("");
+            } else {
+                self.command_bar.set_hint(&format!("[{progress}]"));
+            }
+        } else {
+            self.update_message(&progress);
         }
     }
 
+    fn search_for_prompt_value(&mut self) {
+        let query = self.command_bar.value();
+        match self.view.search(&query) {
+            Ok(()) => self.command_bar.set_hint(""),
+            Err(error) => self.command_bar.set_hint(&format!("[{error}]")),
+        }
+    }
+
+    fn toggle_search_option(&mut self, option: fn(&mut SearchOptions) -> &mut bool) {
+        let mut options = self.view.search_options();
+        let value = option(&mut options);
+        *value = !*value;
+        self.view.set_search_options(options);
+        let verb = if self.prompt_type == PromptType::Replace {
+            "Replace"
+        } else {
+            "Search"
+        };
+        self.command_bar.set_prompt(&self.search_prompt(verb));
+        self.search_for_prompt_value();
+    }
+
+    fn search_prompt(&self, verb: &str) -> String {
+        let SearchOptions {
+            regex,
+            ignore_case,
+            whole_word,
+        } = self.view.search_options();
+        let modes: Vec<&str> = [
+            (regex, "regex"),
+            (ignore_case, "ignore case"),
+            (whole_word, "whole word"),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, name)| enabled.then_some(name))
+        .collect();
+        let modes = if modes.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", modes.join(", "))
+        };
+        format!("{verb}{modes} (Esc to cancel, Arrows to navigate, Alt+R/C/W = regex/case/word): ")
+    }
+
+    fn process_command_during_replace(&mut self, command: Command) {
+        match command {
+            Edit(command::Edit::InsertNewLine) => {
+                if self.command_bar.value().is_empty() {
+                    self.set_prompt(PromptType::None);
+                    self.view.dismiss_search();
+                } else {
+                    self.set_prompt(PromptType::ReplaceWith);
+                }
+            }
+            // Everything else behaves just like searching
+            _ => self.process_command_during_search(command),
+        }
+    }
+
+    fn process_command_during_replace_with(&mut self, command: Command) {
+        match command {
+            System(Dismiss) => {
+                self.set_prompt(PromptType::None);
+                self.view.dismiss_search();
+                self.update_message("Replace aborted.");
+            }
+            Edit(command::Edit::InsertNewLine) => {
+                self.replacement = self.command_bar.value();
+                self.replaced_count = 0;
+                if self.view.start_replace() {
+                    self.set_prompt(PromptType::ReplaceConfirm);
+                } else {
+                    self.finish_replace();
+                }
+            }
+            Edit(edit_command) => {
+                self.command_bar.handle_edit_command(edit_command);
+            }
+            // Not applicable during replace prompt
+            _ => {}
+        }
+    }
+
+    #[allow(clippy::needless_pass_by_value)]
+    fn process_command_during_replace_confirm(&mut self, command: Command) {
+        match command {
+            Edit(command::Edit::Insert('y' | 'Y')) => {
+                let has_next = if self.view.replace_current_match(&self.replacement) {
+                    self.replaced_count = self.replaced_count.saturating_add(1);
+                    self.view.find_next_replace_match()
+                } else {
+                    self.view.skip_current_match()
+                };
+                if !has_next {
+                    self.finish_replace();
+                }
+            }
+            Edit(command::Edit::Insert('n' | 'N')) => {
+                let has_next = self.view.skip_current_match();
+                if !has_next {
+                    self.finish_replace();
+                }
+            }
+            Edit(command::Edit::Insert('a' | 'A')) => {
+                let count = self.view.replace_remaining_matches(&self.replacement);
+                self.replaced_count = self.replaced_count.saturating_add(count);
+                self.finish_replace();
+            }
+            Edit(command::Edit::Insert('q' | 'Q')) | System(Dismiss) => self.finish_replace(),
+            // Not applicable while confirming replacements
+            _ => {}
+        }
+    }
+
+    fn process_command_during_switch_buffer(&mut self, command: Command) {
+        match command {
+            System(Dismiss) => self.set_prompt(PromptType::None),
+            Edit(command::Edit::InsertNewLine) => {
+                let index = self.buffer_matches.get(self.selected_buffer_match).copied();
+                self.set_prompt(PromptType::None);
+                match index {
+                    Some(index) if index < self.view_count() => self.switch_to_view(index),
+                    Some(index) => {
+                        let pane = self
+                            .unfocused_pane_ids()
+                            .get(index.saturating_sub(self.view_count()))
+                            .copied();
+                        if let Some(pane) = pane {
+                            self.focus_pane(pane);
+                        }
+                    }
+                    None => self.update_message("No matching buffer."),
+                }
+            }
+            Edit(edit_command) => {
+                self.command_bar.handle_edit_command(edit_command);
+                self.update_buffer_matches();
+            }
+            Move(Right | Down) | System(ListBuffers | NextBuffer) => {
+                self.select_buffer_match(true);
+            }
+            Move(Up | Left) | System(PreviousBuffer) => self.select_buffer_match(false),
+            // Not applicable while switching buffers
+            _ => {}
+        }
+    }
+
+    fn update_buffer_matches(&mut self) {
+        let statuses = self.view_statuses();
+        let listed = self.listed_views();
+        let names: Vec<&str> = listed
+            .iter()
+            .filter_map(|index| statuses.get(*index))
+            .map(|status| status.file_name.as_str())
+            .collect();
+        self.buffer_matches = fuzzy_filter(&self.command_bar.value(), &names)
+            .into_iter()
+            .filter_map(|position| listed.get(position).copied())
+            .collect();
+        self.selected_buffer_match = 0;
+        self.show_buffer_matches(&statuses);
+    }
+
+    fn select_buffer_match(&mut self, forward: bool) {
+        let count = self.buffer_matches.len();
+        let index = if forward {
+            self.selected_buffer_match.saturating_add(1)
+        } else {
+            self.selected_buffer_match
+                .saturating_add(count)
+                .saturating_sub(1)
+        };
+        self.selected_buffer_match = index.checked_rem(count).unwrap_or(0);
+        self.show_buffer_matches(&self.view_statuses());
+    }
+
+    fn show_buffer_matches(&mut self, statuses: &[DocumentStatus]) {
+        let names: Vec<String> = self
+            .buffer_matches
+            .iter()
+            .enumerate()
+            .filter_map(|(position, index)| {
+                let status = statuses.get(*index)?;
+                let modified = if status.is_modified { "*" } else { "" };
+                let name = format!("{}{modified}", status.file_name);
+                Some(if position == self.selected_buffer_match {
+                    format!("[{name}]")
+                } else {
+                    name
+                })
+            })
+            .collect();
+        if names.is_empty() {
+            self.command_bar.set_hint("[no match]");
+        } else {
+            self.command_bar.set_hint(&names.join(" "));
+        }
+    }
+
+    fn process_command_during_open(&mut self, command: Command) {
+        match command {
+            System(Dismiss) => self.set_prompt(PromptType::None),
+            Edit(command::Edit::Insert('\t')) => self.complete_open_path(),
+            Edit(command::Edit::InsertNewLine) => {
+                let input = self.command_bar.value();
+                self.set_prompt(PromptType::None);
+                if !input.is_empty() {
+                    self.open_path(&input);
+                }
+            }
+            Edit(edit_command) => {
+                self.command_bar.handle_edit_command(edit_command);
+                self.command_bar.set_hint("");
+            }
+            // Not applicable during open prompt
+            _ => {}
+        }
+    }
+
+    fn complete_open_path(&mut self) {
+        let completion = complete_path(&self.command_bar.value());
+        self.command_bar.clear_value();
+        self.command_bar
+            .handle_edit_command(command::Edit::InsertText(completion.completed));
+        if completion.candidates.len() > 1 {
+            self.command_bar.set_hint(&completion.candidates.join(" "));
+        } else if completion.candidates.is_empty() {
+            self.command_bar.set_hint("[no match]");
+        }
+    }
+
+    fn open_path(&mut self, input: &str) {
+        let path = expand_home(input);
+        if path.is_dir() {
+            self.browse(&path);
+        } else if let Err(err) = self.open_file(&path.to_string_lossy()) {
+            self.update_message(&format!("ERR: Could not open file {input}: {err}"));
+        }
+    }
+
+    fn browse(&mut self, directory: &Path) {
+        if self.file_browser.open(directory).is_err() {
+            self.update_message(&format!(
+                "ERR: Could not read directory: {}",
+                directory.display()
+            ));
+            return;
+        }
+        if self.prompt_type != PromptType::Browse {
+            self.set_prompt(PromptType::Browse);
+            self.file_browser.resize(self.view_size());
+        }
+        self.command_bar
+            .set_hint(&self.file_browser.directory().display().to_string());
+    }
+
+    fn close_browser(&mut self) {
+        self.set_prompt(PromptType::None);
+        self.view.mark_redraw(true);
+    }
+
+    #[allow(clippy::needless_pass_by_value)]
+    fn process_command_during_browse(&mut self, command: Command) {
+        match command {
+            System(Dismiss) => self.close_browser(),
+            Edit(command::Edit::InsertNewLine) | Move(Right) => {
+                let Some((path, is_dir)) = self
+                    .file_browser
+                    .selected_entry()
+                    .map(|(path, is_dir)| (path.to_path_buf(), is_dir))
+                else {
+                    return;
+                };
+                if is_dir {
+                    self.browse(&path);
+                } else {
+                    self.close_browser();
+                    self.open_path(&path.to_string_lossy());
+                }
+            }
+            Edit(command::Edit::Backspace) | Move(Left) => {
+                let parent = self
+                    .file_browser
+                    .directory()
+                    .parent()
+                    .map(Path::to_path_buf);
+                if let Some(parent) = parent {
+                    self.browse(&parent);
+                }
+            }
+            Move(move_command) => self.file_browser.handle_move_command(move_command),
+            // Not applicable while browsing
+            _ => {}
+        }
+    }
+
+    #[allow(clippy::needless_pass_by_value)]
+    fn process_command_during_recover(&mut self, command: Command) {
+        match command {
+            Edit(command::Edit::Insert('r' | 'R')) => {
+                self.close_recovery_diff();
+                let name = self.view.get_status().file_name;
+                match self.view.recover_from_swap_file() {
+                    Ok(()) => self.finish_recovery(&format!(
+                        "Recovered unsaved changes to {name}. Save to keep them."
+                    )),
+                    Err(err) => self.finish_recovery(&format!("ERR: Could not recover: {err}")),
+                }
+            }
+            Edit(command::Edit::Insert('d' | 'D')) => {
+                if self.recovery_diff_view.is_some() {
+                    self.close_recovery_diff();
+                } else {
+                    self.show_recovery_diff();
+                }
+            }
+            Edit(command::Edit::Insert('x' | 'X')) => {
+                self.close_recovery_diff();
+                let name = self.view.get_status().file_name;
+                match self.view.discard_swap_file() {
+                    Ok(()) => {
+                        self.finish_recovery(&format!("Discarded unsaved changes to {name}."));
+                    }
+                    Err(err) => self.finish_recovery(&format!("ERR: {err}")),
+                }
+            }
+            System(Dismiss) => {
+                self.close_recovery_diff();
+                let name = self.view.get_status().file_name;
+                self.finish_recovery(&format!("Kept the swap file of {name} for later."));
+            }
+            Move(move_command) => self.view.handle_move_command(move_command),
+            // Not applicable while recovering
+            _ => {}
+        }
+    }
+
+    fn prompt_next_recovery(&mut self) {
+        let Some(index) = self.recoveries.first().copied() else {
+            self.set_prompt(PromptType::None);
+            return;
+        };
+        self.switch_to_view(index);
+        self.set_prompt(PromptType::Recover);
+    }
+
+    fn finish_recovery(&mut self, message: &str) {
+        if !self.recoveries.is_empty() {
+            self.recoveries.remove(0);
+        }
+        self.update_message(message);
+        self.prompt_next_recovery();
+    }
+
+    fn show_recovery_diff(&mut self) {
+        match self.view.diff_against_swap_file() {
+            Ok(diff) => {
+                self.inactive_views.push(View::from_text(&diff));
+                let index = self.view_count().saturating_sub(1);
+                self.switch_to_view(index);
+                self.recovery_diff_view = Some(index);
+            }
+            Err(err) => self.command_bar.set_hint(&format!("[ERR: {err}]")),
+        }
+    }
+
+    fn close_recovery_diff(&mut self) {
+        let Some(diff_index) = self.recovery_diff_view.take() else {
+            return;
+        };
+        if let Some(index) = self.recoveries.first().copied() {
+            self.switch_to_view(index);
+        }
+        if diff_index != self.view_index {
+            self.remove_inactive_view(diff_index);
+        }
+    }
+
+    fn process_command_during_palette(&mut self, command: Command) {
+        match command {
+            System(Dismiss) => self.set_prompt(PromptType::None),
+            Edit(command::Edit::Insert('\t')) => self.complete_palette_command(),
+            Edit(command::Edit::InsertNewLine) => {
+                let input = self.palette_input();
+                self.set_prompt(PromptType::None);
+                match palette::parse(&input) {
+                    Ok(invocation) => self.run_palette_command(invocation),
+                    Err(err) => self.update_message(&format!("ERR: {err}")),
+                }
+            }
+            Edit(edit_command) => {
+                self.command_bar.handle_edit_command(edit_command);
+                self.update_palette_matches();
+            }
+            Move(Right | Down) => self.select_palette_match(true),
+            Move(Up | Left) => self.select_palette_match(false),
+            // Not applicable in the command palette
+            _ => {}
+        }
+    }
+
+    // Splits the palette's input into the command name and its arguments,
+    // if any have been started.
+    fn palette_command_name(&self) -> (String, Option<String>) {
+        let value = self.command_bar.value();
+        let value = value.trim_start();
+        match value.split_once(char::is_whitespace) {
+            Some((name, arguments)) => (name.to_string(), Some(arguments.to_string())),
+            None => (value.to_string(), None),
+        }
+    }
+
+    // The input with the selected match in place of a command name which
+    // isn't complete.
+    fn palette_input(&self) -> String {
+        let (name, arguments) = self.palette_command_name();
+        let selected = self.palette_matches.get(self.selected_palette_match);
+        let name = match selected {
+            Some(selected) if !palette::command_names().contains(&name.as_str()) => {
+                (*selected).to_string()
+            }
+            _ => name,
+        };
+        match arguments {
+            Some(arguments) => format!("{name} {arguments}"),
+            None => name,
+        }
+    }
+
+    fn complete_palette_command(&mut self) {
+        let (_, arguments) = self.palette_command_name();
+        let Some(selected) = self.palette_matches.get(self.selected_palette_match) else {
+            return;
+        };
+        if arguments.is_some() {
+            return;
+        }
+        let completed = format!("{selected} ");
+        self.command_bar.clear_value();
+        self.command_bar
+            .handle_edit_command(command::Edit::InsertText(completed));
+        self.update_palette_matches();
+    }
+
+    fn update_palette_matches(&mut self) {
+        let (name, arguments) = self.palette_command_name();
+        if arguments.is_some() {
+            self.palette_matches.clear();
+        } else {
+            let names = palette::command_names();
+            self.palette_matches = fuzzy_filter(&name, &names)
+                .into_iter()
+                .filter_map(|index| names.get(index).copied())
+                .collect();
+        }
+        self.selected_palette_match = 0;
+        self.show_palette_matches();
+    }
+
+    fn select_palette_match(&mut self, forward: bool) {
+        let count = self.palette_matches.len();
+        let index = if forward {
+            self.selected_palette_match.saturating_add(1)
+        } else {
+            self.selected_palette_match
+                .saturating_add(count)
+                .saturating_sub(1)
+        };
+        self.selected_palette_match = index.checked_rem(count).unwrap_or(0);
+        self.show_palette_matches();
+    }
+
+    // While the name is typed, lists the commands it matches, and then how
+    // to use the command once it's complete.
+    fn show_palette_matches(&mut self) {
+        let (name, arguments) = self.palette_command_name();
+        if arguments.is_some() {
+            let hint = palette::usage(&name).unwrap_or_else(|| format!("Unknown command '{name}'"));
+            self.command_bar.set_hint(&hint);
+            return;
+        }
+        // Shows the selected match first, so it's never cut off.
+        let names: Vec<String> = self
+            .palette_matches
+            .iter()
+            .enumerate()
+            .skip(self.selected_palette_match)
+            .chain(
+                self.palette_matches
+                    .iter()
+                    .enumerate()
+                    .take(self.selected_palette_match),
+            )
+            .map(|(position, name)| {
+                if position == self.selected_palette_match {
+                    format!("[{name}]")
+                } else {
+                    (*name).to_string()
+                }
+            })
+            .collect();
+        if names.is_empty() {
+            self.command_bar.set_hint("[no match]");
+        } else {
+            self.command_bar.set_hint(&names.join(" "));
+        }
+    }
+
+    fn run_palette_command(&mut self, invocation: Invocation) {
+        match invocation {
+            Invocation::GoTo(line_index) => self.view.go_to_line(line_index),
+            Invocation::SetTabWidth(width) => {
+                Line::set_tab_width(width);
+                self.view.reset_line_layout();
+                for view in &mut self.inactive_views {
+                    view.reset_line_layout();
+                }
+                for pane in self.unfocused_panes.values_mut() {
+                    pane.view.reset_line_layout();
+                }
+                self.update_message(&format!("Tab width: {width}"));
+            }
+            Invocation::SetTheme(name) => {
+                if let Some(index) = self.theme_index(&name) {
+                    self.theme_index = index;
+                    self.apply_theme();
+                    self.update_message(&format!("Theme: {name}"));
+                } else {
+                    self.update_message(&format!("ERR: Unknown theme '{name}'"));
+                }
+            }
+            Invocation::SaveAs(file_name) => match self.view.read_only() {
+                Some(reason @ ReadOnly::Binary) => {
+                    self.update_message(&format!("Can't save: {reason}."));
+                }
+                _ => self.save(Some(&file_name)),
+            },
+            Invocation::SetFileType(file_type) => {
+                self.view.set_file_type(file_type);
+                self.update_message(&format!("File type: {file_type}"));
+            }
+            Invocation::Help => self.show_help(),
+            Invocation::Run(command) => self.process_command_no_prompt(command),
+        }
+    }
+
+    // Opens a buffer listing all commands the palette offers.
+    fn show_help(&mut self) {
+        let usages = palette::help_lines();
+        let width = usages
+            .iter()
+            .map(|(usage, _)| usage.len())
+            .chain(Command::NAMED.iter().map(|(name, _)| name.len()))
+            .max()
+            .unwrap_or(0);
+        let mut lines = vec![String::from("Commands (Ctrl+P to run one):"), String::new()];
+        lines.extend(
+            usages
+                .iter()
+                .map(|(usage, description)| format!("  {usage:<width$}  {description}")),
+        );
+        lines.push(String::new());
+        lines.push(String::from("Commands which can be bound to keys:"));
+        lines.push(String::new());
+        lines.extend(Command::NAMED.iter().map(|(name, _)| {
+            let keys = self.keymap.keys_for(name).join(", ");
+            format!("  {name:<width$}  {keys}").trim_end().to_string()
+        }));
+        self.inactive_views.push(View::from_text(&lines.join("\n")));
+        self.switch_to_view(self.view_count().saturating_sub(1));
+    }
+
+    fn finish_replace(&mut self) {
+        self.set_prompt(PromptType::None);
+        self.view.exit_search();
+        let message = match self.replaced_count {
+            0 => String::from("No occurrences replaced."),
+            1 => String::from("Replaced 1 occurrence."),
+            count => format!("Replaced {count} occurrences."),
+        };
+        self.update_message(&message);
+    }
+
     fn set_prompt(&mut self, prompt_type: PromptType) {
         match prompt_type {
             PromptType::Save => self.command_bar.set_prompt("Save as: "),
             PromptType::Search => {
                 self.view.enter_search();
-                self.command_bar
-                    .set_prompt("Search (Esc to cancel, Arrows to navigate): ");
+                self.command_bar.set_prompt(&self.search_prompt("Search"));
+            }
+            PromptType::Replace => {
+                self.view.enter_search();
+                self.command_bar.set_prompt(&self.search_prompt("Replace"));
+            }
+            PromptType::ReplaceWith => self.command_bar.set_prompt("Replace with: "),
+            PromptType::ReplaceConfirm => self
+                .command_bar
+                .set_prompt("Replace this match? (y)es, (n)o, (a)ll, (q)uit: "),
+            PromptType::SwitchBuffer => self
+                .command_bar
+                .set_prompt("Switch to buffer (Esc to cancel, Arrows to select): "),
+            PromptType::Open => self
+                .command_bar
+                .set_prompt("Open (Esc to cancel, Tab to complete): "),
+            PromptType::Browse => self
+                .command_bar
+                .set_prompt("Browse (Enter to open, Backspace to go up, Esc to cancel): "),
+            PromptType::Recover => {
+                let name = self.view.get_status().file_name;
+                self.command_bar.set_prompt(&format!(
+                    "Unsaved changes to {name} found: (r)ecover, (d)iff, (x) discard, Esc = later: "
+                ));
             }
+            PromptType::Palette => self
+                .command_bar
+                .set_prompt("Command (Tab to complete, Arrows to select): "),
             PromptType::None => self.message_bar.mark_redraw(true),
         }
         self.command_bar.clear_value();
@@ -201,29 +1036,359 @@ impl Editor {
             return;
         }
         self.reset_quit_times();
+        let overwrite = take(&mut self.overwrite_warned) && matches!(command, System(Save));
+        if let Some(reason) = self.view.read_only() {
+            if matches!(
+                command,
+                Edit(_) | System(Cut | Paste | Replace | ConvertLineEndings)
+            ) {
+                self.update_message(&format!("Can't edit: {reason}."));
+                return;
+            }
+        }
 
         match command {
-            System(Quit | Resize(_) | Dismiss) => {} // already handled or not applicable
+            System(
+                Quit | Resize(_) | Dismiss | ToggleRegex | ToggleIgnoreCase | ToggleWholeWord,
+            ) => {} // already handled or not applicable
             System(Search) => self.set_prompt(PromptType::Search),
-            System(Save) => self.handle_save(),
+            System(Replace) => self.set_prompt(PromptType::Replace),
+            System(ListBuffers) => {
+                self.set_prompt(PromptType::SwitchBuffer);
+                self.update_buffer_matches();
+            }
+            System(NextBuffer) => self.cycle_view(true),
+            System(PreviousBuffer) => self.cycle_view(false),
+            System(SplitHorizontal) => self.split_pane(SplitDirection::Horizontal),
+            System(SplitVertical) => self.split_pane(SplitDirection::Vertical),
+            System(FocusNextPane) => self.focus_next_pane(),
+            System(GrowPane) => self.resize_pane(true),
+            System(ShrinkPane) => self.resize_pane(false),
+            System(ClosePane) => self.close_pane(),
+            System(CycleWrapMode) => {
+                let wrap_mode = self.view.cycle_wrap_mode();
+                self.update_message(&format!("Soft wrap: {wrap_mode}"));
+            }
+            System(CycleLineNumbers) => {
+                let line_numbers = self.view.cycle_line_numbers();
+                self.update_message(&format!("Line numbers: {line_numbers}"));
+            }
+            System(ConvertLineEndings) => {
+                let line_ending = self.view.convert_line_endings();
+                self.update_message(&format!("Line endings: {line_ending}"));
+            }
+            System(CycleTheme) => {
+                self.theme_index = self
+                    .theme_index
+                    .saturating_add(1)
+                    .checked_rem(self.themes.len())
+                    .unwrap_or(0);
+                self.apply_theme();
+                if let Some(theme) = self.themes.get(self.theme_index) {
+                    self.update_message(&format!("Theme: {}", theme.name));
+                }
+            }
+            System(Save) => self.handle_save(overwrite),
+            System(SaveAs) => self.handle_save_as(),
+            System(Reload) => self.reload(),
+            System(Open) => self.set_prompt(PromptType::Open),
+            System(CommandPalette) => {
+                self.set_prompt(PromptType::Palette);
+                self.update_palette_matches();
+            }
+            System(Cut) => {
+                if let Some(text) = self.view.cut() {
+                    self.clipboard = text;
+                }
+            }
+            System(Copy) => {
+                if let Some(text) = self.view.copy() {
+                    self.clipboard = text;
+                }
+            }
+            System(Paste) => {
+                if !self.clipboard.is_empty() {
+                    self.view.insert_text(&self.clipboard);
+                }
+            }
             Edit(edit_command) => self.view.handle_edit_command(edit_command),
             Move(move_command) => self.view.handle_move_command(move_command),
+            Select(move_command) => self.view.handle_select_command(move_command),
         }
     }
 
     #[allow(clippy::arithmetic_side_effects)]
     fn handle_quit_command(&mut self) {
-        if !self.view.get_status().is_modified || self.quit_times + 1 == QUIT_TIMES {
+        let views = self.all_views();
+        let modified_count = views
+            .iter()
+            .enumerate()
+            // Count buffers shown in several views only once.
+            .filter(|(index, view)| {
+                !views
+                    .iter()
+                    .take(*index)
+                    .any(|other| other.shares_buffer_with(view))
+            })
+            .filter(|(_, view)| view.get_status().is_modified)
+            .count();
+        if modified_count == 0 || self.quit_times + 1 >= self.config.quit_times {
             self.should_quit = true;
-        } else if self.view.get_status().is_modified {
+        } else {
+            let files = if modified_count > 1 {
+                format!("{modified_count} files have")
+            } else if self.view.get_status().is_modified {
+                String::from("File has")
+            } else {
+                String::from("Another file has")
+            };
             self.update_message(&format!(
-                "WARNING! File has unsaved changes. Press Ctrl+Q {} more times to quit.",
-                QUIT_TIMES - self.quit_times - 1
+                "WARNING! {files} unsaved changes. Press Ctrl+Q {} more times to quit.",
+                self.config.quit_times - self.quit_times - 1
             ));
             self.quit_times += 1;
         }
     }
 
+    fn view_count(&self) -> usize {
+        self.inactive_views.len().saturating_add(1)
+    }
+
+    /// Returns every open view in buffer list order: first those which can
+    /// be switched to in the focused pane, then those of the other panes.
+    fn all_views(&self) -> Vec<&View> {
+        let mut views: Vec<&View> = self.inactive_views.iter().collect();
+        views.insert(self.view_index, &self.view);
+        views.extend(
+            self.unfocused_pane_ids()
+                .iter()
+                .filter_map(|pane| self.unfocused_panes.get(pane))
+                .map(|pane| &pane.view),
+        );
+        views
+    }
+
+    // Returns the indices in `all_views` of the views in the buffer list. A
+    // buffer shown in several panes is listed once, for the first.
+    fn listed_views(&self) -> Vec<usize> {
+        let all_views = self.all_views();
+        (0..all_views.len())
+            .filter(|index| {
+                all_views.iter().take(*index).all(|other| {
+                    all_views
+                        .get(*index)
+                        .is_some_and(|view| !view.shares_buffer_with(other))
+                })
+            })
+            .collect()
+    }
+
+    // The status of `view`, along with where its buffer is in the buffer list.
+    fn status_of(&self, view: &View) -> DocumentStatus {
+        let all_views = self.all_views();
+        let listed = self.listed_views();
+        let mut status = view.get_status();
+        status.buffer_index = listed
+            .iter()
+            .position(|index| {
+                all_views
+                    .get(*index)
+                    .is_some_and(|listed_view| listed_view.shares_buffer_with(view))
+            })
+            .unwrap_or(0);
+        status.buffer_count = listed.len();
+        status
+    }
+
+    fn view_statuses(&self) -> Vec<DocumentStatus> {
+        self.all_views().into_iter().map(View::get_status).collect()
+    }
+
+    fn open_file(&mut self, file_name: &str) -> Result<(), Error> {
+        let mut view = View::default();
+        view.load(file_name)?;
+        if self.options.read_only {
+            view.set_read_only();
+        }
+        if view.is_large_file() {
+            self.update_message("Large file: syntax highlighting is off.");
+        }
+        let status = self.view.get_status();
+        if !self.view.is_file_loaded() && !status.is_modified && status.total_lines == 0 {
+            // Replace the empty buffer the editor starts out with.
+            view.resize(self.view_size());
+            self.view = view;
+        } else {
+            self.inactive_views.push(view);
+            self.switch_to_view(self.view_count().saturating_sub(1));
+        }
+        Ok(())
+    }
+
+    fn switch_to_view(&mut self, index: usize) {
+        if index == self.view_index || index >= self.view_count() {
+            return;
+        }
+        let previous_index = self.view_index;
+        let mut views = take(&mut self.inactive_views);
+        views.insert(self.view_index, take(&mut self.view));
+        self.view = views.remove(index);
+        self.inactive_views = views;
+        self.view_index = index;
+        self.view.resize(self.view_size());
+        // The view switched away from isn't needed to get back to its buffer
+        // if another view on it remains, e.g. in another pane.
+        let all_views = self.all_views();
+        let is_redundant = all_views.get(previous_index).is_some_and(|previous| {
+            all_views
+                .iter()
+                .enumerate()
+                .any(|(index, view)| index != previous_index && view.shares_buffer_with(previous))
+        });
+        if is_redundant {
+            self.remove_inactive_view(previous_index);
+        }
+    }
+
+    // Closes a view in the focused pane's buffer list other than the shown one.
+    fn remove_inactive_view(&mut self, index: usize) {
+        debug_assert!(index != self.view_index);
+        let inactive_index = if index > self.view_index {
+            index.saturating_sub(1)
+        } else {
+            index
+        };
+        if inactive_index < self.inactive_views.len() {
+            self.inactive_views.remove(inactive_index);
+        }
+        if index < self.view_index {
+            self.view_index = self.view_index.saturating_sub(1);
+        }
+    }
+
+    fn unfocused_pane_ids(&self) -> Vec<PaneId> {
+        self.layout
+            .panes()
+            .into_iter()
+            .filter(|pane| *pane != self.focused_pane)
+            .collect()
+    }
+
+    fn focus_pane(&mut self, pane: PaneId) {
+        let Some(Pane { view, status_bar }) = self.unfocused_panes.remove(&pane) else {
+            return;
+        };
+        self.view.blur();
+        self.status_bar.set_focused(false);
+        let previous = Pane {
+            view: replace(&mut self.view, view),
+            status_bar: replace(&mut self.status_bar, status_bar),
+        };
+        self.unfocused_panes.insert(self.focused_pane, previous);
+        self.focused_pane = pane;
+        self.view.focus();
+        self.status_bar.set_focused(true);
+    }
+
+    fn focus_next_pane(&mut self) {
+        self.focus_pane(self.layout.next_pane(self.focused_pane));
+    }
+
+    fn split_pane(&mut self, direction: SplitDirection) {
+        let new_pane = self.layout.split(self.focused_pane, direction);
+        self.unfocused_panes.insert(
+            new_pane,
+            Pane {
+                view: self.view.split(),
+                status_bar: StatusBar::default(),
+            },
+        );
+        self.focus_pane(new_pane);
+        self.apply_layout();
+    }
+
+    fn resize_pane(&mut self, grow: bool) {
+        if self.layout.resize(self.focused_pane, grow) {
+            self.apply_layout();
+        }
+    }
+
+    fn close_pane(&mut self) {
+        let Some(next) = self.layout.close(self.focused_pane) else {
+            self.update_message("Cannot close the last pane.");
+            return;
+        };
+        let Some(Pane { view, status_bar }) = self.unfocused_panes.remove(&next) else {
+            return;
+        };
+        let closed = replace(&mut self.view, view);
+        self.status_bar = status_bar;
+        self.focused_pane = next;
+        self.view.focus();
+        self.status_bar.set_focused(true);
+        // Keep buffers which aren't shown anywhere else around, so that no
+        // changes get lost.
+        if closed.is_only_view_on_buffer() {
+            self.inactive_views.push(closed);
+        }
+        self.apply_layout();
+    }
+
+    const fn layout_area(&self) -> Rect {
+        Rect {
+            origin: Position { col: 0, row: 0 },
+            size: Size {
+                height: self.terminal_size.height.saturating_sub(1),
+                width: self.terminal_size.width,
+            },
+        }
+    }
+
+    fn focused_pane_area(&self) -> Rect {
+        let (panes, _) = self.layout.arrange(self.layout_area());
+        panes
+            .into_iter()
+            .find(|(pane, _)| *pane == self.focused_pane)
+            .map(|(_, area)| area)
+            .unwrap_or_default()
+    }
+
+    // Every pane shows its view above its own status bar.
+    fn apply_layout(&mut self) {
+        let (panes, _) = self.layout.arrange(self.layout_area());
+        for (pane, area) in panes {
+            let (view, status_bar) = if pane == self.focused_pane {
+                (&mut self.view, &mut self.status_bar)
+            } else if let Some(pane) = self.unfocused_panes.get_mut(&pane) {
+                (&mut pane.view, &mut pane.status_bar)
+            } else {
+                continue;
+            };
+            let view_size = Size {
+                height: area.size.height.saturating_sub(1),
+                width: area.size.width,
+            };
+            if pane == self.focused_pane {
+                self.file_browser.resize(view_size);
+            }
+            view.resize(view_size);
+            status_bar.resize(Size {
+                height: 1,
+                width: area.size.width,
+            });
+        }
+    }
+
+    fn cycle_view(&mut self, forward: bool) {
+        let count = self.view_count();
+        let index = if forward {
+            self.view_index.saturating_add(1)
+        } else {
+            self.view_index.saturating_add(count).saturating_sub(1)
+        };
+        self.switch_to_view(index.checked_rem(count).unwrap_or(0));
+    }
+
     fn update_message(&mut self, message: &str) {
         self.message_bar.update_message(message);
     }
@@ -235,63 +1400,159 @@ impl Editor {
         }
     }
 
-    fn handle_save(&mut self) {
-        if self.view.is_file_loaded() {
+    fn handle_save(&mut self, overwrite: bool) {
+        if let Some(reason) = self.view.read_only() {
+            let message = if reason == ReadOnly::Binary {
+                format!("Can't save: {reason}.")
+            } else {
+                format!("Can't save: {reason}. Press Ctrl+W to save elsewhere.")
+            };
+            self.update_message(&message);
+        } else if self.view.is_file_loaded() {
+            if !overwrite && self.view.is_changed_on_disk() {
+                self.overwrite_warned = true;
+                self.update_message(
+                    "WARNING! File changed on disk. Press Ctrl+S again to overwrite it, or Ctrl+L to reload it.",
+                );
+                return;
+            }
             self.save(None);
         } else {
             self.set_prompt(PromptType::Save);
         }
     }
 
+    fn handle_save_as(&mut self) {
+        match self.view.read_only() {
+            Some(reason @ ReadOnly::Binary) => {
+                self.update_message(&format!("Can't save: {reason}."));
+            }
+            _ => self.set_prompt(PromptType::Save),
+        }
+    }
+
     fn save(&mut self, file_name: Option<&str>) {
         let result = if let Some(name) = file_name {
-            self.view.save_as(name)
+            self.view.save_as(name, self.options.make_backups)
         } else {
-            self.view.save()
+            self.view.save(self.options.make_backups)
         };
-        if result.is_ok() {
-            self.update_message("File saved successfully.");
+        match result {
+            Ok(()) => self.update_message("File saved successfully."),
+            Err(err) => self.update_message(&format!("ERR: {err}")),
+        }
+    }
+
+    fn reload(&mut self) {
+        if !self.view.is_file_loaded() {
+            self.update_message("Nothing to reload.");
+        } else if self.view.get_status().is_modified {
+            self.update_message("Can't reload: the buffer has unsaved changes.");
         } else {
-            self.update_message("Error writing file!");
+            match self.view.reload() {
+                Ok(()) => self.update_message("File reloaded."),
+                Err(err) => self.update_message(&format!("ERR: Could not reload file: {err}")),
+            }
         }
     }
 
+    fn theme_index(&self, name: &str) -> Option<usize> {
+        self.themes.iter().position(|theme| theme.name == name)
+    }
+
+    // The colors set in the configuration take precedence over any theme's.
+    fn apply_theme(&mut self) {
+        let Some(mut theme) = self.themes.get(self.theme_index).cloned() else {
+            return;
+        };
+        for (annotation_type, attribute) in &self.config.colors {
+            theme.set_annotation(*annotation_type, *attribute);
+        }
+        Terminal::set_theme(theme);
+        self.handle_resize_command(self.terminal_size);
+    }
+
     fn handle_resize_command(&mut self, size: Size) {
         self.terminal_size = size;
-        self.view.resize(Size {
-            height: size.height.saturating_sub(2),
-            width: size.width,
-        });
+        self.apply_layout();
         let bar_size = Size {
             height: 1,
             width: size.width,
         };
         self.message_bar.resize(bar_size);
-        self.status_bar.resize(bar_size);
         self.command_bar.resize(bar_size);
     }
 
+    fn view_size(&self) -> Size {
+        let Rect { size, .. } = self.focused_pane_area();
+        Size {
+            height: size.height.saturating_sub(1),
+            width: size.width,
+        }
+    }
+
     fn in_prompt(&self) -> bool {
         !self.prompt_type.is_none()
     }
 
+    fn render_panes(&mut self) {
+        let (panes, dividers) = self.layout.arrange(self.layout_area());
+        for (pane, Rect { origin, size }) in panes {
+            if size.height == 0 || size.width == 0 {
+                continue;
+            }
+            let (view, status_bar) = if pane == self.focused_pane {
+                (&mut self.view, &mut self.status_bar)
+            } else if let Some(pane) = self.unfocused_panes.get_mut(&pane) {
+                (&mut pane.view, &mut pane.status_bar)
+            } else {
+                continue;
+            };
+            status_bar.render(Position {
+                col: origin.col,
+                row: origin.row.saturating_add(size.height).saturating_sub(1),
+            });
+            if size.height > 1 {
+                if pane == self.focused_pane && self.prompt_type == PromptType::Browse {
+                    // The file browser takes the place of the view.
+                    self.file_browser.render(origin);
+                } else {
+                    view.render(origin);
+                }
+            }
+        }
+        for Rect { origin, size } in dividers {
+            for row in origin.row..origin.row.saturating_add(size.height) {
+                let _ = Terminal::print_at(
+                    Position {
+                        col: origin.col,
+                        row,
+                    },
+                    size.width,
+                    "│",
+                    UiElement::Divider,
+                );
+            }
+        }
+    }
+
     fn refresh_screen(&mut self) {
         if self.terminal_size.width == 0 || self.terminal_size.height == 0 {
             return; // No terminal size, nothing to render
         }
         let bottom_bar_row = self.terminal_size.height.saturating_sub(1);
+        let bottom_bar_origin = Position {
+            col: 0,
+            row: bottom_bar_row,
+        };
         let _ = Terminal::hide_caret();
         if self.in_prompt() {
-            self.command_bar.render(bottom_bar_row);
+            self.command_bar.render(bottom_bar_origin);
         } else {
-            self.message_bar.render(bottom_bar_row);
+            self.message_bar.render(bottom_bar_origin);
         }
         if self.terminal_size.height > 1 {
-            self.status_bar
-                .render(self.terminal_size.height.saturating_sub(2));
-        }
-        if self.terminal_size.height > 2 {
-            self.view.render(0);
+            self.render_panes();
         }
         let new_caret_pos = if self.in_prompt() {
             Position {
@@ -299,7 +1560,9 @@ impl Editor {
                 col: self.command_bar.caret_position_col(),
             }
         } else {
-            self.view.caret_position()
+            self.focused_pane_area()
+                .origin
+                .saturating_add(self.view.caret_position())
         };
         debug_assert!(new_caret_pos.row < self.terminal_size.height);
         debug_assert!(new_caret_pos.col < self.terminal_size.width);
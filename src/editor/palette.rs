@@ -0,0 +1,189 @@
+use crate::prelude::*;
+
+use super::{Command, FileType, TAB_WIDTH_RANGE};
+
+// The commands which take arguments or can only be run from the palette,
+// with their arguments and what they do.
+const PALETTE_COMMANDS: &[(&str, &str, &str)] = &[
+    ("goto", "LINE", "Moves to the start of a line"),
+    (
+        "set",
+        "SETTING VALUE",
+        "Changes a setting: tabwidth or theme",
+    ),
+    ("saveas", "FILE", "Saves the buffer under another name"),
+    (
+        "filetype",
+        "TYPE",
+        "Changes how the buffer is highlighted: rust or text",
+    ),
+    ("help", "", "Lists all commands and their keys"),
+];
+
+/// What was asked for in the command palette.
+pub enum Invocation {
+    GoTo(LineIdx),
+    SetTabWidth(usize),
+    SetTheme(String),
+    SaveAs(String),
+    SetFileType(FileType),
+    Help,
+    /// One of the commands which can also be bound to keys.
+    Run(Command),
+}
+
+/// The names of all commands which can be run from the palette.
+pub fn command_names() -> Vec<&'static str> {
+    PALETTE_COMMANDS
+        .iter()
+        .map(|(name, _, _)| *name)
+        .chain(Command::NAMED.iter().map(|(name, _)| *name))
+        .collect()
+}
+
+/// Shows how to call the command, e.g. "goto LINE", or `None` if there's no
+/// command of that name.
+pub fn usage(name: &str) -> Option<String> {
+    PALETTE_COMMANDS
+        .iter()
+        .find(|(known_name, _, _)| *known_name == name)
+        .map(|(name, arguments, description)| {
+            format!("{name} {arguments}").trim_end().to_string() + " - " + description
+        })
+        .or_else(|| Command::canonical_name(name).map(String::from))
+}
+
+/// Describes every command the palette offers, one per line.
+pub fn help_lines() -> Vec<(String, &'static str)> {
+    PALETTE_COMMANDS
+        .iter()
+        .map(|(name, arguments, description)| {
+            (
+                format!("{name} {arguments}").trim_end().to_string(),
+                *description,
+            )
+        })
+        .collect()
+}
+
+pub fn parse(input: &str) -> Result<Invocation, String> {
+    let input = input.trim();
+    let (name, arguments) = input
+        .split_once(char::is_whitespace)
+        .map_or((input, ""), |(name, arguments)| (name, arguments.trim()));
+    match name {
+        "goto" => arguments
+            .parse::<LineIdx>()
+            .ok()
+            .and_then(|line_number| line_number.checked_sub(1))
+            .map(Invocation::GoTo)
+            .ok_or_else(|| String::from("goto needs a line number, e.g. goto 120")),
+        "set" => parse_setting(arguments),
+        "saveas" if arguments.is_empty() => Err(String::from("saveas needs a file name")),
+        "saveas" => Ok(Invocation::SaveAs(arguments.to_string())),
+        "filetype" => FileType::from_name(arguments)
+            .map(Invocation::SetFileType)
+            .ok_or_else(|| {
+                let known: Vec<String> = FileType::ALL
+                    .iter()
+                    .map(|file_type| file_type.to_string().to_lowercase())
+                    .collect();
+                format!("filetype needs one of: {}", known.join(", "))
+            }),
+        "help" => Ok(Invocation::Help),
+        "" => Err(String::from("No command given")),
+        _ => match Command::from_name(name) {
+            Some(_) if !arguments.is_empty() => Err(format!("{name} takes no arguments")),
+            Some(command) => Ok(Invocation::Run(command)),
+            None => Err(format!("Unknown command '{name}'")),
+        },
+    }
+}
+
+fn parse_setting(arguments: &str) -> Result<Invocation, String> {
+    let (setting, value) = arguments
+        .split_once(char::is_whitespace)
+        .map_or((arguments, ""), |(setting, value)| (setting, value.trim()));
+    match setting {
+        "tabwidth" => value
+            .parse::<i64>()
+            .ok()
+            .filter(|width| TAB_WIDTH_RANGE.contains(width))
+            .and_then(|width| usize::try_from(width).ok())
+            .map(Invocation::SetTabWidth)
+            .ok_or_else(|| {
+                format!(
+                    "tabwidth must be from {} to {}",
+                    TAB_WIDTH_RANGE.start(),
+                    TAB_WIDTH_RANGE.end()
+                )
+            }),
+        "theme" if value.is_empty() => Err(String::from("set theme needs a theme name")),
+        "theme" => Ok(Invocation::SetTheme(value.to_string())),
+        _ => Err(String::from(
+            "set needs tabwidth or theme, e.g. set tabwidth 4",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_with_arguments_are_parsed() {
+        assert!(matches!(parse("goto 120"), Ok(Invocation::GoTo(119))));
+        assert!(matches!(
+            parse("  set   tabwidth 4 "),
+            Ok(Invocation::SetTabWidth(4))
+        ));
+        assert!(
+            matches!(parse("set theme light"), Ok(Invocation::SetTheme(theme)) if theme == "light")
+        );
+        assert!(
+            matches!(parse("saveas a b.txt"), Ok(Invocation::SaveAs(name)) if name == "a b.txt")
+        );
+        assert!(matches!(
+            parse("filetype rust"),
+            Ok(Invocation::SetFileType(FileType::Rust))
+        ));
+        assert!(matches!(parse("help"), Ok(Invocation::Help)));
+        assert!(matches!(parse("save"), Ok(Invocation::Run(_))));
+    }
+
+    #[test]
+    fn bad_arguments_are_reported() {
+        for input in [
+            "",
+            "goto",
+            "goto 0",
+            "goto x",
+            "set",
+            "set tabwidth 0",
+            "set tabwidth 17",
+            "set theme",
+            "set colour red",
+            "saveas",
+            "filetype cobol",
+            "save now",
+            "fly",
+        ] {
+            assert!(parse(input).is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn every_command_has_a_usage() {
+        for name in command_names() {
+            assert!(
+                usage(name).is_some_and(|usage| usage.starts_with(name)),
+                "{name}"
+            );
+        }
+        assert_eq!(
+            usage("goto").as_deref(),
+            Some("goto LINE - Moves to the start of a line")
+        );
+        assert_eq!(usage("fly"), None);
+    }
+}
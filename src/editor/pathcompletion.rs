@@ -0,0 +1,64 @@
+use std::env;
+use std::fs::read_dir;
+use std::path::PathBuf;
+
+/// Expands a leading `~` to the home directory.
+pub fn expand_home(path: &str) -> PathBuf {
+    if let Some(rest) = path.strip_prefix('~') {
+        if rest.is_empty() || rest.starts_with('/') {
+            if let Some(home) = env::var_os("HOME") {
+                let mut expanded = PathBuf::from(home);
+                expanded.push(rest.trim_start_matches('/'));
+                return expanded;
+            }
+        }
+    }
+    PathBuf::from(path)
+}
+
+pub struct PathCompletion {
+    pub completed: String,
+    /// Names of all entries which matched, with a `/` appended to directories.
+    pub candidates: Vec<String>,
+}
+
+/// Completes `input` as far as all matching file system entries agree.
+pub fn complete_path(input: &str) -> PathCompletion {
+    let (directory, partial) = input
+        .rfind('/')
+        .map_or(("", input), |index| input.split_at(index.saturating_add(1)));
+    let entries = read_dir(expand_home(if directory.is_empty() {
+        "."
+    } else {
+        directory
+    }));
+    let mut candidates: Vec<String> = entries
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            // Hidden entries are only offered when asked for explicitly.
+            if !name.starts_with(partial) || (name.starts_with('.') && !partial.starts_with('.')) {
+                return None;
+            }
+            let is_dir = entry.path().is_dir();
+            Some(if is_dir { format!("{name}/") } else { name })
+        })
+        .collect();
+    candidates.sort();
+    let common_prefix = candidates.first().map_or(partial, |first| {
+        candidates.iter().fold(first.as_str(), |prefix, candidate| {
+            let common_len = prefix
+                .char_indices()
+                .zip(candidate.chars())
+                .find(|((_, a), b)| a != b)
+                .map_or(prefix.len().min(candidate.len()), |((index, _), _)| index);
+            &prefix[..common_len]
+        })
+    });
+    PathCompletion {
+        completed: format!("{directory}{common_prefix}"),
+        candidates,
+    }
+}
@@ -0,0 +1,184 @@
+use std::iter::from_fn;
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::prelude::*;
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub regex: bool,
+    pub ignore_case: bool,
+    pub whole_word: bool,
+}
+
+#[derive(Clone)]
+pub struct SearchPattern {
+    regex: Regex,
+    whole_word: bool,
+}
+
+impl SearchPattern {
+    pub fn new(query: &str, options: SearchOptions) -> Result<Self, String> {
+        let pattern = if options.regex {
+            query.to_string()
+        } else {
+            regex::escape(query)
+        };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(options.ignore_case)
+            .build()
+            .map(|regex| Self {
+                regex,
+                whole_word: options.whole_word,
+            })
+            .map_err(|err| Self::summarize_error(&err))
+    }
+
+    // The regex crate reports syntax errors over several lines, pointing at
+    // the offending part of the pattern. Only the last line fits a prompt.
+    fn summarize_error(err: &regex::Error) -> String {
+        let message = err.to_string();
+        let last_line = message.lines().last().unwrap_or_default().trim();
+        last_line
+            .strip_prefix("error: ")
+            .unwrap_or(last_line)
+            .to_string()
+    }
+
+    /// Returns the byte ranges of all non-overlapping, non-empty matches.
+    pub fn find_all<'a>(&'a self, haystack: &'a str) -> impl Iterator<Item = Range<ByteIdx>> + 'a {
+        let mut search_from: ByteIdx = 0;
+        from_fn(move || {
+            while search_from <= haystack.len() {
+                let found = self.regex.find_at(haystack, search_from)?;
+                if found.is_empty() || !self.is_acceptable(haystack, &found.range()) {
+                    // Retry right after the start of the rejected match, since
+                    // a match overlapping it might still be acceptable.
+                    search_from = haystack[found.start()..]
+                        .chars()
+                        .next()
+                        .map_or(usize::MAX, |c| found.start().saturating_add(c.len_utf8()));
+                    continue;
+                }
+                search_from = found.end();
+                return Some(found.range());
+            }
+            None
+        })
+    }
+
+    fn is_acceptable(&self, haystack: &str, range: &Range<ByteIdx>) -> bool {
+        if !self.whole_word {
+            return true;
+        }
+        let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+        let starts_word = haystack[..range.start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !is_word_char(c));
+        let ends_word = haystack[range.end..]
+            .chars()
+            .next()
+            .is_none_or(|c| !is_word_char(c));
+        starts_word && ends_word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(query: &str, options: SearchOptions, haystack: &str) -> Vec<Range<ByteIdx>> {
+        SearchPattern::new(query, options)
+            .unwrap()
+            .find_all(haystack)
+            .collect()
+    }
+
+    const REGEX: SearchOptions = SearchOptions {
+        regex: true,
+        ignore_case: false,
+        whole_word: false,
+    };
+
+    #[test]
+    fn literal_queries_match_their_special_characters() {
+        let options = SearchOptions::default();
+        assert_eq!(matches("a.c", options, "abc a.c"), vec![4..7]);
+        assert_eq!(matches("(x)*", options, "x (x)*"), vec![2..6]);
+        assert_eq!(matches("[", options, "a[b["), vec![1..2, 3..4]);
+    }
+
+    #[test]
+    fn regex_queries_are_interpreted() {
+        assert_eq!(matches("a.c", REGEX, "abc a.c"), vec![0..3, 4..7]);
+        assert_eq!(matches(r"\d+", REGEX, "a12 b3"), vec![1..3, 5..6]);
+        assert_eq!(matches("^a", REGEX, "aa"), vec![0..1]);
+    }
+
+    #[test]
+    fn invalid_regexes_are_reported_on_one_line() {
+        for query in ["(", "[a", "a{2,1}"] {
+            let err = SearchPattern::new(query, REGEX).err().unwrap();
+            assert!(!err.is_empty() && !err.contains('\n'), "{query}: {err}");
+        }
+        assert!(SearchPattern::new("(", SearchOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn empty_matches_are_skipped() {
+        assert!(matches("x*", REGEX, "").is_empty());
+        assert_eq!(matches("x*", REGEX, "axx"), vec![1..3]);
+        assert!(matches(r"\b", REGEX, "a b").is_empty());
+        assert!(matches("", SearchOptions::default(), "ab").is_empty());
+    }
+
+    #[test]
+    fn multibyte_text_is_matched_by_byte_ranges() {
+        assert_eq!(
+            matches("ö", SearchOptions::default(), "aöböc"),
+            vec![1..3, 4..6]
+        );
+        assert_eq!(matches("世.", REGEX, "世界世"), vec![0..6]);
+        // Empty matches mid-text must move past whole characters.
+        assert_eq!(matches("x*", REGEX, "界x"), vec![3..4]);
+    }
+
+    #[test]
+    fn matching_can_ignore_case() {
+        let options = SearchOptions {
+            ignore_case: true,
+            ..SearchOptions::default()
+        };
+        assert_eq!(matches("ab", options, "AB aB ab"), vec![0..2, 3..5, 6..8]);
+        assert_eq!(matches("ÄÖ", options, "äö"), vec![0..4]);
+        assert_eq!(matches("ab", SearchOptions::default(), "AB ab"), vec![3..5]);
+    }
+
+    #[test]
+    fn whole_words_must_not_touch_word_characters() {
+        let options = SearchOptions {
+            whole_word: true,
+            ..SearchOptions::default()
+        };
+        assert_eq!(
+            matches("cat", options, "cat cats scat cat_ (cat) cat"),
+            vec![0..3, 20..23, 25..28]
+        );
+        assert!(matches("cat", options, "écat catö 1cat").is_empty());
+        assert_eq!(matches("cat", options, "ö cat"), vec![3..6]);
+    }
+
+    #[test]
+    fn rejected_matches_dont_hide_overlapping_ones() {
+        let options = SearchOptions {
+            regex: true,
+            whole_word: true,
+            ..SearchOptions::default()
+        };
+        // The first match, "aa", is part of a longer word, but "a" isn't.
+        assert_eq!(matches("a+", options, "aab a"), vec![4..5]);
+        assert_eq!(matches(r"\w+b", options, "xab"), vec![0..3]);
+    }
+}
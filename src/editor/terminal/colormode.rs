@@ -0,0 +1,233 @@
+use std::env;
+use std::ffi::OsString;
+
+use crossterm::style::Color;
+
+// The intensities of each component in the 6×6×6 color cube of the 256-color
+// palette, which starts at index 16.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+const CUBE_START: u8 = 16;
+// The grays following the cube, from 8 up in steps of 10.
+const GRAY_START: u8 = 232;
+const GRAY_COUNT: u8 = 24;
+// The 16 basic colors, as xterm shows them by default.
+const ANSI_16: [(Color, [u8; 3]); 16] = [
+    (Color::Black, [0, 0, 0]),
+    (Color::DarkRed, [205, 0, 0]),
+    (Color::DarkGreen, [0, 205, 0]),
+    (Color::DarkYellow, [205, 205, 0]),
+    (Color::DarkBlue, [0, 0, 238]),
+    (Color::DarkMagenta, [205, 0, 205]),
+    (Color::DarkCyan, [0, 205, 205]),
+    (Color::Grey, [229, 229, 229]),
+    (Color::DarkGrey, [127, 127, 127]),
+    (Color::Red, [255, 0, 0]),
+    (Color::Green, [0, 255, 0]),
+    (Color::Yellow, [255, 255, 0]),
+    (Color::Blue, [92, 92, 255]),
+    (Color::Magenta, [255, 0, 255]),
+    (Color::Cyan, [0, 255, 255]),
+    (Color::White, [255, 255, 255]),
+];
+
+/// Which colors the terminal can show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    /// Any RGB color.
+    #[default]
+    TrueColor,
+    Ansi256,
+    Ansi16,
+    /// No colors at all, as asked for by setting `NO_COLOR`.
+    NoColor,
+}
+
+impl ColorMode {
+    /// Guesses what the terminal supports from the environment.
+    pub fn detect() -> Self {
+        Self::detect_from(|name| env::var_os(name))
+    }
+
+    // Like `detect`, with `var` looking up environment variables.
+    fn detect_from(var: impl Fn(&str) -> Option<OsString>) -> Self {
+        if var("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+            return Self::NoColor;
+        }
+        let color_term = var("COLORTERM").unwrap_or_default();
+        if color_term == "truecolor" || color_term == "24bit" {
+            return Self::TrueColor;
+        }
+        let Some(term) = var("TERM") else {
+            // Not a Unix terminal, such as on Windows, where all are able to.
+            return Self::TrueColor;
+        };
+        let term = term.to_string_lossy();
+        if term.ends_with("-direct") {
+            Self::TrueColor
+        } else if term.contains("256color") {
+            Self::Ansi256
+        } else if term == "dumb" {
+            Self::NoColor
+        } else {
+            Self::Ansi16
+        }
+    }
+
+    /// Parses the mode as given in the configuration.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "truecolor" => Some(Self::TrueColor),
+            "256" => Some(Self::Ansi256),
+            "16" => Some(Self::Ansi16),
+            "none" => Some(Self::NoColor),
+            _ => None,
+        }
+    }
+
+    /// Returns the color closest to `color` which the terminal can show.
+    pub fn adapt(self, color: Color) -> Option<Color> {
+        let Color::Rgb { r, g, b } = color else {
+            return (self != Self::NoColor).then_some(color);
+        };
+        let rgb = [r, g, b];
+        match self {
+            Self::TrueColor => Some(color),
+            Self::Ansi256 => Some(Color::AnsiValue(nearest_ansi_256(rgb))),
+            Self::Ansi16 => ANSI_16
+                .iter()
+                .min_by_key(|(_, candidate)| distance(rgb, *candidate))
+                .map(|(ansi_color, _)| *ansi_color),
+            Self::NoColor => None,
+        }
+    }
+}
+
+// Picks whichever is closer of the nearest color in the cube and the nearest
+// gray.
+fn nearest_ansi_256(rgb: [u8; 3]) -> u8 {
+    let cube_indices = rgb.map(|component| {
+        (0_u8..)
+            .zip(CUBE_LEVELS)
+            .min_by_key(|(_, level)| component.abs_diff(*level))
+            .map_or(0, |(index, _)| index)
+    });
+    let cube_rgb =
+        cube_indices.map(|index| CUBE_LEVELS.get(usize::from(index)).copied().unwrap_or(0));
+    let [r_index, g_index, b_index] = cube_indices;
+    let cube_index = CUBE_START
+        .saturating_add(r_index.saturating_mul(36))
+        .saturating_add(g_index.saturating_mul(6))
+        .saturating_add(b_index);
+
+    let (gray_offset, gray_distance) = (0..GRAY_COUNT)
+        .map(|offset| {
+            let level = offset.saturating_mul(10).saturating_add(8);
+            (offset, distance(rgb, [level; 3]))
+        })
+        .min_by_key(|(_, gray_distance)| *gray_distance)
+        .unwrap_or((0, u32::MAX));
+    if gray_distance < distance(rgb, cube_rgb) {
+        GRAY_START.saturating_add(gray_offset)
+    } else {
+        cube_index
+    }
+}
+
+// The squared distance between two colors.
+fn distance(first: [u8; 3], second: [u8; 3]) -> u32 {
+    first
+        .iter()
+        .zip(second)
+        .map(|(a, b)| u32::from(a.abs_diff(b)).saturating_pow(2))
+        .fold(0, u32::saturating_add)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(vars: &[(&str, &str)]) -> ColorMode {
+        ColorMode::detect_from(|name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| OsString::from(value))
+        })
+    }
+
+    fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color::Rgb { r, g, b }
+    }
+
+    #[test]
+    fn modes_are_detected_from_the_environment() {
+        assert_eq!(detect(&[("TERM", "xterm-256color")]), ColorMode::Ansi256);
+        assert_eq!(detect(&[("TERM", "xterm")]), ColorMode::Ansi16);
+        assert_eq!(detect(&[("TERM", "xterm-direct")]), ColorMode::TrueColor);
+        assert_eq!(detect(&[("TERM", "dumb")]), ColorMode::NoColor);
+        assert_eq!(detect(&[]), ColorMode::TrueColor);
+        assert_eq!(
+            detect(&[("TERM", "xterm"), ("COLORTERM", "24bit")]),
+            ColorMode::TrueColor
+        );
+    }
+
+    #[test]
+    fn no_color_wins_unless_empty() {
+        assert_eq!(
+            detect(&[("NO_COLOR", "1"), ("COLORTERM", "truecolor")]),
+            ColorMode::NoColor
+        );
+        assert_eq!(
+            detect(&[("NO_COLOR", ""), ("TERM", "xterm-256color")]),
+            ColorMode::Ansi256
+        );
+    }
+
+    #[test]
+    fn rgb_colors_are_mapped_onto_the_256_color_palette() {
+        let ansi_256 = |color| ColorMode::Ansi256.adapt(color);
+        // The corners of the color cube.
+        for (color, index) in [
+            (rgb(0, 0, 0), 16),
+            (rgb(255, 0, 0), 196),
+            (rgb(0, 0, 255), 21),
+            (rgb(255, 255, 0), 226),
+            (rgb(255, 255, 255), 231),
+            (rgb(95, 135, 175), 67),
+        ] {
+            assert_eq!(ansi_256(color), Some(Color::AnsiValue(index)), "{color:?}");
+        }
+        // Grays between the cube's levels go to the gray ramp.
+        for (level, index) in [(8, 232), (128, 244), (130, 244), (238, 255)] {
+            assert_eq!(
+                ansi_256(rgb(level, level, level)),
+                Some(Color::AnsiValue(index)),
+                "{level}"
+            );
+        }
+    }
+
+    #[test]
+    fn rgb_colors_are_mapped_onto_the_16_basic_colors() {
+        for (color, expected) in [
+            (rgb(0, 0, 0), Color::Black),
+            (rgb(255, 255, 255), Color::White),
+            (rgb(128, 128, 128), Color::DarkGrey),
+            (rgb(250, 10, 10), Color::Red),
+            (rgb(190, 10, 10), Color::DarkRed),
+            (rgb(100, 100, 250), Color::Blue),
+        ] {
+            assert_eq!(ColorMode::Ansi16.adapt(color), Some(expected), "{color:?}");
+        }
+    }
+
+    #[test]
+    fn named_colors_are_kept_unless_colors_are_off() {
+        for mode in [ColorMode::TrueColor, ColorMode::Ansi256, ColorMode::Ansi16] {
+            assert_eq!(mode.adapt(Color::Red), Some(Color::Red));
+        }
+        assert_eq!(ColorMode::TrueColor.adapt(rgb(1, 2, 3)), Some(rgb(1, 2, 3)));
+        assert_eq!(ColorMode::NoColor.adapt(Color::Red), None);
+        assert_eq!(ColorMode::NoColor.adapt(rgb(1, 2, 3)), None);
+    }
+}
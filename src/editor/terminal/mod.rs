@@ -1,16 +1,19 @@
 mod attribute;
+mod colormode;
 
 use std::{
     fmt::Display,
     io::{stdout, Error, Write},
+    sync::{Mutex, MutexGuard, PoisonError},
 };
 
 use crossterm::{
     cursor::MoveTo,
+    event::{DisableBracketedPaste, EnableBracketedPaste},
     queue,
     style::{
-        Attribute::{Reset, Reverse},
-        ResetColor, SetBackgroundColor, SetForegroundColor,
+        Attribute::{Bold, Italic, Reset, Reverse, Underlined},
+        Colored, SetAttribute, SetBackgroundColor, SetForegroundColor,
     },
     terminal::{
         disable_raw_mode, enable_raw_mode, size, Clear, ClearType, DisableLineWrap, EnableLineWrap,
@@ -22,7 +25,13 @@ use crossterm::{
 use crate::prelude::*;
 
 use super::annotatedstring::AnnotatedString;
-use attribute::Attribute;
+use super::theme::{Theme, UiElement};
+pub use attribute::Attribute;
+pub use colormode::ColorMode;
+
+// The styles everything is printed in.
+static THEME: Mutex<Theme> = Mutex::new(Theme::plain());
+static COLOR_MODE: Mutex<ColorMode> = Mutex::new(ColorMode::TrueColor);
 
 /// Represents the Terminal.
 pub struct Terminal {}
@@ -31,6 +40,7 @@ impl Terminal {
     pub fn initialize() -> Result<(), Error> {
         enable_raw_mode()?;
         Self::enter_alternate_screen()?;
+        Self::enable_bracketed_paste()?;
         Self::disable_line_wrap()?;
         Self::clear_screen()?;
         Self::execute()?;
@@ -57,55 +67,128 @@ impl Terminal {
         Ok(())
     }
 
-    pub fn print_row(row: RowIdx, line_text: &str) -> Result<(), Error> {
+    pub fn set_theme(theme: Theme) {
+        *Self::theme() = theme;
+    }
+
+    fn theme() -> MutexGuard<'static, Theme> {
+        THEME.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn set_color_mode(color_mode: ColorMode) {
+        // crossterm leaves out all colors if `NO_COLOR` is set, even if a
+        // color mode was configured explicitly.
+        Colored::set_ansi_color_disabled(color_mode == ColorMode::NoColor);
+        *COLOR_MODE.lock().unwrap_or_else(PoisonError::into_inner) = color_mode;
+    }
+
+    fn color_mode() -> ColorMode {
+        *COLOR_MODE.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Prints `line_text` over the whole row, in the style of `element`.
+    pub fn print_row(row: RowIdx, line_text: &str, element: UiElement) -> Result<(), Error> {
         Self::move_caret_to(Position { col: 0, row })?;
+        // Clearing fills the row with the background color set.
+        let theme = Self::theme();
+        Self::set_attribute(theme.element(element), &theme)?;
         Self::clear_line()?;
         Self::print(line_text)?;
+        Self::reset_attribute()?;
         Ok(())
     }
 
-    pub fn print_inverted_row(row: RowIdx, line_text: &str) -> Result<(), Error> {
-        let width = Self::size()?.width;
-        Self::print_row(row, &format!("{Reverse}{line_text:width$.width$}{Reset}"))
+    /// Prints `line_text` at `origin` in the style of `element`, after
+    /// clearing the `width` columns from there on, without touching the rest
+    /// of the row.
+    pub fn print_at(
+        origin: Position,
+        width: usize,
+        line_text: &str,
+        element: UiElement,
+    ) -> Result<(), Error> {
+        let theme = Self::theme();
+        Self::set_attribute(theme.element(element), &theme)?;
+        Self::clear_columns(origin, width)?;
+        Self::print(line_text)?;
+        Self::reset_attribute()?;
+        Ok(())
     }
 
-    pub fn print_annotated_row(
-        row: RowIdx,
+    /// Prints `annotated_string` on top of the style of `element`.
+    pub fn print_annotated_at(
+        origin: Position,
+        width: usize,
         annotated_string: &AnnotatedString,
+        element: UiElement,
     ) -> Result<(), Error> {
-        Self::move_caret_to(Position { col: 0, row })?;
-        Self::clear_line()?;
+        let theme = Self::theme();
+        let base = theme.element(element);
+        Self::set_attribute(base, &theme)?;
+        Self::clear_columns(origin, width)?;
 
         annotated_string
             .into_iter()
             .try_for_each(|part| -> Result<(), Error> {
                 if let Some(annotation_type) = part.annotation_type {
-                    let attribute: Attribute = annotation_type.into();
-                    Self::set_attribute(&attribute)?;
+                    Self::set_attribute(theme.annotation(annotation_type).over(base), &theme)?;
+                    Self::print(part.string)?;
+                    Self::reset_attribute()?;
+                    Self::set_attribute(base, &theme)?;
+                } else {
+                    Self::print(part.string)?;
                 }
-                Self::print(part.string)?;
-                Self::reset_color()?;
                 Ok(())
             })?;
+        Self::reset_attribute()?;
         Ok(())
     }
 
-    fn set_attribute(attribute: &Attribute) -> Result<(), Error> {
-        if let Some(foreground_color) = attribute.foreground {
-            Self::queue_command(SetForegroundColor(foreground_color))?;
+    // Shows the colors as well as the terminal can. Without colors, what
+    // stands out from the text by its background is shown inverted instead,
+    // so that e.g. the status bar and selections can still be told apart.
+    fn set_attribute(attribute: Attribute, theme: &Theme) -> Result<(), Error> {
+        let color_mode = Self::color_mode();
+        if color_mode == ColorMode::NoColor {
+            if attribute.background.is_some()
+                && attribute.background != theme.element(UiElement::Text).background
+            {
+                Self::queue_command(SetAttribute(Reverse))?;
+            }
+        } else {
+            if let Some(foreground_color) = attribute
+                .foreground
+                .and_then(|color| color_mode.adapt(color))
+            {
+                Self::queue_command(SetForegroundColor(foreground_color))?;
+            }
+            if let Some(background_color) = attribute
+                .background
+                .and_then(|color| color_mode.adapt(color))
+            {
+                Self::queue_command(SetBackgroundColor(background_color))?;
+            }
+        }
+        if attribute.bold {
+            Self::queue_command(SetAttribute(Bold))?;
+        }
+        if attribute.italic {
+            Self::queue_command(SetAttribute(Italic))?;
         }
-        if let Some(background_color) = attribute.background {
-            Self::queue_command(SetBackgroundColor(background_color))?;
+        if attribute.underline {
+            Self::queue_command(SetAttribute(Underlined))?;
         }
         Ok(())
     }
 
-    fn reset_color() -> Result<(), Error> {
-        Self::queue_command(ResetColor)?;
+    // Resets the colors as well.
+    fn reset_attribute() -> Result<(), Error> {
+        Self::queue_command(SetAttribute(Reset))?;
         Ok(())
     }
 
     pub fn move_caret_to(position: Position) -> Result<(), Error> {
+        #[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
         let command = MoveTo(position.col as u16, position.row as u16);
         Self::queue_command(command)?;
         Ok(())
@@ -121,6 +204,13 @@ impl Terminal {
         Ok(())
     }
 
+    fn clear_columns(origin: Position, width: usize) -> Result<(), Error> {
+        Self::move_caret_to(origin)?;
+        Self::print(" ".repeat(width))?;
+        Self::move_caret_to(origin)?;
+        Ok(())
+    }
+
     pub fn disable_line_wrap() -> Result<(), Error> {
         Self::queue_command(DisableLineWrap)?;
         Ok(())
@@ -131,7 +221,18 @@ impl Terminal {
         Ok(())
     }
 
+    pub fn enable_bracketed_paste() -> Result<(), Error> {
+        Self::queue_command(EnableBracketedPaste)?;
+        Ok(())
+    }
+
+    pub fn disable_bracketed_paste() -> Result<(), Error> {
+        Self::queue_command(DisableBracketedPaste)?;
+        Ok(())
+    }
+
     pub fn terminate() -> Result<(), Error> {
+        Self::disable_bracketed_paste()?;
         Self::leave_alternate_screen()?;
         Self::enable_line_wrap()?;
         Self::show_caret()?;
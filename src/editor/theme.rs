@@ -0,0 +1,353 @@
+use std::fs;
+use std::path::Path;
+
+use crossterm::style::Color;
+use toml::{Table, Value};
+
+use super::{AnnotationType, Attribute};
+
+pub const DEFAULT_THEME_NAME: &str = "dark";
+// Built-in themes, in the same format as those users add.
+const BUILTIN_THEMES: &[(&str, &str)] = &[
+    ("dark", include_str!("themes/dark.toml")),
+    ("light", include_str!("themes/light.toml")),
+];
+
+/// The parts of the screen which aren't text in the buffer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UiElement {
+    /// The buffer's text, and the empty rows after it.
+    Text,
+    Welcome,
+    Gutter,
+    Divider,
+    StatusBar,
+    InactiveStatusBar,
+    MessageBar,
+    CommandBar,
+    /// The selected entry of a list, such as in the file browser.
+    SelectedEntry,
+}
+
+impl UiElement {
+    pub const ALL: [Self; 9] = [
+        Self::Text,
+        Self::Welcome,
+        Self::Gutter,
+        Self::Divider,
+        Self::StatusBar,
+        Self::InactiveStatusBar,
+        Self::MessageBar,
+        Self::CommandBar,
+        Self::SelectedEntry,
+    ];
+
+    /// How the element is referred to in theme files.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Welcome => "welcome",
+            Self::Gutter => "gutter",
+            Self::Divider => "divider",
+            Self::StatusBar => "status_bar",
+            Self::InactiveStatusBar => "inactive_status_bar",
+            Self::MessageBar => "message_bar",
+            Self::CommandBar => "command_bar",
+            Self::SelectedEntry => "selected_entry",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|element| element.name() == name)
+    }
+}
+
+/// A named set of styles for everything shown on screen.
+#[derive(Clone)]
+pub struct Theme {
+    pub name: String,
+    annotations: Vec<(AnnotationType, Attribute)>,
+    elements: Vec<(UiElement, Attribute)>,
+}
+
+impl Theme {
+    /// Styles nothing, leaving everything in the terminal's colors.
+    pub const fn plain() -> Self {
+        Self {
+            name: String::new(),
+            annotations: Vec::new(),
+            elements: Vec::new(),
+        }
+    }
+
+    pub fn builtin() -> Vec<Self> {
+        BUILTIN_THEMES
+            .iter()
+            .filter_map(|(name, source)| {
+                let theme = Self::parse(name, source, &[]);
+                debug_assert!(theme.is_ok(), "{name}: {:?}", theme.as_ref().err());
+                theme.ok()
+            })
+            .collect()
+    }
+
+    /// Reads the `.toml` files in `dir`, each defining a theme named after
+    /// the file. Themes which can't be read are described in the errors.
+    pub fn load_dir(dir: &Path) -> (Vec<Self>, Vec<String>) {
+        let mut themes = Vec::new();
+        let mut errors = Vec::new();
+        let Ok(entries) = fs::read_dir(dir) else {
+            return (themes, errors);
+        };
+        let mut paths: Vec<_> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "toml")
+            })
+            .collect();
+        paths.sort();
+        let builtin = Self::builtin();
+        for path in paths {
+            let Some(name) = path.file_stem().map(|stem| stem.to_string_lossy()) else {
+                continue;
+            };
+            match fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|source| Self::parse(&name, &source, &builtin))
+            {
+                Ok(theme) => themes.push(theme),
+                Err(err) => errors.push(format!("{}: {err}", path.display())),
+            }
+        }
+        (themes, errors)
+    }
+
+    // A theme may start out as a copy of one of `bases`, and only change
+    // some of its styles.
+    fn parse(name: &str, source: &str, bases: &[Self]) -> Result<Self, String> {
+        let table = source
+            .parse::<Table>()
+            .map_err(|err| err.message().to_string())?;
+        let mut theme = match table.get("base") {
+            None => Self::plain(),
+            Some(Value::String(base)) => bases
+                .iter()
+                .find(|theme| theme.name == *base)
+                .cloned()
+                .ok_or_else(|| format!("Unknown base theme '{base}'"))?,
+            Some(_) => return Err(String::from("base must be the name of a theme")),
+        };
+        theme.name = name.to_string();
+        for (key, value) in table.iter().filter(|(key, _)| *key != "base") {
+            let annotation_type = AnnotationType::from_name(key);
+            let element = UiElement::from_name(key);
+            if annotation_type.is_none() && element.is_none() {
+                return Err(format!("Unknown style '{key}'"));
+            }
+            let attribute = parse_attribute(value).map_err(|err| format!("{key}: {err}"))?;
+            if let Some(annotation_type) = annotation_type {
+                theme.set_annotation(annotation_type, attribute);
+            }
+            if let Some(element) = element {
+                theme.elements.retain(|(existing, _)| *existing != element);
+                theme.elements.push((element, attribute));
+            }
+        }
+        Ok(theme)
+    }
+
+    pub fn set_annotation(&mut self, annotation_type: AnnotationType, attribute: Attribute) {
+        self.annotations
+            .retain(|(existing, _)| *existing != annotation_type);
+        self.annotations.push((annotation_type, attribute));
+    }
+
+    pub fn annotation(&self, annotation_type: AnnotationType) -> Attribute {
+        self.annotations
+            .iter()
+            .find(|(existing, _)| *existing == annotation_type)
+            .map(|(_, attribute)| *attribute)
+            .unwrap_or_default()
+    }
+
+    pub fn element(&self, element: UiElement) -> Attribute {
+        self.elements
+            .iter()
+            .find(|(existing, _)| *existing == element)
+            .map(|(_, attribute)| *attribute)
+            .unwrap_or_default()
+    }
+}
+
+/// Parses a style: either just the foreground color, like "#rrggbb", or a
+/// table with any of a foreground and background color, bold, italic and
+/// underline.
+pub fn parse_attribute(value: &Value) -> Result<Attribute, String> {
+    match value {
+        Value::String(color) => Ok(Attribute {
+            foreground: Some(parse_color(color)?),
+            ..Attribute::default()
+        }),
+        Value::Table(table) => {
+            let mut attribute = Attribute::default();
+            for (key, value) in table {
+                match key.as_str() {
+                    "foreground" | "background" => {
+                        let color = value
+                            .as_str()
+                            .ok_or_else(|| format!("{key} must be a color like \"#rrggbb\""))
+                            .and_then(parse_color)?;
+                        if key == "foreground" {
+                            attribute.foreground = Some(color);
+                        } else {
+                            attribute.background = Some(color);
+                        }
+                    }
+                    "bold" | "italic" | "underline" => {
+                        let flag = value
+                            .as_bool()
+                            .ok_or_else(|| format!("{key} must be true or false"))?;
+                        match key.as_str() {
+                            "bold" => attribute.bold = flag,
+                            "italic" => attribute.italic = flag,
+                            _ => attribute.underline = flag,
+                        }
+                    }
+                    _ => return Err(format!("Unknown key '{key}'")),
+                }
+            }
+            Ok(attribute)
+        }
+        _ => Err(String::from(
+            "must be a color like \"#rrggbb\", or a table with a foreground, background, bold, italic or underline",
+        )),
+    }
+}
+
+fn parse_color(color: &str) -> Result<Color, String> {
+    let invalid = || format!("'{color}' isn't a color like \"#rrggbb\"");
+    let hex = color.strip_prefix('#').ok_or_else(invalid)?;
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let component = |range| {
+        hex.get(range)
+            .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+            .ok_or_else(invalid)
+    };
+    Ok(Color::Rgb {
+        r: component(0..2)?,
+        g: component(2..4)?,
+        b: component(4..6)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::process;
+
+    use super::*;
+
+    const RED: Color = Color::Rgb { r: 255, g: 0, b: 0 };
+
+    fn attribute(source: &str) -> Result<Attribute, String> {
+        let table = format!("style = {source}").parse::<Table>().unwrap();
+        parse_attribute(table.get("style").unwrap())
+    }
+
+    #[test]
+    fn builtin_themes_are_valid() {
+        let names: Vec<_> = Theme::builtin()
+            .into_iter()
+            .map(|theme| theme.name)
+            .collect();
+        assert_eq!(names, vec!["dark", "light"]);
+    }
+
+    #[test]
+    fn styles_are_a_color_or_a_table() {
+        let style = attribute("\"#ff0000\"").unwrap();
+        assert_eq!(style.foreground, Some(RED));
+        assert_eq!(style.background, None);
+
+        let style =
+            attribute("{ background = \"#FF0000\", bold = true, underline = true }").unwrap();
+        assert_eq!((style.foreground, style.background), (None, Some(RED)));
+        assert!(style.bold && style.underline && !style.italic);
+    }
+
+    #[test]
+    fn malformed_styles_are_rejected() {
+        for source in [
+            "\"red\"",
+            "\"#ff00\"",
+            "\"#ff00000\"",
+            "\"#gg0000\"",
+            "\"#ff00é\"",
+            "{ foreground = 1 }",
+            "{ bold = \"yes\" }",
+            "{ blink = true }",
+            "3",
+        ] {
+            assert!(attribute(source).is_err(), "{source}");
+        }
+    }
+
+    #[test]
+    fn themes_change_the_styles_of_their_base() {
+        let bases = Theme::builtin();
+        let dark = bases.iter().find(|theme| theme.name == "dark").unwrap();
+        let theme = Theme::parse(
+            "mine",
+            "base = \"dark\"\nkeyword = \"#ff0000\"\nstatus_bar = { background = \"#ff0000\" }\n",
+            &bases,
+        )
+        .unwrap();
+        assert_eq!(theme.name, "mine");
+        assert_eq!(
+            theme.annotation(AnnotationType::Keyword).foreground,
+            Some(RED)
+        );
+        assert_eq!(theme.element(UiElement::StatusBar).background, Some(RED));
+        assert_eq!(
+            theme.annotation(AnnotationType::Comment).foreground,
+            dark.annotation(AnnotationType::Comment).foreground
+        );
+
+        let plain = Theme::parse("plain", "number = \"#ff0000\"", &bases).unwrap();
+        assert_eq!(plain.annotation(AnnotationType::Keyword).foreground, None);
+    }
+
+    #[test]
+    fn malformed_themes_are_rejected() {
+        let bases = Theme::builtin();
+        for source in [
+            "base = \"sepia\"",
+            "base = 1",
+            "sparkle = \"#ffffff\"",
+            "keyword = \"red\"",
+            "keyword = ",
+        ] {
+            assert!(Theme::parse("bad", source, &bases).is_err(), "{source}");
+        }
+    }
+
+    #[test]
+    fn theme_files_are_loaded_and_named_after_the_file() {
+        let dir = temp_dir().join(format!("hecto-test-{}-themes", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("b.toml"), "base = \"light\"").unwrap();
+        fs::write(dir.join("a.toml"), "keyword = \"#ff0000\"").unwrap();
+        fs::write(dir.join("broken.toml"), "keyword = 1").unwrap();
+        fs::write(dir.join("notes.txt"), "not a theme").unwrap();
+        let (themes, errors) = Theme::load_dir(&dir);
+        let _ = fs::remove_dir_all(&dir);
+        let names: Vec<_> = themes.into_iter().map(|theme| theme.name).collect();
+        assert_eq!(names, vec!["a", "b"]);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("broken.toml"), "{}", errors[0]);
+    }
+}
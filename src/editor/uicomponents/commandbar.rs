@@ -4,13 +4,14 @@ use crate::prelude::*;
 
 use super::super::command::Edit;
 use super::super::terminal::Terminal;
-use super::super::Line;
+use super::super::{Line, UiElement};
 use super::UIComponent;
 
 #[derive(Default)]
 pub struct CommandBar {
     prompt: String,
     value: Line,
+    hint: String,
     needs_redraw: bool,
     size: Size,
 }
@@ -21,7 +22,15 @@ impl CommandBar {
             Edit::Insert(c) => {
                 self.value.append_char(c);
             }
-            Edit::Delete | Edit::InsertNewLine => {}
+            Edit::InsertText(text) => {
+                // The prompt is a single line, so only take the first one.
+                text.lines()
+                    .next()
+                    .unwrap_or_default()
+                    .chars()
+                    .for_each(|c| self.value.append_char(c));
+            }
+            Edit::Delete | Edit::InsertNewLine | Edit::Undo | Edit::Redo => {}
             Edit::Backspace => self.value.delete_last(),
         }
         self.mark_redraw(true);
@@ -37,7 +46,8 @@ impl CommandBar {
             .prompt
             .len()
             .saturating_add(self.value.grapheme_count());
-        min(max_width, self.size.width)
+        // The caret stays on screen, after the last character shown.
+        min(max_width, self.size.width.saturating_sub(1))
     }
 
     pub fn value(&self) -> String {
@@ -46,6 +56,13 @@ impl CommandBar {
 
     pub fn clear_value(&mut self) {
         self.value = Line::default();
+        self.hint.clear();
+        self.mark_redraw(true);
+    }
+
+    /// Shows `hint` after the value, e.g. to report an invalid value inline.
+    pub fn set_hint(&mut self, hint: &str) {
+        self.hint = hint.to_string();
         self.mark_redraw(true);
     }
 }
@@ -63,8 +80,13 @@ impl UIComponent for CommandBar {
         self.size = to;
     }
 
-    fn draw(&mut self, origin_y: RowIdx) -> Result<(), std::io::Error> {
-        let area_for_value = self.size.width.saturating_sub(self.prompt.len());
+    fn draw(&mut self, origin: Position) -> Result<(), std::io::Error> {
+        // One column is left for the caret.
+        let area_for_value = self
+            .size
+            .width
+            .saturating_sub(self.prompt.len())
+            .saturating_sub(1);
         let value_end = self.value.width();
         let value_start = value_end.saturating_sub(area_for_value);
         let message = format!(
@@ -73,10 +95,13 @@ impl UIComponent for CommandBar {
             self.value.get_visible_graphemes(value_start..value_end)
         );
         let to_print = if message.len() <= self.size.width {
-            message
+            // Show as much of the hint as fits after the message.
+            let hint = Line::from(&format!("  {}", self.hint));
+            let area_for_hint = self.size.width.saturating_sub(message.len());
+            format!("{message}{}", hint.get_visible_graphemes(0..area_for_hint))
         } else {
             String::new()
         };
-        Terminal::print_row(origin_y, &to_print)
+        Terminal::print_row(origin.row, &to_print, UiElement::CommandBar)
     }
 }
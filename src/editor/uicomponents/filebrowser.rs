@@ -0,0 +1,137 @@
+use std::fs::read_dir;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+use crate::prelude::*;
+
+use super::super::command::Move;
+use super::super::terminal::Terminal;
+use super::super::{Line, UiElement};
+use super::UIComponent;
+
+struct Entry {
+    name: String,
+    path: PathBuf,
+    is_dir: bool,
+}
+
+/// Lists the contents of a directory, to pick a file to open from.
+#[derive(Default)]
+pub struct FileBrowser {
+    directory: PathBuf,
+    entries: Vec<Entry>,
+    selected: usize,
+    scroll_offset: usize,
+    needs_redraw: bool,
+    size: Size,
+}
+
+impl FileBrowser {
+    pub fn open(&mut self, directory: &Path) -> Result<(), Error> {
+        let directory = directory.canonicalize()?;
+        let mut entries: Vec<Entry> = read_dir(&directory)?
+            .flatten()
+            .map(|entry| {
+                let path = entry.path();
+                Entry {
+                    name: entry.file_name().to_string_lossy().into_owned(),
+                    is_dir: path.is_dir(),
+                    path,
+                }
+            })
+            .collect();
+        // Directories first, then everything else by name.
+        entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+        if let Some(parent) = directory.parent() {
+            entries.insert(
+                0,
+                Entry {
+                    name: String::from(".."),
+                    path: parent.to_path_buf(),
+                    is_dir: true,
+                },
+            );
+        }
+        self.directory = directory;
+        self.entries = entries;
+        self.selected = 0;
+        self.scroll_offset = 0;
+        self.mark_redraw(true);
+        Ok(())
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Returns the selected entry, and whether it is a directory.
+    pub fn selected_entry(&self) -> Option<(&Path, bool)> {
+        self.entries
+            .get(self.selected)
+            .map(|entry| (entry.path.as_path(), entry.is_dir))
+    }
+
+    pub fn handle_move_command(&mut self, command: Move) {
+        let last = self.entries.len().saturating_sub(1);
+        let page = self.size.height.saturating_sub(1).max(1);
+        self.selected = match command {
+            Move::Up => self.selected.saturating_sub(1),
+            Move::Down => self.selected.saturating_add(1).min(last),
+            Move::Left | Move::Right => self.selected,
+            Move::PageUp => self.selected.saturating_sub(page),
+            Move::PageDown => self.selected.saturating_add(page).min(last),
+            Move::Home => 0,
+            Move::End => last,
+        };
+        self.scroll_selection_into_view();
+        self.mark_redraw(true);
+    }
+
+    fn scroll_selection_into_view(&mut self) {
+        let height = self.size.height;
+        if self.selected < self.scroll_offset {
+            self.scroll_offset = self.selected;
+        } else if self.selected >= self.scroll_offset.saturating_add(height) {
+            self.scroll_offset = self.selected.saturating_sub(height).saturating_add(1);
+        }
+    }
+}
+
+impl UIComponent for FileBrowser {
+    fn mark_redraw(&mut self, value: bool) {
+        self.needs_redraw = value;
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+
+    fn set_size(&mut self, to: Size) {
+        self.size = to;
+        self.scroll_selection_into_view();
+    }
+
+    fn draw(&mut self, origin: Position) -> Result<(), Error> {
+        let Size { height, width } = self.size;
+        for current_row in 0..height {
+            let row_origin = Position {
+                col: origin.col,
+                row: origin.row.saturating_add(current_row),
+            };
+            let index = current_row.saturating_add(self.scroll_offset);
+            let Some(entry) = self.entries.get(index) else {
+                Terminal::print_at(row_origin, width, "~", UiElement::Text)?;
+                continue;
+            };
+            let suffix = if entry.is_dir { "/" } else { "" };
+            let text = Line::from_label(&format!("{}{suffix}", entry.name));
+            let visible = text.get_visible_graphemes(0..width);
+            if index == self.selected {
+                Terminal::print_at(row_origin, width, &visible, UiElement::SelectedEntry)?;
+            } else {
+                Terminal::print_at(row_origin, width, &visible, UiElement::Text)?;
+            }
+        }
+        Ok(())
+    }
+}
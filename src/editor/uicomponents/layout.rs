@@ -0,0 +1,384 @@
+use std::mem::replace;
+
+use crate::prelude::*;
+
+pub type PaneId = usize;
+
+const DEFAULT_SHARE: usize = 50;
+const MIN_SHARE: usize = 10;
+const MAX_SHARE: usize = 90;
+const SHARE_STEP: usize = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SplitDirection {
+    /// The panes are stacked on top of each other.
+    Horizontal,
+    /// The panes are side by side, separated by a divider column.
+    Vertical,
+}
+
+enum Node {
+    Pane(PaneId),
+    Split {
+        direction: SplitDirection,
+        // Percentage of the available space which goes to `first`.
+        first_share: usize,
+        first: Box<Node>,
+        second: Box<Node>,
+    },
+}
+
+impl Node {
+    fn contains(&self, pane: PaneId) -> bool {
+        match self {
+            Self::Pane(id) => *id == pane,
+            Self::Split { first, second, .. } => first.contains(pane) || second.contains(pane),
+        }
+    }
+
+    fn first_pane(&self) -> PaneId {
+        match self {
+            Self::Pane(id) => *id,
+            Self::Split { first, .. } => first.first_pane(),
+        }
+    }
+
+    fn collect_panes(&self, panes: &mut Vec<PaneId>) {
+        match self {
+            Self::Pane(id) => panes.push(*id),
+            Self::Split { first, second, .. } => {
+                first.collect_panes(panes);
+                second.collect_panes(panes);
+            }
+        }
+    }
+
+    fn split(&mut self, pane: PaneId, new_pane: PaneId, direction: SplitDirection) -> bool {
+        match self {
+            Self::Pane(id) if *id == pane => {
+                *self = Self::Split {
+                    direction,
+                    first_share: DEFAULT_SHARE,
+                    first: Box::new(Self::Pane(pane)),
+                    second: Box::new(Self::Pane(new_pane)),
+                };
+                true
+            }
+            Self::Pane(_) => false,
+            Self::Split { first, second, .. } => {
+                first.split(pane, new_pane, direction) || second.split(pane, new_pane, direction)
+            }
+        }
+    }
+
+    // Replaces the split directly containing `pane` with the sibling of
+    // `pane`, and returns the pane which should be focused instead.
+    fn remove(&mut self, pane: PaneId) -> Option<PaneId> {
+        let Self::Split { first, second, .. } = self else {
+            return None;
+        };
+        let sibling = if matches!(**first, Self::Pane(id) if id == pane) {
+            second
+        } else if matches!(**second, Self::Pane(id) if id == pane) {
+            first
+        } else {
+            return first.remove(pane).or_else(|| second.remove(pane));
+        };
+        let sibling = replace(&mut **sibling, Self::Pane(pane));
+        let focus = sibling.first_pane();
+        *self = sibling;
+        Some(focus)
+    }
+
+    // Adjusts the innermost split containing `pane` in its favor if `grow`,
+    // or in favor of its sibling otherwise.
+    fn resize(&mut self, pane: PaneId, grow: bool) -> bool {
+        let Self::Split {
+            first_share,
+            first,
+            second,
+            ..
+        } = self
+        else {
+            return false;
+        };
+        let grow_first = if first.contains(pane) {
+            if first.resize(pane, grow) {
+                return true;
+            }
+            grow
+        } else if second.contains(pane) {
+            if second.resize(pane, grow) {
+                return true;
+            }
+            !grow
+        } else {
+            return false;
+        };
+        *first_share = if grow_first {
+            first_share.saturating_add(SHARE_STEP).min(MAX_SHARE)
+        } else {
+            first_share.saturating_sub(SHARE_STEP).max(MIN_SHARE)
+        };
+        true
+    }
+
+    fn arrange(&self, area: Rect, panes: &mut Vec<(PaneId, Rect)>, dividers: &mut Vec<Rect>) {
+        let Self::Split {
+            direction,
+            first_share,
+            first,
+            second,
+        } = self
+        else {
+            if let Self::Pane(id) = self {
+                panes.push((*id, area));
+            }
+            return;
+        };
+        let Rect { origin, size } = area;
+        let share_of = |available: usize| {
+            available
+                .saturating_mul(*first_share)
+                .checked_div(100)
+                .unwrap_or(0)
+        };
+        let (first_area, second_area) = match direction {
+            SplitDirection::Horizontal => {
+                let first_height = share_of(size.height);
+                (
+                    Rect {
+                        origin,
+                        size: Size {
+                            height: first_height,
+                            width: size.width,
+                        },
+                    },
+                    Rect {
+                        origin: Position {
+                            col: origin.col,
+                            row: origin.row.saturating_add(first_height),
+                        },
+                        size: Size {
+                            height: size.height.saturating_sub(first_height),
+                            width: size.width,
+                        },
+                    },
+                )
+            }
+            SplitDirection::Vertical => {
+                let available = size.width.saturating_sub(1);
+                let first_width = share_of(available);
+                dividers.push(Rect {
+                    origin: Position {
+                        col: origin.col.saturating_add(first_width),
+                        row: origin.row,
+                    },
+                    size: Size {
+                        height: size.height,
+                        width: 1,
+                    },
+                });
+                (
+                    Rect {
+                        origin,
+                        size: Size {
+                            height: size.height,
+                            width: first_width,
+                        },
+                    },
+                    Rect {
+                        origin: Position {
+                            col: origin.col.saturating_add(first_width).saturating_add(1),
+                            row: origin.row,
+                        },
+                        size: Size {
+                            height: size.height,
+                            width: available.saturating_sub(first_width),
+                        },
+                    },
+                )
+            }
+        };
+        first.arrange(first_area, panes, dividers);
+        second.arrange(second_area, panes, dividers);
+    }
+}
+
+/// Divides the screen among panes, by recursively splitting it in two.
+pub struct Layout {
+    root: Node,
+    next_id: PaneId,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            root: Node::Pane(0),
+            next_id: 1,
+        }
+    }
+}
+
+impl Layout {
+    /// Splits `pane` in two, and returns the id of the new second half.
+    pub fn split(&mut self, pane: PaneId, direction: SplitDirection) -> PaneId {
+        let new_pane = self.next_id;
+        self.next_id = self.next_id.saturating_add(1);
+        let found = self.root.split(pane, new_pane, direction);
+        debug_assert!(found, "Attempting to split a pane which doesn't exist");
+        new_pane
+    }
+
+    /// Removes `pane`, giving its space to its sibling, and returns the pane
+    /// to focus instead. Returns `None` if `pane` is the only one.
+    pub fn close(&mut self, pane: PaneId) -> Option<PaneId> {
+        self.root.remove(pane)
+    }
+
+    /// Grows or shrinks `pane` at the expense of its sibling. Returns `false`
+    /// if there is nothing to resize.
+    pub fn resize(&mut self, pane: PaneId, grow: bool) -> bool {
+        self.root.resize(pane, grow)
+    }
+
+    /// Returns all panes in order, from top left to bottom right.
+    pub fn panes(&self) -> Vec<PaneId> {
+        let mut panes = Vec::new();
+        self.root.collect_panes(&mut panes);
+        panes
+    }
+
+    /// Returns the pane after `pane`, going back to the first one after the
+    /// last one.
+    pub fn next_pane(&self, pane: PaneId) -> PaneId {
+        let panes = self.panes();
+        panes
+            .iter()
+            .position(|id| *id == pane)
+            .and_then(|index| panes.get(index.saturating_add(1)))
+            .or(panes.first())
+            .copied()
+            .unwrap_or(pane)
+    }
+
+    /// Returns the area of every pane, and those of the dividers between them.
+    pub fn arrange(&self, area: Rect) -> (Vec<(PaneId, Rect)>, Vec<Rect>) {
+        let mut panes = Vec::new();
+        let mut dividers = Vec::new();
+        self.root.arrange(area, &mut panes, &mut dividers);
+        (panes, dividers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(col: ColIdx, row: RowIdx, width: usize, height: usize) -> Rect {
+        Rect {
+            origin: Position { col, row },
+            size: Size { height, width },
+        }
+    }
+
+    // Rects as (col, row, width, height), to be compared easily.
+    fn corners(rects: impl IntoIterator<Item = Rect>) -> Vec<(usize, usize, usize, usize)> {
+        rects
+            .into_iter()
+            .map(|rect| {
+                (
+                    rect.origin.col,
+                    rect.origin.row,
+                    rect.size.width,
+                    rect.size.height,
+                )
+            })
+            .collect()
+    }
+
+    fn pane_areas(layout: &Layout, area: Rect) -> Vec<(usize, usize, usize, usize)> {
+        corners(layout.arrange(area).0.into_iter().map(|(_, rect)| rect))
+    }
+
+    #[test]
+    fn splitting_puts_the_new_pane_after_the_split_one() {
+        let mut layout = Layout::default();
+        let right = layout.split(0, SplitDirection::Vertical);
+        let bottom_left = layout.split(0, SplitDirection::Horizontal);
+        assert_eq!(layout.panes(), vec![0, bottom_left, right]);
+        assert_eq!(
+            pane_areas(&layout, area(0, 0, 81, 20)),
+            vec![(0, 0, 40, 10), (0, 10, 40, 10), (41, 0, 40, 20)]
+        );
+    }
+
+    #[test]
+    fn odd_sizes_give_the_remainder_to_the_second_pane() {
+        let mut layout = Layout::default();
+        layout.split(0, SplitDirection::Vertical);
+        let (panes, dividers) = layout.arrange(area(2, 1, 82, 5));
+        assert_eq!(
+            corners(panes.into_iter().map(|(_, rect)| rect)),
+            vec![(2, 1, 40, 5), (43, 1, 41, 5)]
+        );
+        assert_eq!(corners(dividers), vec![(42, 1, 1, 5)]);
+
+        let mut layout = Layout::default();
+        layout.split(0, SplitDirection::Horizontal);
+        assert_eq!(
+            pane_areas(&layout, area(0, 0, 10, 25)),
+            vec![(0, 0, 10, 12), (0, 12, 10, 13)]
+        );
+    }
+
+    #[test]
+    fn resizing_changes_the_share_within_bounds() {
+        let mut layout = Layout::default();
+        assert!(!layout.resize(0, true));
+        let bottom = layout.split(0, SplitDirection::Horizontal);
+        assert!(layout.resize(bottom, true));
+        assert_eq!(
+            pane_areas(&layout, area(0, 0, 10, 20)),
+            vec![(0, 0, 10, 9), (0, 9, 10, 11)]
+        );
+        for _ in 0..20 {
+            layout.resize(0, true);
+        }
+        assert_eq!(
+            pane_areas(&layout, area(0, 0, 10, 20)),
+            vec![(0, 0, 10, 18), (0, 18, 10, 2)]
+        );
+    }
+
+    #[test]
+    fn closing_a_pane_gives_its_space_to_its_sibling() {
+        let mut layout = Layout::default();
+        let right = layout.split(0, SplitDirection::Vertical);
+        let bottom_right = layout.split(right, SplitDirection::Horizontal);
+        assert_eq!(layout.close(right), Some(bottom_right));
+        assert_eq!(layout.panes(), vec![0, bottom_right]);
+        assert_eq!(
+            pane_areas(&layout, area(0, 0, 81, 20)),
+            vec![(0, 0, 40, 20), (41, 0, 40, 20)]
+        );
+        assert_eq!(layout.close(0), Some(bottom_right));
+        assert_eq!(layout.panes(), vec![bottom_right]);
+        assert_eq!(layout.close(bottom_right), None);
+        assert_eq!(
+            pane_areas(&layout, area(0, 0, 81, 20)),
+            vec![(0, 0, 81, 20)]
+        );
+    }
+
+    #[test]
+    fn focus_moves_through_the_panes_in_order() {
+        let mut layout = Layout::default();
+        assert_eq!(layout.next_pane(0), 0);
+        let right = layout.split(0, SplitDirection::Vertical);
+        let bottom_left = layout.split(0, SplitDirection::Horizontal);
+        assert_eq!(layout.next_pane(0), bottom_left);
+        assert_eq!(layout.next_pane(bottom_left), right);
+        assert_eq!(layout.next_pane(right), 0);
+    }
+}
@@ -3,17 +3,29 @@ use std::time::{Duration, Instant};
 use crate::prelude::*;
 
 use super::super::terminal::Terminal;
+use super::super::UiElement;
 use super::UIComponent;
 
-#[derive(Default)]
 pub struct MessageBar {
     current_message: Message,
+    duration: Duration,
     needs_redraw: bool,
     cleared_after_expiry: bool,
 }
 
 const DEFAULT_DURATION: Duration = Duration::new(5, 0); // 5 seconds
 
+impl Default for MessageBar {
+    fn default() -> Self {
+        Self {
+            current_message: Message::default(),
+            duration: DEFAULT_DURATION,
+            needs_redraw: false,
+            cleared_after_expiry: false,
+        }
+    }
+}
+
 struct Message {
     text: String,
     time: Instant,
@@ -29,12 +41,16 @@ impl Default for Message {
 }
 
 impl Message {
-    fn is_expired(&self) -> bool {
-        Instant::now().duration_since(self.time) > DEFAULT_DURATION
+    fn is_expired(&self, duration: Duration) -> bool {
+        Instant::now().duration_since(self.time) > duration
     }
 }
 
 impl MessageBar {
+    pub fn set_duration(&mut self, duration: Duration) {
+        self.duration = duration;
+    }
+
     pub fn update_message(&mut self, new_message: &str) {
         self.current_message = Message {
             text: new_message.to_string(),
@@ -51,20 +67,21 @@ impl UIComponent for MessageBar {
     }
 
     fn needs_redraw(&self) -> bool {
-        (!self.cleared_after_expiry && self.current_message.is_expired()) || self.needs_redraw
+        (!self.cleared_after_expiry && self.current_message.is_expired(self.duration))
+            || self.needs_redraw
     }
 
     fn set_size(&mut self, _to: Size) {
         // MessageBar does not need to handle size changes
     }
 
-    fn draw(&mut self, origin: RowIdx) -> Result<(), std::io::Error> {
-        let message = if self.current_message.is_expired() {
+    fn draw(&mut self, origin: Position) -> Result<(), std::io::Error> {
+        let message = if self.current_message.is_expired(self.duration) {
             self.cleared_after_expiry = true;
             ""
         } else {
             &self.current_message.text
         };
-        Terminal::print_row(origin, message)
+        Terminal::print_row(origin.row, message, UiElement::MessageBar)
     }
 }
@@ -1,11 +1,15 @@
 mod commandbar;
+mod filebrowser;
+mod layout;
 mod messagebar;
 mod statusbar;
 mod uicomponent;
 mod view;
 
 pub use commandbar::CommandBar;
+pub use filebrowser::FileBrowser;
+pub use layout::{Layout, PaneId, SplitDirection};
 pub use messagebar::MessageBar;
 pub use statusbar::StatusBar;
 pub use uicomponent::UIComponent;
-pub use view::View;
+pub use view::{Buffer, Highlighter, ReadOnly, View};
@@ -1,11 +1,12 @@
 use crate::prelude::*;
 
-use super::super::{documentstatus::DocumentStatus, terminal::Terminal};
+use super::super::{documentstatus::DocumentStatus, terminal::Terminal, Line, UiElement};
 use super::UIComponent;
 
 #[derive(Default)]
 pub struct StatusBar {
     current_status: DocumentStatus,
+    is_focused: bool,
     needs_redraw: bool,
     size: Size,
 }
@@ -17,6 +18,13 @@ impl StatusBar {
             self.mark_redraw(true);
         }
     }
+
+    pub fn set_focused(&mut self, is_focused: bool) {
+        if self.is_focused != is_focused {
+            self.is_focused = is_focused;
+            self.mark_redraw(true);
+        }
+    }
 }
 
 impl UIComponent for StatusBar {
@@ -32,23 +40,36 @@ impl UIComponent for StatusBar {
         self.size = to;
     }
 
-    fn draw(&mut self, origin_y: RowIdx) -> Result<(), std::io::Error> {
+    fn draw(&mut self, origin: Position) -> Result<(), std::io::Error> {
         let line_count = self.current_status.line_count_to_string();
         let modified_indicator = self.current_status.modified_indicator_to_string();
+        let buffer_indicator = self.current_status.buffer_indicator_to_string();
+        let read_only_indicator = self.current_status.read_only_indicator_to_string();
+        // File names may hold line breaks, which would break the bar.
+        let file_name = Line::from_label(&self.current_status.file_name);
         let beginning = format!(
-            "{} - {line_count} {modified_indicator}",
-            self.current_status.file_name
+            "{buffer_indicator}{file_name}{read_only_indicator} - {line_count} {modified_indicator}"
         );
 
         let position_indicator = self.current_status.position_indicator_to_string();
+        let file_type = self.current_status.file_type_to_string();
+        let encoding = self.current_status.encoding_to_string();
+        let line_ending = self.current_status.line_ending_to_string();
+        let back_part = format!("{file_type} | {encoding} | {line_ending} | {position_indicator}");
         let remainder_len = self.size.width.saturating_sub(beginning.len());
-        let status = format!("{beginning}{position_indicator:>remainder_len$}");
+        let status = format!("{beginning}{back_part:>remainder_len$}");
+        // Narrow panes only get as much of the file part as fits.
         let to_print = if status.len() <= self.size.width {
             status
         } else {
-            String::new()
+            Line::from(&beginning).get_visible_graphemes(0..self.size.width)
+        };
+        let element = if self.is_focused {
+            UiElement::StatusBar
+        } else {
+            UiElement::InactiveStatusBar
         };
-        Terminal::print_inverted_row(origin_y, &to_print)?;
+        Terminal::print_at(origin, self.size.width, &to_print, element)?;
         Ok(())
     }
 }
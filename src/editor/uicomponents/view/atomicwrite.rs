@@ -0,0 +1,276 @@
+use std::fs::{self, File, Metadata, OpenOptions};
+use std::io::{BufWriter, Error, ErrorKind, Write};
+#[cfg(unix)]
+use std::os::unix::fs::{fchown, MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Replaces the file at `path` with what `write_contents` writes, such that
+/// the file either keeps its old contents or gets all of the new ones, even
+/// if writing fails midway or the system crashes. The contents go to a
+/// temporary file next to it first, which is then renamed over it. If
+/// `make_backup`, the old contents are kept in a copy with `~` appended to
+/// its name. If `private`, only the owner may read the file; otherwise it
+/// keeps the permissions and, where possible, the owner of the file it
+/// replaces.
+pub fn write_atomically(
+    path: &Path,
+    make_backup: bool,
+    private: bool,
+    write_contents: impl FnOnce(&mut BufWriter<File>) -> Result<(), Error>,
+) -> Result<(), Error> {
+    // Writing through a symlink should replace its target, not the link.
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let existing = fs::metadata(&path).ok();
+    let temp_path = temp_path_for(&path);
+    let temp_file = match create_temp_file(&temp_path, private) {
+        Ok(file) => file,
+        // The file may be writable even though its directory isn't, so it
+        // can still be overwritten, just not replaced.
+        Err(err) if err.kind() == ErrorKind::PermissionDenied && existing.is_some() => {
+            return write_in_place(&path, make_backup, write_contents);
+        }
+        Err(err) => {
+            return Err(with_context(
+                &err,
+                &format!("Could not create temporary file {}", temp_path.display()),
+            ))
+        }
+    };
+
+    let existing = existing.filter(|_| !private);
+    let result = write_temp_file(temp_file, existing.as_ref(), write_contents)
+        .and_then(|()| {
+            if make_backup && existing.is_some() {
+                back_up(&path)?;
+            }
+            Ok(())
+        })
+        .and_then(|()| {
+            fs::rename(&temp_path, &path)
+                .map_err(|err| with_context(&err, &format!("Could not replace {}", path.display())))
+        });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return result;
+    }
+    // Makes the rename itself durable. The new contents are already safe at
+    // this point, so failing here isn't worth reporting.
+    if let Some(directory) = path.parent() {
+        if let Ok(directory) = File::open(directory) {
+            let _ = directory.sync_all();
+        }
+    }
+    Ok(())
+}
+
+// The temporary file is private from the start if it's meant to be, so its
+// contents are never exposed.
+fn create_temp_file(temp_path: &Path, private: bool) -> Result<File, Error> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if private {
+        options.mode(0o600);
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+    options.open(temp_path)
+}
+
+fn write_temp_file(
+    file: File,
+    existing: Option<&Metadata>,
+    write_contents: impl FnOnce(&mut BufWriter<File>) -> Result<(), Error>,
+) -> Result<(), Error> {
+    if let Some(existing) = existing {
+        // Only root may give files away, and other users may only pick
+        // among their own groups, so this is kept where possible.
+        #[cfg(unix)]
+        let _ = fchown(&file, Some(existing.uid()), Some(existing.gid()));
+        file.set_permissions(existing.permissions())
+            .map_err(|err| with_context(&err, "Could not keep the file's permissions"))?;
+    }
+    write_and_sync(file, write_contents)
+}
+
+// Overwrites the file without the safety of a temporary file, for when
+// there's nowhere to put one.
+fn write_in_place(
+    path: &Path,
+    make_backup: bool,
+    write_contents: impl FnOnce(&mut BufWriter<File>) -> Result<(), Error>,
+) -> Result<(), Error> {
+    if make_backup {
+        back_up(path)?;
+    }
+    let file = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(path)
+        .map_err(|err| with_context(&err, &format!("Could not open {}", path.display())))?;
+    write_and_sync(file, write_contents)
+}
+
+fn write_and_sync(
+    file: File,
+    write_contents: impl FnOnce(&mut BufWriter<File>) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut writer = BufWriter::new(file);
+    write_contents(&mut writer)
+        .and_then(|()| writer.flush())
+        .map_err(|err| with_context(&err, "Could not write the file"))?;
+    writer
+        .get_ref()
+        .sync_all()
+        .map_err(|err| with_context(&err, "Could not flush the file to disk"))
+}
+
+fn back_up(path: &Path) -> Result<(), Error> {
+    let backup_path = append_to_file_name(path, "~");
+    fs::copy(path, &backup_path).map_err(|err| {
+        with_context(
+            &err,
+            &format!("Could not back up to {}", backup_path.display()),
+        )
+    })?;
+    Ok(())
+}
+
+fn temp_path_for(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{name}.{}.tmp", process::id()))
+}
+
+fn append_to_file_name(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+fn with_context(err: &Error, context: &str) -> Error {
+    Error::new(err.kind(), format!("{context}: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    #[cfg(unix)]
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    use super::*;
+
+    // Runs `test` in a directory of its own, which is removed afterwards.
+    fn in_temp_dir(name: &str, test: impl FnOnce(&Path)) {
+        let dir = temp_dir().join(format!("hecto-test-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        test(&dir);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn write(path: &Path, make_backup: bool, private: bool, text: &str) -> Result<(), Error> {
+        write_atomically(path, make_backup, private, |writer| {
+            writer.write_all(text.as_bytes())
+        })
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn files_are_replaced_without_leaving_the_temporary_file() {
+        in_temp_dir("replace", |dir| {
+            let path = dir.join("file.txt");
+            write(&path, false, false, "old").unwrap();
+            write(&path, false, false, "new").unwrap();
+            assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+            assert_eq!(file_names(dir), vec!["file.txt"]);
+        });
+    }
+
+    #[test]
+    fn failed_writes_keep_the_old_contents() {
+        in_temp_dir("fail", |dir| {
+            let path = dir.join("file.txt");
+            write(&path, false, false, "old").unwrap();
+            let result = write_atomically(&path, true, false, |writer| {
+                writer.write_all(b"partial")?;
+                Err(Error::other("disk full"))
+            });
+            assert!(result.is_err());
+            assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+            assert_eq!(file_names(dir), vec!["file.txt"]);
+        });
+    }
+
+    #[test]
+    fn backups_keep_the_old_contents() {
+        in_temp_dir("backup", |dir| {
+            let path = dir.join("file.txt");
+            write(&path, true, false, "old").unwrap();
+            assert_eq!(file_names(dir), vec!["file.txt"]);
+            write(&path, true, false, "new").unwrap();
+            assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+            assert_eq!(fs::read_to_string(dir.join("file.txt~")).unwrap(), "old");
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn permissions_are_kept_unless_private() {
+        in_temp_dir("permissions", |dir| {
+            let path = dir.join("file.txt");
+            let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+            write(&path, false, false, "old").unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+            write(&path, false, false, "new").unwrap();
+            assert_eq!(mode(&path), 0o640);
+            write(&path, false, true, "secret").unwrap();
+            assert_eq!(mode(&path), 0o600);
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_written_through() {
+        in_temp_dir("symlink", |dir| {
+            let target = dir.join("target.txt");
+            let link = dir.join("link.txt");
+            write(&target, false, false, "old").unwrap();
+            symlink(&target, &link).unwrap();
+            write(&link, false, false, "new").unwrap();
+            assert!(fs::symlink_metadata(&link)
+                .unwrap()
+                .file_type()
+                .is_symlink());
+            assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn writing_in_place_keeps_the_file_itself() {
+        in_temp_dir("in-place", |dir| {
+            let path = dir.join("file.txt");
+            write(&path, false, false, "old text").unwrap();
+            let inode = fs::metadata(&path).unwrap().ino();
+            write_in_place(&path, true, |writer| writer.write_all(b"new")).unwrap();
+            assert_eq!(fs::metadata(&path).unwrap().ino(), inode);
+            assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+            assert_eq!(
+                fs::read_to_string(dir.join("file.txt~")).unwrap(),
+                "old text"
+            );
+        });
+    }
+}
@@ -1,21 +1,51 @@
 use std::ops::Range;
 use std::{
-    fs::{read_to_string, File},
-    io::{Error, Write},
+    io::{Error, ErrorKind, Write},
+    path::PathBuf,
 };
 
 use crate::{editor::annotatedstring::AnnotatedString, prelude::*};
 
-use super::Highlighter;
+use super::SearchPattern;
+use super::TextFile;
+use super::{create_syntax_highlighter, Highlighter, SyntaxHighlighter};
 
+use super::write_atomically;
+use super::Encoding;
 use super::FileInfo;
+use super::FileType;
 use super::Line;
+use super::LineEnding;
+use super::LineRope;
+use super::ReadOnly;
+use super::{
+    is_swap_file, read_swap_file, remove_swap_file, swap_path_for, unified_diff, write_swap_file,
+};
+use super::{Change, History, TextPosition};
 
 #[derive(Default)]
 pub struct Buffer {
-    lines: Vec<Line>,
+    lines: LineRope,
     file_info: FileInfo,
     dirty: bool,
+    history: History,
+    // Increases with every change to `lines`, so views can tell when to redraw.
+    revision: usize,
+    syntax_highlighter: Option<Box<dyn SyntaxHighlighter>>,
+    // Converting the line endings changes the file without changing any line.
+    saved_line_ending: LineEnding,
+    // Whether the swap file holds this buffer's changes, rather than those
+    // left behind by an earlier session, and as of which revision.
+    owns_swap_file: bool,
+    swapped_revision: Option<usize>,
+    // The swap file which couldn't be written, so that the failure is only
+    // reported once, rather than on every update until the file is renamed.
+    failed_swap_path: Option<PathBuf>,
+    // Large files aren't highlighted or backed up to a swap file, to keep
+    // them responsive.
+    is_large: bool,
+    // Binary files are read-only regardless.
+    read_only: Option<ReadOnly>,
 }
 
 impl Buffer {
@@ -23,6 +53,10 @@ impl Buffer {
         self.dirty
     }
 
+    pub const fn revision(&self) -> usize {
+        self.revision
+    }
+
     pub const fn get_file_info(&self) -> &FileInfo {
         &self.file_info
     }
@@ -37,33 +71,101 @@ impl Buffer {
             .map_or(0, |line| line.width_until(until))
     }
 
+    pub fn wrap_line(
+        &self,
+        line_index: LineIdx,
+        width: ColIdx,
+        at_words: bool,
+    ) -> Vec<Range<GraphemeIdx>> {
+        self.lines.get(line_index).map_or_else(
+            || Line::default().wrap(width, at_words),
+            |line| line.wrap(width, at_words),
+        )
+    }
+
     pub fn load(file_name: &str) -> Result<Self, Error> {
-        let contents = read_to_string(file_name)?;
-        let mut lines = Vec::new();
-        for value in contents.lines() {
-            lines.push(Line::from(value));
-        }
+        let file = TextFile::read(file_name)?;
+        let mut file_info = FileInfo::from(file_name);
+        file_info.record_disk_state();
+        file_info.set_encoding(file.encoding);
+        file_info.set_line_ending(file.line_ending);
+        file_info.set_final_newline(file.has_final_newline);
+        let syntax_highlighter = if file.is_large {
+            None
+        } else {
+            create_syntax_highlighter(file_info.get_file_type())
+        };
+        let read_only = (!file_info.is_writable()).then_some(ReadOnly::NotWritable);
         Ok(Self {
-            lines,
-            file_info: FileInfo::from(file_name),
+            lines: file.lines,
+            syntax_highlighter,
+            file_info,
             dirty: false,
+            history: History::default(),
+            revision: 0,
+            saved_line_ending: file.line_ending,
+            owns_swap_file: false,
+            swapped_revision: None,
+            failed_swap_path: None,
+            is_large: file.is_large,
+            read_only,
         })
     }
 
-    pub fn save_as(&mut self, file_name: &str) -> Result<(), Error> {
-        let file_info = FileInfo::from(file_name);
-        self.save_to_file(&file_info)?;
+    /// Returns a buffer holding `text` which isn't backed by any file.
+    pub fn from_text(text: &str) -> Self {
+        Self {
+            lines: Line::split_text(text).collect(),
+            ..Self::default()
+        }
+    }
+
+    pub fn save_as(&mut self, file_name: &str, make_backup: bool) -> Result<(), Error> {
+        let file_info = FileInfo::from(file_name).with_format_of(&self.file_info);
+        self.save_to_file(&file_info, make_backup)?;
+        if !self.is_large && file_info.get_file_type() != self.file_info.get_file_type() {
+            self.syntax_highlighter = create_syntax_highlighter(file_info.get_file_type());
+        }
+        // The changes are safe now, and the swap file would be left behind
+        // under the old name.
+        let _ = self.remove_swap_file();
         self.file_info = file_info;
-        self.dirty = false;
+        // The buffer is backed by the file just written now.
+        self.read_only = None;
+        self.mark_saved();
         Ok(())
     }
 
-    fn save_to_file(&self, file_info: &FileInfo) -> Result<(), Error> {
+    fn save_to_file(&self, file_info: &FileInfo, make_backup: bool) -> Result<(), Error> {
         if let Some(path) = file_info.get_path() {
-            let mut file = File::create(path)?;
-            for line in &self.lines {
-                writeln!(file, "{line}")?;
+            let encoding = file_info.get_encoding();
+            let separator = encoding.encode(file_info.get_line_ending().as_str())?;
+            let crlf = encoding.encode(LineEnding::Crlf.as_str())?;
+            // Lines not read yet would come from the changed file.
+            if self.lines.has_unread_lines() && self.is_changed_on_disk() {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "The file changed on disk since it was opened, reload it first",
+                ));
             }
+            // The file may be written in place, so nothing can be read from
+            // it afterwards.
+            self.lines.read_all()?;
+            write_atomically(path, make_backup, false, |file| {
+                file.write_all(encoding.bom())?;
+                let mut lines = self.lines.iter().peekable();
+                while let Some(line) = lines.next() {
+                    file.write_all(&encoding.encode(line)?)?;
+                    if lines.peek().is_some() || file_info.has_final_newline() {
+                        if line.has_carriage_return() {
+                            file.write_all(&crlf)?;
+                        } else {
+                            file.write_all(&separator)?;
+                        }
+                    }
+                }
+                Ok(())
+            })?;
         } else {
             #[cfg(debug_assertions)]
             {
@@ -73,48 +175,503 @@ impl Buffer {
         Ok(())
     }
 
-    pub fn save(&mut self) -> Result<(), Error> {
-        self.save_to_file(&self.file_info)?;
+    pub fn save(&mut self, make_backup: bool) -> Result<(), Error> {
+        self.save_to_file(&self.file_info, make_backup)?;
+        self.mark_saved();
+        Ok(())
+    }
+
+    /// Reads the file again, discarding the undo history.
+    pub fn reload(&mut self) -> Result<(), Error> {
+        let Some(file_name) = self
+            .file_info
+            .get_path()
+            .and_then(|path| path.to_str())
+            .map(String::from)
+        else {
+            return Ok(());
+        };
+        let revision = self.revision.wrapping_add(1);
+        let requested_read_only = self.read_only == Some(ReadOnly::Requested);
+        let _ = self.remove_swap_file();
+        *self = Self::load(&file_name)?;
+        if requested_read_only {
+            self.set_read_only();
+        }
+        // Views on this buffer need to notice the change.
+        self.revision = revision;
+        Ok(())
+    }
+
+    pub const fn is_large(&self) -> bool {
+        self.is_large
+    }
+
+    /// Returns why the buffer can't be edited, if it can't.
+    pub fn read_only(&self) -> Option<ReadOnly> {
+        if self.file_info.get_encoding() == Encoding::Binary {
+            Some(ReadOnly::Binary)
+        } else {
+            self.read_only
+        }
+    }
+
+    /// Highlights the buffer as `file_type`, whatever its name suggests.
+    pub fn set_file_type(&mut self, file_type: FileType) {
+        self.file_info.set_file_type(file_type);
+        if !self.is_large {
+            self.syntax_highlighter = create_syntax_highlighter(file_type);
+        }
+    }
+
+    /// Lays all lines out anew, e.g. after the tab width changed.
+    pub fn reset_line_layout(&mut self) {
+        // Lines not read yet are laid out once they are.
+        self.lines.iter_read_mut().for_each(Line::reset_fragments);
+    }
+
+    /// Keeps the buffer from being edited, e.g. to view a file without
+    /// risking changes to it.
+    pub fn set_read_only(&mut self) {
+        self.read_only.get_or_insert(ReadOnly::Requested);
+    }
+
+    pub fn is_changed_on_disk(&self) -> bool {
+        self.file_info.is_changed_on_disk()
+    }
+
+    fn mark_saved(&mut self) {
+        self.file_info.record_disk_state();
+        self.history.mark_saved();
+        self.saved_line_ending = self.file_info.get_line_ending();
         self.dirty = false;
+        // Failing to clean up doesn't make the save any less successful.
+        let _ = self.remove_swap_file();
+    }
+
+    fn swap_path(&self) -> Option<PathBuf> {
+        self.file_info.get_path().map(swap_path_for)
+    }
+
+    /// Returns whether there's a swap file left behind by an earlier session
+    /// which didn't exit cleanly.
+    pub fn has_leftover_swap_file(&self) -> bool {
+        !self.owns_swap_file && self.swap_path().is_some_and(|path| is_swap_file(&path))
+    }
+
+    /// Writes the unsaved changes to the swap file if they changed since the
+    /// last time, or removes it once there are none. A leftover swap file is
+    /// kept as it is until it's recovered or discarded.
+    pub fn update_swap_file(&mut self) -> Result<(), Error> {
+        let Some(swap_path) = self.swap_path() else {
+            return Ok(());
+        };
+        // Writing it would read all the lines of a large file into memory.
+        if !self.dirty || self.is_large {
+            return self.remove_swap_file();
+        }
+        // Leftover swap files, and files of that name which aren't swap files
+        // at all, are left as they are.
+        let is_taken = !self.owns_swap_file && swap_path.exists();
+        let has_failed = self.failed_swap_path.as_ref() == Some(&swap_path);
+        if self.swapped_revision == Some(self.revision) || is_taken || has_failed {
+            return Ok(());
+        }
+        self.swapped_revision = Some(self.revision);
+        if let Err(err) = write_swap_file(&swap_path, &self.text()) {
+            self.failed_swap_path = Some(swap_path);
+            return Err(err);
+        }
+        self.owns_swap_file = true;
         Ok(())
     }
 
+    /// Removes the swap file, unless it was left behind by an earlier session.
+    pub fn remove_swap_file(&mut self) -> Result<(), Error> {
+        if !self.owns_swap_file {
+            return Ok(());
+        }
+        self.owns_swap_file = false;
+        self.swapped_revision = None;
+        self.swap_path()
+            .map_or(Ok(()), |path| remove_swap_file(&path))
+    }
+
+    /// Replaces the text with that of the leftover swap file as a single
+    /// undoable step, which leaves the buffer modified until it's saved.
+    pub fn recover_from_swap_file(&mut self) -> Result<(), Error> {
+        let Some(swap_path) = self.swap_path() else {
+            return Ok(());
+        };
+        let text = read_swap_file(&swap_path)?;
+        let end = self.to_location(self.end_position());
+        self.replace_range(Location::default(), end, &text);
+        self.owns_swap_file = true;
+        Ok(())
+    }
+
+    pub fn discard_swap_file(&mut self) -> Result<(), Error> {
+        self.owns_swap_file = true;
+        self.remove_swap_file()
+    }
+
+    /// Describes how the leftover swap file differs from the text.
+    pub fn diff_against_swap_file(&self) -> Result<String, Error> {
+        let Some(swap_path) = self.swap_path() else {
+            return Ok(String::new());
+        };
+        let recovered = read_swap_file(&swap_path)?;
+        let current = self.text();
+        let diff = unified_diff(
+            &current.split('\n').collect::<Vec<_>>(),
+            &recovered.split('\n').collect::<Vec<_>>(),
+        );
+        Ok(format!(
+            "--- {} (on disk)\n+++ {} (unsaved changes)\n{diff}",
+            self.file_info, self.file_info
+        ))
+    }
+
+    /// Switches to the next line ending, and returns it. Mixed line endings
+    /// are unified by replacing each CRLF with LF, as a single undoable step.
+    pub fn convert_line_endings(&mut self, caret: Location) -> LineEnding {
+        let from = self.file_info.get_line_ending();
+        self.history.begin_group();
+        // The final line break isn't part of the text, so it's changed
+        // along with the line ending rather than undone with the text.
+        if let Some(last) = self.lines.last_mut() {
+            last.set_carriage_return(false);
+        }
+        for line_index in 0..self.height().saturating_sub(1) {
+            let Some(len) = self
+                .lines
+                .get(line_index)
+                .filter(|line| line.has_carriage_return())
+                .map(|line| line.len())
+            else {
+                continue;
+            };
+            let line_end = TextPosition {
+                line_index,
+                byte_index: len,
+            };
+            let next_line_start = TextPosition {
+                line_index: line_index.saturating_add(1),
+                byte_index: 0,
+            };
+            self.remove_between(line_end, next_line_start, caret);
+            self.insert_at_position(line_end, String::from("\n"), caret);
+        }
+        self.history.end_group();
+        let to = from.next();
+        self.file_info.set_line_ending(to);
+        self.update_dirty();
+        to
+    }
+
     pub fn insert_char(&mut self, character: char, at: Location) {
         debug_assert!(at.line_index <= self.height());
-        if at.line_index == self.height() {
-            self.lines.push(Line::from(&character.to_string()));
-            self.dirty = true;
-        } else if let Some(line) = self.lines.get_mut(at.line_index) {
-            line.insert_char(character, at.grapheme_index);
-            self.dirty = true;
-        }
+        self.insert_text(&character.to_string(), at);
     }
 
     pub fn delete(&mut self, at: Location) {
         if let Some(line) = self.lines.get(at.line_index) {
-            if at.grapheme_index >= line.grapheme_count()
-                && self.height() > at.line_index.saturating_add(1)
-            {
-                let next_line = self.lines.remove(at.line_index.saturating_add(1));
-                #[allow(clippy::indexing_slicing)]
-                self.lines[at.line_index].append(&next_line);
-                self.dirty = true;
-            } else if at.grapheme_index < line.grapheme_count() {
-                #[allow(clippy::indexing_slicing)]
-                self.lines[at.line_index].delete(at.grapheme_index);
-                self.dirty = true;
-            }
+            let end = if at.grapheme_index < line.grapheme_count() {
+                Location {
+                    grapheme_index: at.grapheme_index.saturating_add(1),
+                    line_index: at.line_index,
+                }
+            } else if self.height() > at.line_index.saturating_add(1) {
+                Location {
+                    grapheme_index: 0,
+                    line_index: at.line_index.saturating_add(1),
+                }
+            } else {
+                return;
+            };
+            self.remove_range(at, end);
         }
     }
 
     pub fn insert_newline(&mut self, at: Location) {
+        self.insert_text("\n", at);
+    }
+
+    pub fn delete_range(&mut self, start: Location, end: Location) {
+        self.remove_range(start, end);
+    }
+
+    /// Replaces the text between `start` and `end` with `text` as a single
+    /// undoable step, and returns the location right after the new text.
+    pub fn replace_range(&mut self, start: Location, end: Location, text: &str) -> Location {
+        self.history.begin_group();
+        self.remove_range(start, end);
+        let caret = self.insert_text(text, start);
+        self.history.end_group();
+        caret
+    }
+
+    /// Replaces the match of `pattern` starting at `at`, and returns the
+    /// location right after the replacement, or `None` if there's no match
+    /// at `at`.
+    pub fn replace_match(
+        &mut self,
+        at: Location,
+        pattern: &SearchPattern,
+        replacement: &str,
+    ) -> Option<Location> {
+        let from = self.to_text_position(at);
+        let matched = self.lines.get(from.line_index).and_then(|line| {
+            line.find_all(pattern, from.byte_index..line.len())
+                .into_iter()
+                .next()
+                .filter(|(range, _)| range.start == from.byte_index)
+        })?;
+        let to = TextPosition {
+            line_index: from.line_index,
+            byte_index: matched.0.end,
+        };
+        self.history.begin_group();
+        self.remove_between(from, to, at);
+        let caret = self.insert_at_position(from, replacement.to_string(), at);
+        self.history.end_group();
+        Some(caret)
+    }
+
+    /// Replaces every match of `pattern` from `from` to the end of the
+    /// document as a single undoable step, and returns how many were replaced.
+    pub fn replace_all(
+        &mut self,
+        pattern: &SearchPattern,
+        replacement: &str,
+        from: Location,
+    ) -> usize {
+        let mut count: usize = 0;
+        self.history.begin_group();
+        for line_index in from.line_index..self.height() {
+            let Some(line) = self.lines.get(line_index) else {
+                break;
+            };
+            let start_byte_idx = if line_index == from.line_index {
+                line.grapheme_idx_to_byte_idx(from.grapheme_index.min(line.grapheme_count()))
+            } else {
+                0
+            };
+            let matches = line.find_all(pattern, start_byte_idx..line.len());
+            // Replace from right to left, so that earlier byte indices stay valid.
+            for (range, _) in matches.into_iter().rev() {
+                let at = TextPosition {
+                    line_index,
+                    byte_index: range.start,
+                };
+                let to = TextPosition {
+                    line_index,
+                    byte_index: range.end,
+                };
+                self.remove_between(at, to, from);
+                self.insert_at_position(at, replacement.to_string(), from);
+                count = count.saturating_add(1);
+            }
+        }
+        self.history.end_group();
+        count
+    }
+
+    pub fn text_in_range(&self, start: Location, end: Location) -> String {
+        self.text_between(self.to_text_position(start), self.to_text_position(end))
+    }
+
+    fn insert_text(&mut self, text: &str, at: Location) -> Location {
+        // Inserting on the line just past the end of the document first
+        // needs to create that line.
+        let (position, text) = if at.line_index >= self.height() && !self.is_empty() {
+            (self.end_position(), format!("\n{text}"))
+        } else {
+            (self.to_text_position(at), text.to_string())
+        };
+        self.insert_at_position(position, text, at)
+    }
+
+    fn insert_at_position(
+        &mut self,
+        position: TextPosition,
+        text: String,
+        caret_before: Location,
+    ) -> Location {
+        let end = self.apply_insert(position, &text);
+        let caret_after = self.to_location(end);
+        self.history.record(
+            Change::Insert { at: position, text },
+            caret_before,
+            caret_after,
+        );
+        self.update_dirty();
+        caret_after
+    }
+
+    fn remove_range(&mut self, start: Location, end: Location) {
+        debug_assert!(start <= end);
+        self.remove_between(
+            self.to_text_position(start),
+            self.to_text_position(end),
+            start,
+        );
+    }
+
+    fn remove_between(&mut self, from: TextPosition, to: TextPosition, caret: Location) {
+        let text = self.text_between(from, to);
+        if text.is_empty() {
+            return;
+        }
+        self.apply_remove(from, &text);
+        self.history
+            .record(Change::Remove { at: from, text }, caret, caret);
+        self.update_dirty();
+    }
+
+    pub fn undo(&mut self) -> Option<Location> {
+        let (changes, caret) = self.history.undo()?;
+        self.apply_changes(&changes);
+        Some(caret)
+    }
+
+    pub fn redo(&mut self) -> Option<Location> {
+        let (changes, caret) = self.history.redo()?;
+        self.apply_changes(&changes);
+        Some(caret)
+    }
+
+    fn apply_changes(&mut self, changes: &[Change]) {
+        for change in changes {
+            match change {
+                Change::Insert { at, text } => {
+                    self.apply_insert(*at, text);
+                }
+                Change::Remove { at, text } => self.apply_remove(*at, text),
+            }
+        }
+        self.update_dirty();
+    }
+
+    fn update_dirty(&mut self) {
+        self.dirty = !self.history.is_at_save_point()
+            || self.file_info.get_line_ending() != self.saved_line_ending;
+    }
+
+    fn apply_insert(&mut self, at: TextPosition, text: &str) -> TextPosition {
+        self.revision = self.revision.wrapping_add(1);
         if at.line_index == self.height() {
             self.lines.push(Line::default());
-            self.dirty = true;
-        } else if let Some(line) = self.lines.get_mut(at.line_index) {
-            let new_line = line.split(at.grapheme_index);
-            self.lines.insert(at.line_index.saturating_add(1), new_line);
-            self.dirty = true;
+        }
+        let Some(line) = self.lines.get(at.line_index) else {
+            return at;
+        };
+        let (head, tail) = line.split_at(at.byte_index);
+        let combined = format!("{head}{text}{tail}");
+        // The line break the line ended in now ends the last inserted line.
+        let carriage_return = line.has_carriage_return();
+        let mut lines: Vec<Line> = Line::split_text(&combined).collect();
+        if let Some(last) = lines.last_mut() {
+            last.set_carriage_return(carriage_return);
+        }
+        self.lines.splice(at.line_index..=at.line_index, lines);
+        let end = at.advanced_by(text);
+        self.notify_lines_changed(
+            at.line_index,
+            1,
+            end.line_index
+                .saturating_sub(at.line_index)
+                .saturating_add(1),
+        );
+        end
+    }
+
+    fn apply_remove(&mut self, at: TextPosition, text: &str) {
+        self.revision = self.revision.wrapping_add(1);
+        let end = at.advanced_by(text);
+        let (Some(first), Some(last)) = (
+            self.lines.get(at.line_index),
+            self.lines.get(end.line_index),
+        ) else {
+            return;
+        };
+        let mut joined = Line::from(&format!(
+            "{}{}",
+            &first[..at.byte_index],
+            &last[end.byte_index..]
+        ));
+        joined.set_carriage_return(last.has_carriage_return());
+        self.lines.splice(at.line_index..=end.line_index, [joined]);
+        self.notify_lines_changed(
+            at.line_index,
+            end.line_index
+                .saturating_sub(at.line_index)
+                .saturating_add(1),
+            1,
+        );
+    }
+
+    fn notify_lines_changed(&mut self, at: LineIdx, removed: usize, inserted: usize) {
+        if let Some(syntax_highlighter) = &mut self.syntax_highlighter {
+            syntax_highlighter.lines_changed(at, removed, inserted);
+        }
+    }
+
+    fn text_between(&self, from: TextPosition, to: TextPosition) -> String {
+        let mut result = String::new();
+        for line_index in from.line_index..=to.line_index {
+            let Some(line) = self.lines.get(line_index) else {
+                break;
+            };
+            let start = if line_index == from.line_index {
+                from.byte_index
+            } else {
+                0
+            };
+            if line_index == to.line_index {
+                result.push_str(line.get(start..to.byte_index).unwrap_or_default());
+            } else {
+                result.push_str(line.get(start..).unwrap_or_default());
+                if line.has_carriage_return() {
+                    result.push('\r');
+                }
+                result.push('\n');
+            }
+        }
+        result
+    }
+
+    fn text(&self) -> String {
+        self.text_between(TextPosition::default(), self.end_position())
+    }
+
+    fn end_position(&self) -> TextPosition {
+        TextPosition {
+            line_index: self.height().saturating_sub(1),
+            byte_index: self.lines.last().map_or(0, |line| line.len()),
+        }
+    }
+
+    fn to_text_position(&self, at: Location) -> TextPosition {
+        if at.line_index >= self.height() {
+            return self.end_position();
+        }
+        TextPosition {
+            line_index: at.line_index,
+            byte_index: self
+                .lines
+                .get(at.line_index)
+                .map_or(0, |line| line.grapheme_idx_to_byte_idx(at.grapheme_index)),
+        }
+    }
+
+    fn to_location(&self, position: TextPosition) -> Location {
+        Location {
+            grapheme_index: self.lines.get(position.line_index).map_or(0, |line| {
+                line.byte_idx_to_grapheme_idx(position.byte_index)
+                    .unwrap_or_else(|| line.grapheme_count())
+            }),
+            line_index: position.line_index,
         }
     }
 
@@ -130,68 +687,73 @@ impl Buffer {
         self.file_info.has_path()
     }
 
-    pub fn search_forward(&self, query: &str, from: Location) -> Option<Location> {
-        if query.is_empty() {
-            return None;
-        }
-        let mut is_first = true;
-        for (line_index, line) in self
-            .lines
-            .iter()
-            .enumerate()
-            .cycle()
-            .skip(from.line_index)
-            .take(self.lines.len().saturating_add(1))
-        {
-            let from_grapheme_index = if is_first {
-                is_first = false;
-                from.grapheme_index
-            } else {
-                0
-            };
-            if let Some(grapheme_index) = line.search_forward(query, from_grapheme_index) {
-                return Some(Location {
-                    grapheme_index,
-                    line_index,
-                });
-            }
-        }
-        None
-    }
-
-    pub fn search_backward(&self, query: &str, from: Location) -> Option<Location> {
-        if query.is_empty() {
-            return None;
-        }
-        let mut is_first = true;
-        for (line_index, line) in self
-            .lines
-            .iter()
-            .enumerate()
-            .rev()
-            .cycle()
-            .skip(
-                self.lines
-                    .len()
-                    .saturating_sub(from.line_index)
-                    .saturating_sub(1),
-            )
-            .take(self.lines.len().saturating_add(1))
-        {
-            let from_grapheme_index = if is_first {
-                is_first = false;
-                from.grapheme_index
-            } else {
-                line.grapheme_count()
-            };
-            if let Some(grapheme_index) = line.search_backward(query, from_grapheme_index) {
-                return Some(Location {
+    pub fn search_forward(&self, pattern: &SearchPattern, from: Location) -> Option<Location> {
+        self.search_lines_forward(pattern, from, 0..self.search_length())
+    }
+
+    /// How many lines a search goes through: all of them, wrapping around to
+    /// end on the starting line again.
+    pub fn search_length(&self) -> usize {
+        self.height().saturating_add(1)
+    }
+
+    /// Searches only the lines `offsets` after `from`, so that a search can
+    /// be split up.
+    pub fn search_lines_forward(
+        &self,
+        pattern: &SearchPattern,
+        from: Location,
+        offsets: Range<usize>,
+    ) -> Option<Location> {
+        let height = self.height();
+        let line_indices = offsets.filter_map(|offset| {
+            let line_index = from.line_index.saturating_add(offset).checked_rem(height)?;
+            Some((line_index, offset))
+        });
+        self.lines
+            .find_map(line_indices, |line, line_index, offset| {
+                let from_grapheme_index = if offset == 0 { from.grapheme_index } else { 0 };
+                line.search_forward(pattern, from_grapheme_index)
+                    .map(|grapheme_index| Location {
+                        grapheme_index,
+                        line_index,
+                    })
+            })
+    }
+
+    pub fn search_backward(&self, pattern: &SearchPattern, from: Location) -> Option<Location> {
+        self.search_lines_backward(pattern, from, 0..self.search_length())
+    }
+
+    /// Searches only the lines `offsets` before `from`, so that a search can
+    /// be split up.
+    pub fn search_lines_backward(
+        &self,
+        pattern: &SearchPattern,
+        from: Location,
+        offsets: Range<usize>,
+    ) -> Option<Location> {
+        let height = self.height();
+        let from_line_index = from.line_index.min(height.saturating_sub(1));
+        let line_indices = offsets.filter_map(|offset| {
+            let line_index = from_line_index
+                .saturating_add(height)
+                .saturating_sub(offset)
+                .checked_rem(height)?;
+            Some((line_index, offset))
+        });
+        self.lines
+            .find_map(line_indices, |line, line_index, offset| {
+                let found = if offset == 0 {
+                    line.search_backward(pattern, from.grapheme_index)
+                } else {
+                    line.search_backward_from_end(pattern)
+                };
+                found.map(|grapheme_index| Location {
                     grapheme_index,
                     line_index,
-                });
-            }
-        }
-        None
+                })
+            })
     }
 
     pub fn get_highlighted_substring(
@@ -201,13 +763,133 @@ impl Buffer {
         highlighter: &Highlighter,
     ) -> Option<AnnotatedString> {
         self.lines.get(line_index).map(|line| {
-            line.get_annotated_visible_substr(range, Some(&highlighter.get_annotations(line_index)))
+            let mut annotations = self
+                .syntax_highlighter
+                .as_ref()
+                .and_then(|syntax_highlighter| syntax_highlighter.get_annotations(line_index))
+                .cloned()
+                .unwrap_or_default();
+            annotations.extend(highlighter.get_annotations(line_index));
+            line.get_annotated_visible_substr(range, Some(&annotations))
         })
     }
 
-    pub fn highlight(&mut self, line_index: LineIdx, highlighter: &mut Highlighter) {
-        if let Some(line) = self.lines.get_mut(line_index) {
-            highlighter.highlight(line_index, line);
+    /// Highlights the lines in `range`. Syntax highlighting depends on the
+    /// lines before, so it first catches up with any edits above `range`.
+    pub fn highlight(&mut self, range: Range<LineIdx>, highlighter: &mut Highlighter) {
+        if let Some(syntax_highlighter) = &mut self.syntax_highlighter {
+            let mut line_index = syntax_highlighter.first_stale_line();
+            while line_index < range.end {
+                let Some(line) = self.lines.get(line_index) else {
+                    break;
+                };
+                syntax_highlighter.highlight(line_index, line);
+                line_index = syntax_highlighter.first_stale_line();
+            }
         }
+        for line_index in range {
+            if let Some(line) = self.lines.get(line_index) {
+                highlighter.highlight(line_index, line);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::fs;
+    use std::process;
+
+    use super::*;
+
+    // Writes `contents` to a file of its own, loads it, lets `edit` change the
+    // buffer, and returns what saving it writes.
+    fn load_and_save(name: &str, contents: &[u8], edit: impl FnOnce(&mut Buffer)) -> Vec<u8> {
+        let path = temp_dir().join(format!("hecto-test-{}-{name}", process::id()));
+        let path_str = path.to_string_lossy().to_string();
+        fs::write(&path, contents).unwrap();
+        let mut buffer = Buffer::load(&path_str).unwrap();
+        edit(&mut buffer);
+        buffer.save(false).unwrap();
+        let saved = fs::read(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        saved
+    }
+
+    fn location(line_index: LineIdx, grapheme_index: GraphemeIdx) -> Location {
+        Location {
+            grapheme_index,
+            line_index,
+        }
+    }
+
+    #[test]
+    fn line_endings_are_saved_unchanged() {
+        for (name, contents) in [
+            ("lf", "a\nb\n"),
+            ("crlf", "a\r\nb\r\n"),
+            ("mixed", "a\r\nb\nc\r\n"),
+            ("mixed-noeol", "a\nb\r\nc"),
+            ("lone-cr", "a\rb\r\n\r\n"),
+            ("trailing-cr", "a\r\nb\nc\r"),
+        ] {
+            let saved = load_and_save(name, contents.as_bytes(), |_| {});
+            assert_eq!(String::from_utf8_lossy(&saved), contents, "{name}");
+        }
+    }
+
+    #[test]
+    fn mixed_line_endings_stay_with_their_lines() {
+        let saved = load_and_save("mixed-edit", b"ab\r\ncd\nef\r\n", |buffer| {
+            buffer.insert_newline(location(0, 1));
+            buffer.delete_range(location(2, 2), location(3, 0));
+        });
+        assert_eq!(String::from_utf8_lossy(&saved), "a\nb\r\ncdef\r\n");
+    }
+
+    #[test]
+    fn converting_mixed_line_endings_leaves_lf() {
+        let saved = load_and_save("mixed-convert", b"a\r\nb\nc\r\n", |buffer| {
+            assert_eq!(
+                buffer.convert_line_endings(Location::default()),
+                LineEnding::Lf
+            );
+        });
+        assert_eq!(String::from_utf8_lossy(&saved), "a\nb\nc\n");
+    }
+
+    #[test]
+    fn converting_crlf_and_back_round_trips() {
+        let saved = load_and_save("crlf-convert", b"a\r\nb\r\n", |buffer| {
+            buffer.convert_line_endings(Location::default());
+            buffer.convert_line_endings(Location::default());
+        });
+        assert_eq!(String::from_utf8_lossy(&saved), "a\r\nb\r\n");
+    }
+
+    #[test]
+    fn swap_files_which_cant_be_written_are_given_up_on() {
+        let dir = temp_dir().join(format!("hecto-test-{}-swap-dir", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file.txt");
+        fs::write(&path, "text").unwrap();
+        let mut buffer = Buffer::load(&path.to_string_lossy()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        buffer.insert_newline(location(0, 1));
+        assert!(buffer.update_swap_file().is_err());
+        buffer.insert_newline(location(0, 1));
+        assert!(buffer.update_swap_file().is_ok());
+
+        let new_path = temp_dir().join(format!("hecto-test-{}-swap-moved", process::id()));
+        buffer.save_as(&new_path.to_string_lossy(), false).unwrap();
+        buffer.insert_newline(location(0, 1));
+        let result = buffer.update_swap_file();
+        let swap_path = swap_path_for(&new_path);
+        let has_swap_file = swap_path.exists();
+        let _ = fs::remove_file(&swap_path);
+        let _ = fs::remove_file(&new_path);
+        assert!(result.is_ok() && has_swap_file);
     }
 }
@@ -1,14 +1,52 @@
+#[cfg(unix)]
+use std::{ffi::CString, os::unix::ffi::OsStrExt};
 use std::{
     fmt::{self, Display},
+    fs::{self, Metadata},
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 
-use super::super::super::FileType;
+use super::super::super::{Encoding, FileType, LineEnding};
 
-#[derive(Default, Debug)]
+// Enough of a file's metadata to tell whether someone else wrote to it.
+#[derive(Debug, PartialEq, Eq)]
+struct DiskState {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl From<Metadata> for DiskState {
+    fn from(metadata: Metadata) -> Self {
+        Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct FileInfo {
     path: Option<PathBuf>,
     file_type: FileType,
+    encoding: Encoding,
+    line_ending: LineEnding,
+    has_final_newline: bool,
+    // As of the last time the file was loaded or saved.
+    disk_state: Option<DiskState>,
+}
+
+impl Default for FileInfo {
+    fn default() -> Self {
+        Self {
+            path: None,
+            file_type: FileType::default(),
+            encoding: Encoding::default(),
+            line_ending: LineEnding::default(),
+            has_final_newline: true,
+            disk_state: None,
+        }
+    }
 }
 
 impl FileInfo {
@@ -16,7 +54,7 @@ impl FileInfo {
         let path = PathBuf::from(file_name);
         let file_type = if path
             .extension()
-            .map_or(false, |ext| ext.eq_ignore_ascii_case("rs"))
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("rs"))
         {
             FileType::Rust
         } else {
@@ -25,9 +63,19 @@ impl FileInfo {
         Self {
             path: Some(path),
             file_type,
+            ..Self::default()
         }
     }
 
+    /// Takes over how `other` is encoded and ends its lines, e.g. when saving
+    /// under a new name.
+    pub const fn with_format_of(mut self, other: &Self) -> Self {
+        self.encoding = other.encoding;
+        self.line_ending = other.line_ending;
+        self.has_final_newline = other.has_final_newline;
+        self
+    }
+
     pub fn get_path(&self) -> Option<&Path> {
         self.path.as_deref()
     }
@@ -39,6 +87,80 @@ impl FileInfo {
     pub const fn get_file_type(&self) -> FileType {
         self.file_type
     }
+
+    pub const fn get_encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    pub fn set_file_type(&mut self, file_type: FileType) {
+        self.file_type = file_type;
+    }
+
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.encoding = encoding;
+    }
+
+    pub const fn get_line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending = line_ending;
+    }
+
+    pub const fn has_final_newline(&self) -> bool {
+        self.has_final_newline
+    }
+
+    pub fn set_final_newline(&mut self, has_final_newline: bool) {
+        self.has_final_newline = has_final_newline;
+    }
+
+    /// Remembers the file's current metadata, to compare against later.
+    pub fn record_disk_state(&mut self) {
+        self.disk_state = self.read_disk_state();
+    }
+
+    /// Returns whether the file was written to since it was loaded or saved.
+    /// A file which was deleted in the meantime doesn't count.
+    pub fn is_changed_on_disk(&self) -> bool {
+        match (&self.disk_state, self.read_disk_state()) {
+            (Some(recorded), Some(current)) => *recorded != current,
+            _ => false,
+        }
+    }
+
+    /// Returns whether the file may be written to. Files which don't exist
+    /// yet count as writable.
+    pub fn is_writable(&self) -> bool {
+        self.get_path().is_none_or(|path| {
+            fs::metadata(path).map_or(true, |metadata| {
+                !metadata.permissions().readonly() && may_write(path)
+            })
+        })
+    }
+
+    fn read_disk_state(&self) -> Option<DiskState> {
+        self.get_path()
+            .and_then(|path| fs::metadata(path).ok())
+            .map(DiskState::from)
+    }
+}
+
+// Unlike the permission bits, this takes the user's groups, root and
+// read-only file systems into account.
+#[cfg(unix)]
+fn may_write(path: &Path) -> bool {
+    let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // SAFETY: `path` is a NUL-terminated string, which outlives the call.
+    unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+}
+
+#[cfg(not(unix))]
+fn may_write(_path: &Path) -> bool {
+    true
 }
 
 impl Display for FileInfo {
@@ -51,3 +173,43 @@ impl Display for FileInfo {
         write!(formatter, "{name}")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::process;
+
+    use super::*;
+
+    fn is_writable(path: &Path) -> bool {
+        FileInfo::from(&path.to_string_lossy()).is_writable()
+    }
+
+    #[test]
+    fn read_only_files_are_not_writable() {
+        let path = temp_dir().join(format!("hecto-test-{}-writable", process::id()));
+        assert!(is_writable(&path));
+        fs::write(&path, "text").unwrap();
+        assert!(is_writable(&path));
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&path, permissions).unwrap();
+        let writable = is_writable(&path);
+        let _ = fs::remove_file(&path);
+        assert!(!writable);
+    }
+
+    // Opening a FIFO for writing would wait for a reader.
+    #[cfg(unix)]
+    #[test]
+    fn fifos_are_checked_without_opening_them() {
+        let path = temp_dir().join(format!("hecto-test-{}-fifo", process::id()));
+        let created = process::Command::new("mkfifo").arg(&path).status();
+        if !created.is_ok_and(|status| status.success()) {
+            return;
+        }
+        let writable = is_writable(&path);
+        let _ = fs::remove_file(&path);
+        assert!(writable);
+    }
+}
@@ -1,51 +1,57 @@
 use crate::editor::filetype::FileType;
-use crate::editor::Line;
 use crate::editor::{Annotation, AnnotationType};
+use crate::editor::{Line, SearchPattern};
 use crate::prelude::*;
+use std::ops::Range;
 
 mod rustsyntaxhighlighter;
 use rustsyntaxhighlighter::RustSyntaxHighlighter;
 mod syntaxhighlighter;
-use syntaxhighlighter::SyntaxHighlighter;
+pub use syntaxhighlighter::SyntaxHighlighter;
 mod searchresulthighlighter;
 use searchresulthighlighter::SearchResultHighlighter;
+mod selectionhighlighter;
+use selectionhighlighter::SelectionHighlighter;
 
-fn create_syntax_highlighter(file_type: FileType) -> Option<Box<dyn SyntaxHighlighter>> {
+pub fn create_syntax_highlighter(file_type: FileType) -> Option<Box<dyn SyntaxHighlighter>> {
     match file_type {
         FileType::Rust => Some(Box::<RustSyntaxHighlighter>::default()),
         FileType::Text => None,
     }
 }
 
+/// Highlights what depends on the view rather than the buffer. Syntax
+/// highlighting is kept by the buffer, as it survives between draws.
 #[derive(Default)]
+#[allow(clippy::struct_field_names)]
 pub struct Highlighter<'a> {
-    synttax_highlighter: Option<Box<dyn SyntaxHighlighter>>,
     search_result_highlighter: Option<SearchResultHighlighter<'a>>,
+    selection_highlighter: Option<SelectionHighlighter>,
 }
 
 impl<'a> Highlighter<'a> {
     pub fn new(
-        matched_word: Option<&'a str>,
+        search_pattern: Option<&'a SearchPattern>,
         selected_match: Option<Location>,
-        file_type: FileType,
+        selection: Option<Range<Location>>,
     ) -> Self {
         let search_result_highlighter =
-            matched_word.map(|word| SearchResultHighlighter::new(word, selected_match));
+            search_pattern.map(|pattern| SearchResultHighlighter::new(pattern, selected_match));
         Highlighter {
-            synttax_highlighter: create_syntax_highlighter(file_type),
             search_result_highlighter,
+            selection_highlighter: selection.map(SelectionHighlighter::new),
         }
     }
 
     pub fn get_annotations(&self, line_index: LineIdx) -> Vec<Annotation> {
         let mut result = Vec::new();
-        if let Some(syntax_highlighter) = &self.synttax_highlighter {
-            if let Some(annotations) = syntax_highlighter.get_annotations(line_index) {
+        if let Some(search_result_highlighter) = &self.search_result_highlighter {
+            if let Some(annotations) = search_result_highlighter.get_annotations(line_index) {
                 result.extend(annotations.iter().copied());
             }
         }
-        if let Some(search_result_highlighter) = &self.search_result_highlighter {
-            if let Some(annotations) = search_result_highlighter.get_annotations(line_index) {
+        if let Some(selection_highlighter) = &self.selection_highlighter {
+            if let Some(annotations) = selection_highlighter.get_annotations(line_index) {
                 result.extend(annotations.iter().copied());
             }
         }
@@ -53,11 +59,11 @@ impl<'a> Highlighter<'a> {
     }
 
     pub fn highlight(&mut self, line_index: LineIdx, line: &Line) {
-        if let Some(syntax_highlighter) = &mut self.synttax_highlighter {
-            syntax_highlighter.highlight(line_index, line);
-        }
         if let Some(search_result_highlighter) = &mut self.search_result_highlighter {
             search_result_highlighter.highlight(line_index, line);
         }
+        if let Some(selection_highlighter) = &mut self.selection_highlighter {
+            selection_highlighter.highlight(line_index, line);
+        }
     }
 }
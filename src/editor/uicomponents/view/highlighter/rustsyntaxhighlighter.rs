@@ -1,3 +1,5 @@
+use std::collections::BTreeSet;
+
 use unicode_segmentation::UnicodeSegmentation;
 
 use crate::editor::{Annotation, AnnotationType, Line};
@@ -67,9 +69,28 @@ const TYPES: [&str; 22] = [
 
 const KNOWN_VALUES: [&str; 6] = ["Some", "None", "Ok", "Err", "true", "false"];
 
+// What is left open from one line to the next.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+struct LineState {
+    ml_comment_balance: usize,
+    in_ml_string: bool,
+}
+
+#[derive(Default)]
+struct HighlightedLine {
+    annotations: Vec<Annotation>,
+    // The state the line was highlighted in, and the one it ended in. `None`
+    // for lines which were edited since they were highlighted.
+    states: Option<(LineState, LineState)>,
+}
+
 #[derive(Default)]
 pub struct RustSyntaxHighlighter {
-    highlights: Vec<Vec<Annotation>>,
+    highlights: Vec<HighlightedLine>,
+    first_stale_line: LineIdx,
+    // Where further runs of stale lines start below `first_stale_line`, e.g.
+    // when lines far apart were edited between draws.
+    stale_lines: BTreeSet<LineIdx>,
     ml_comment_balance: usize,
     in_ml_string: bool,
 }
@@ -244,7 +265,16 @@ fn is_numeric_literal(word: &str) -> bool {
 
 impl SyntaxHighlighter for RustSyntaxHighlighter {
     fn highlight(&mut self, idx: LineIdx, line: &Line) {
-        debug_assert_eq!(idx, self.highlights.len());
+        debug_assert_eq!(idx, self.first_stale_line);
+        let start_state = idx
+            .checked_sub(1)
+            .and_then(|previous| self.highlights.get(previous))
+            .and_then(|highlighted| highlighted.states)
+            .map(|(_, end_state)| end_state)
+            .unwrap_or_default();
+        self.ml_comment_balance = start_state.ml_comment_balance;
+        self.in_ml_string = start_state.in_ml_string;
+
         let mut result = Vec::new();
         let mut iterator = line.split_word_bound_indices().peekable();
         if let Some(annotation) = self.initial_annotation(line) {
@@ -269,11 +299,82 @@ impl SyntaxHighlighter for RustSyntaxHighlighter {
                 }
             }
         }
-        self.highlights.push(result);
+
+        let state = LineState {
+            ml_comment_balance: self.ml_comment_balance,
+            in_ml_string: self.in_ml_string,
+        };
+        let highlighted = HighlightedLine {
+            annotations: result,
+            states: Some((start_state, state)),
+        };
+        if let Some(previous) = self.highlights.get_mut(idx) {
+            *previous = highlighted;
+        } else {
+            self.highlights.push(highlighted);
+        }
+        // If the next line was highlighted in the state this one ends in, it's
+        // still up to date, and so are those after it until the next stale one.
+        let next = idx.saturating_add(1);
+        let next_is_current = !self.stale_lines.contains(&next)
+            && self
+                .highlights
+                .get(next)
+                .and_then(|highlighted| highlighted.states)
+                .is_some_and(|(start_state, _)| start_state == state);
+        self.first_stale_line = if next_is_current {
+            self.stale_lines
+                .range(next..)
+                .next()
+                .copied()
+                .unwrap_or(self.highlights.len())
+                .min(self.highlights.len())
+        } else {
+            next
+        };
+        self.stale_lines.remove(&self.first_stale_line);
     }
 
     fn get_annotations(&self, idx: LineIdx) -> Option<&Vec<Annotation>> {
-        self.highlights.get(idx)
+        self.highlights
+            .get(idx)
+            .filter(|_| idx < self.first_stale_line)
+            .map(|highlighted| &highlighted.annotations)
+    }
+
+    fn first_stale_line(&self) -> LineIdx {
+        self.first_stale_line
+    }
+
+    fn lines_changed(&mut self, at: LineIdx, removed: usize, inserted: usize) {
+        if at >= self.highlights.len() {
+            return;
+        }
+        let end = at.saturating_add(removed).min(self.highlights.len());
+        self.highlights
+            .splice(at..end, (0..inserted).map(|_| HighlightedLine::default()));
+        let shift = |idx: LineIdx| {
+            idx.saturating_sub(end)
+                .saturating_add(at)
+                .saturating_add(inserted)
+        };
+        let len = self.highlights.len();
+        let mut stale_lines = self.stale_lines.split_off(&at);
+        if self.first_stale_line > at {
+            stale_lines.insert(self.first_stale_line);
+            self.first_stale_line = at;
+        } else if self.first_stale_line < at && at < len {
+            // The lines from `at` on, or the one after removed lines, no longer
+            // follow the line they were highlighted after.
+            self.stale_lines.insert(at);
+        }
+        self.stale_lines.extend(
+            stale_lines
+                .into_iter()
+                .filter(|idx| *idx >= end)
+                .map(shift)
+                .filter(|idx| *idx < len),
+        );
     }
 }
 
@@ -355,3 +456,78 @@ where
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Highlights stale lines until `lines` are up to date, and returns which
+    // lines that took.
+    fn highlight(highlighter: &mut RustSyntaxHighlighter, lines: &[&str]) -> Vec<LineIdx> {
+        let mut indices = Vec::new();
+        while let Some(line) = lines.get(highlighter.first_stale_line()) {
+            let idx = highlighter.first_stale_line();
+            highlighter.highlight(idx, &Line::from(line));
+            indices.push(idx);
+        }
+        indices
+    }
+
+    fn is_comment(highlighter: &RustSyntaxHighlighter, idx: LineIdx) -> bool {
+        highlighter.get_annotations(idx).is_some_and(|annotations| {
+            annotations
+                .iter()
+                .any(|annotation| annotation.annotation_type == AnnotationType::Comment)
+        })
+    }
+
+    #[test]
+    fn opening_and_closing_a_block_comment_rehighlights_the_lines_after_it() {
+        let mut highlighter = RustSyntaxHighlighter::default();
+        let mut lines = vec!["let a = 1;", "let b = 2;", "let c = 3;"];
+        assert_eq!(highlight(&mut highlighter, &lines), vec![0, 1, 2]);
+
+        lines[0] = "/* let a = 1;";
+        highlighter.lines_changed(0, 1, 1);
+        highlighter.highlight(0, &Line::from(lines[0]));
+        assert_eq!(highlighter.first_stale_line(), 1);
+        assert!(highlighter.get_annotations(2).is_none());
+        assert_eq!(highlight(&mut highlighter, &lines), vec![1, 2]);
+        assert!((0..3).all(|idx| is_comment(&highlighter, idx)));
+
+        lines[1] = "let b = 2; */";
+        highlighter.lines_changed(1, 1, 1);
+        assert_eq!(highlight(&mut highlighter, &lines), vec![1, 2]);
+        assert!(!is_comment(&highlighter, 2));
+    }
+
+    #[test]
+    fn highlighting_stops_where_the_state_is_unchanged() {
+        let mut highlighter = RustSyntaxHighlighter::default();
+        let mut lines = vec!["let x = 1;"; 10];
+        highlight(&mut highlighter, &lines);
+
+        lines[2] = "let y = 2;";
+        lines[7] = "let z = 3;";
+        highlighter.lines_changed(2, 1, 1);
+        highlighter.lines_changed(7, 1, 1);
+        assert_eq!(highlight(&mut highlighter, &lines), vec![2, 7]);
+        assert!((0..10).all(|idx| highlighter.get_annotations(idx).is_some()));
+    }
+
+    #[test]
+    fn stale_lines_move_with_inserted_and_removed_lines() {
+        let mut highlighter = RustSyntaxHighlighter::default();
+        let mut lines = vec!["let x = 1;"; 10];
+        highlight(&mut highlighter, &lines);
+
+        lines[7] = "/* let z = 3;";
+        highlighter.lines_changed(7, 1, 1);
+        lines.insert(2, "let y = 2;");
+        highlighter.lines_changed(2, 0, 1);
+        lines.remove(5);
+        highlighter.lines_changed(5, 1, 0);
+        assert_eq!(highlight(&mut highlighter, &lines), vec![2, 5, 7, 8, 9]);
+        assert!(is_comment(&highlighter, 9));
+    }
+}
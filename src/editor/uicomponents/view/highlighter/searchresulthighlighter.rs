@@ -1,66 +1,51 @@
-use super::{syntaxhighlighter::SyntaxHighlighter, Annotation, AnnotationType, Line};
+use super::{Annotation, AnnotationType, Line};
+use crate::editor::SearchPattern;
 use crate::prelude::*;
 use std::collections::HashMap;
 
-#[derive(Default)]
 pub struct SearchResultHighlighter<'a> {
-    matched_word: &'a str,
+    pattern: &'a SearchPattern,
     selected_match: Option<Location>,
     highlights: HashMap<LineIdx, Vec<Annotation>>,
 }
 
 impl<'a> SearchResultHighlighter<'a> {
-    pub fn new(matched_word: &'a str, selected_match: Option<Location>) -> Self {
+    pub fn new(pattern: &'a SearchPattern, selected_match: Option<Location>) -> Self {
         Self {
-            matched_word,
+            pattern,
             selected_match,
             highlights: HashMap::new(),
         }
     }
 
-    fn highlight_matched_words(&self, line: &Line, result: &mut Vec<Annotation>) {
-        if self.matched_word.is_empty() {
-            return;
-        }
-        line.find_all(self.matched_word, 0..line.len())
-            .iter()
-            .for_each(|(start, _)| {
+    fn highlight_matches(&self, line_index: LineIdx, line: &Line, result: &mut Vec<Annotation>) {
+        let selected_grapheme_idx = self
+            .selected_match
+            .filter(|selected_match| selected_match.line_index == line_index)
+            .map(|selected_match| selected_match.grapheme_index);
+        line.find_all(self.pattern, 0..line.len())
+            .into_iter()
+            .for_each(|(range, grapheme_idx)| {
+                let annotation_type = if selected_grapheme_idx == Some(grapheme_idx) {
+                    AnnotationType::SelectedMatch
+                } else {
+                    AnnotationType::Match
+                };
                 result.push(Annotation {
-                    annotation_type: AnnotationType::Match,
-                    start: *start,
-                    end: start.saturating_add(self.matched_word.len()),
+                    annotation_type,
+                    start: range.start,
+                    end: range.end,
                 });
             });
     }
 
-    fn highlight_selected_match(&self, result: &mut Vec<Annotation>) {
-        if let Some(selected_match) = self.selected_match {
-            if self.matched_word.is_empty() {
-                return;
-            }
-            let start = selected_match.grapheme_index;
-            result.push(Annotation {
-                annotation_type: AnnotationType::SelectedMatch,
-                start,
-                end: start.saturating_add(self.matched_word.len()),
-            });
-        }
-    }
-}
-
-impl<'a> SyntaxHighlighter for SearchResultHighlighter<'a> {
-    fn highlight(&mut self, line_index: LineIdx, line: &Line) {
+    pub fn highlight(&mut self, line_index: LineIdx, line: &Line) {
         let mut result = Vec::new();
-        self.highlight_matched_words(line, &mut result);
-        if let Some(selected_match) = self.selected_match {
-            if selected_match.line_index == line_index {
-                self.highlight_selected_match(&mut result);
-            }
-        }
+        self.highlight_matches(line_index, line, &mut result);
         self.highlights.insert(line_index, result);
     }
 
-    fn get_annotations(&self, line_index: LineIdx) -> Option<&Vec<Annotation>> {
+    pub fn get_annotations(&self, line_index: LineIdx) -> Option<&Vec<Annotation>> {
         self.highlights.get(&line_index)
     }
 }
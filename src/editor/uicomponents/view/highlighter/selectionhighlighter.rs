@@ -0,0 +1,49 @@
+use super::{Annotation, AnnotationType, Line};
+use crate::prelude::*;
+use std::{collections::HashMap, ops::Range};
+
+#[derive(Default)]
+pub struct SelectionHighlighter {
+    selection: Range<Location>,
+    highlights: HashMap<LineIdx, Vec<Annotation>>,
+}
+
+impl SelectionHighlighter {
+    pub fn new(selection: Range<Location>) -> Self {
+        Self {
+            selection,
+            highlights: HashMap::new(),
+        }
+    }
+
+    pub fn highlight(&mut self, line_index: LineIdx, line: &Line) {
+        let Range { start, end } = self.selection;
+        if line_index < start.line_index || line_index > end.line_index {
+            return;
+        }
+        let start_byte_idx = if line_index == start.line_index {
+            line.grapheme_idx_to_byte_idx(start.grapheme_index.min(line.grapheme_count()))
+        } else {
+            0
+        };
+        let end_byte_idx = if line_index == end.line_index {
+            line.grapheme_idx_to_byte_idx(end.grapheme_index.min(line.grapheme_count()))
+        } else {
+            line.len()
+        };
+        if start_byte_idx < end_byte_idx {
+            self.highlights.insert(
+                line_index,
+                vec![Annotation {
+                    annotation_type: AnnotationType::Selection,
+                    start: start_byte_idx,
+                    end: end_byte_idx,
+                }],
+            );
+        }
+    }
+
+    pub fn get_annotations(&self, line_index: LineIdx) -> Option<&Vec<Annotation>> {
+        self.highlights.get(&line_index)
+    }
+}
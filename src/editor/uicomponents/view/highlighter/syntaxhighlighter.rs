@@ -2,7 +2,14 @@ use super::Annotation;
 use super::Line;
 use crate::prelude::*;
 
+/// Highlights lines in order, keeping the results between draws so that
+/// only edited lines and those affected by them are highlighted again.
 pub trait SyntaxHighlighter {
     fn highlight(&mut self, idx: LineIdx, line: &Line);
     fn get_annotations(&self, idx: LineIdx) -> Option<&Vec<Annotation>>;
+    /// The line to highlight next: all lines before it are up to date.
+    fn first_stale_line(&self) -> LineIdx;
+    /// Called when the `removed` lines starting at `at` were replaced by
+    /// `inserted` new ones.
+    fn lines_changed(&mut self, at: LineIdx, removed: usize, inserted: usize);
 }
@@ -0,0 +1,439 @@
+use crate::prelude::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct TextPosition {
+    pub line_index: LineIdx,
+    pub byte_index: ByteIdx,
+}
+
+impl TextPosition {
+    /// Returns the position right after `text`, if it were inserted here.
+    pub fn advanced_by(self, text: &str) -> Self {
+        let line_breaks = text.matches('\n').count();
+        let last_segment_len = text.rsplit('\n').next().map_or(0, str::len);
+        Self {
+            line_index: self.line_index.saturating_add(line_breaks),
+            byte_index: if line_breaks == 0 {
+                self.byte_index.saturating_add(last_segment_len)
+            } else {
+                last_segment_len
+            },
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum Change {
+    Insert { at: TextPosition, text: String },
+    Remove { at: TextPosition, text: String },
+}
+
+impl Change {
+    fn inverted(&self) -> Self {
+        match self {
+            Self::Insert { at, text } => Self::Remove {
+                at: *at,
+                text: text.clone(),
+            },
+            Self::Remove { at, text } => Self::Insert {
+                at: *at,
+                text: text.clone(),
+            },
+        }
+    }
+
+    // Merges `next` into this change if both belong to the same run of typing
+    // or deleting, so that they can be undone in one step.
+    fn try_merge(&mut self, next: &Self) -> bool {
+        match (self, next) {
+            (
+                Self::Insert { at, text },
+                Self::Insert {
+                    at: next_at,
+                    text: next_text,
+                },
+            ) => {
+                let starts_new_word = text.ends_with(char::is_whitespace)
+                    && !next_text.starts_with(char::is_whitespace);
+                if next_text.contains('\n') || starts_new_word || *next_at != at.advanced_by(text) {
+                    return false;
+                }
+                text.push_str(next_text);
+                true
+            }
+            (
+                Self::Remove { at, text },
+                Self::Remove {
+                    at: next_at,
+                    text: next_text,
+                },
+            ) => {
+                if text.contains('\n')
+                    || next_text.contains('\n')
+                    || next_at.line_index != at.line_index
+                {
+                    return false;
+                }
+                if next_at.byte_index == at.byte_index {
+                    // Delete: the removed text follows what was removed before.
+                    text.push_str(next_text);
+                    true
+                } else if next_at.byte_index.saturating_add(next_text.len()) == at.byte_index {
+                    // Backspace: the removed text precedes what was removed before.
+                    text.insert_str(0, next_text);
+                    *at = *next_at;
+                    true
+                } else {
+                    false
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Transaction {
+    changes: Vec<Change>,
+    caret_before: Location,
+    caret_after: Location,
+    is_group: bool,
+}
+
+#[derive(Eq, PartialEq)]
+enum GroupState {
+    Closed,
+    Opened,
+    Recording,
+}
+
+pub struct History {
+    undo_stack: Vec<Transaction>,
+    redo_stack: Vec<Transaction>,
+    saved_at: Option<usize>,
+    group_state: GroupState,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            saved_at: Some(0),
+            group_state: GroupState::Closed,
+        }
+    }
+}
+
+impl History {
+    /// Records all changes until `end_group` as a single transaction.
+    pub fn begin_group(&mut self) {
+        debug_assert!(self.group_state == GroupState::Closed);
+        self.group_state = GroupState::Opened;
+    }
+
+    pub fn end_group(&mut self) {
+        self.group_state = GroupState::Closed;
+    }
+
+    pub fn record(&mut self, change: Change, caret_before: Location, caret_after: Location) {
+        if !self.redo_stack.is_empty() {
+            if self.saved_at > Some(self.undo_stack.len()) {
+                self.saved_at = None;
+            }
+            self.redo_stack.clear();
+        }
+        if self.group_state == GroupState::Recording {
+            if let Some(last) = self.undo_stack.last_mut() {
+                last.caret_after = caret_after;
+                last.changes.push(change);
+                return;
+            }
+        }
+        // Never merge into the transaction the file was saved at, otherwise
+        // undoing would skip past the saved state.
+        if self.group_state == GroupState::Closed && !self.is_at_save_point() {
+            if let Some(last) = self.undo_stack.last_mut().filter(|last| !last.is_group) {
+                if let Some(last_change) = last.changes.last_mut() {
+                    if last_change.try_merge(&change) {
+                        last.caret_after = caret_after;
+                        return;
+                    }
+                }
+            }
+        }
+        let is_group = self.group_state != GroupState::Closed;
+        if is_group {
+            self.group_state = GroupState::Recording;
+        }
+        self.undo_stack.push(Transaction {
+            changes: vec![change],
+            caret_before,
+            caret_after,
+            is_group,
+        });
+    }
+
+    /// Returns the changes needed to revert the most recent transaction, in the
+    /// order they need to be applied, and the caret location to restore.
+    pub fn undo(&mut self) -> Option<(Vec<Change>, Location)> {
+        let transaction = self.undo_stack.pop()?;
+        let changes = transaction
+            .changes
+            .iter()
+            .rev()
+            .map(Change::inverted)
+            .collect();
+        let caret = transaction.caret_before;
+        self.redo_stack.push(transaction);
+        Some((changes, caret))
+    }
+
+    /// Returns the changes needed to re-apply the most recently undone
+    /// transaction, and the caret location to restore.
+    pub fn redo(&mut self) -> Option<(Vec<Change>, Location)> {
+        let transaction = self.redo_stack.pop()?;
+        let changes = transaction.changes.clone();
+        let caret = transaction.caret_after;
+        self.undo_stack.push(transaction);
+        Some((changes, caret))
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved_at = Some(self.undo_stack.len());
+    }
+
+    pub fn is_at_save_point(&self) -> bool {
+        self.saved_at == Some(self.undo_stack.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(line_index: LineIdx, byte_index: ByteIdx) -> TextPosition {
+        TextPosition {
+            line_index,
+            byte_index,
+        }
+    }
+
+    fn caret(line_index: LineIdx, grapheme_index: GraphemeIdx) -> Location {
+        Location {
+            grapheme_index,
+            line_index,
+        }
+    }
+
+    fn insert(history: &mut History, position: TextPosition, text: &str) {
+        history.record(
+            Change::Insert {
+                at: position,
+                text: String::from(text),
+            },
+            caret(position.line_index, position.byte_index),
+            caret(position.line_index, position.byte_index),
+        );
+    }
+
+    fn remove(history: &mut History, position: TextPosition, text: &str) {
+        history.record(
+            Change::Remove {
+                at: position,
+                text: String::from(text),
+            },
+            caret(position.line_index, position.byte_index),
+            caret(position.line_index, position.byte_index),
+        );
+    }
+
+    // Shows changes like "+0:3 abc" for an insertion of "abc" at line 0, byte 3.
+    fn describe(changes: &[Change]) -> Vec<String> {
+        changes
+            .iter()
+            .map(|change| match change {
+                Change::Insert { at, text } => {
+                    format!("+{}:{} {text}", at.line_index, at.byte_index)
+                }
+                Change::Remove { at, text } => {
+                    format!("-{}:{} {text}", at.line_index, at.byte_index)
+                }
+            })
+            .collect()
+    }
+
+    fn undo(history: &mut History) -> Vec<String> {
+        history
+            .undo()
+            .map(|(changes, _)| describe(&changes))
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn advanced_by_moves_past_inserted_text() {
+        assert!(at(2, 3).advanced_by("ab") == at(2, 5));
+        assert!(at(2, 3).advanced_by("ab\ncde") == at(3, 3));
+        assert!(at(2, 3).advanced_by("\n") == at(3, 0));
+    }
+
+    #[test]
+    fn typing_a_word_is_undone_at_once() {
+        let mut history = History::default();
+        insert(&mut history, at(0, 0), "a");
+        insert(&mut history, at(0, 1), "b");
+        insert(&mut history, at(0, 2), "c");
+        assert_eq!(undo(&mut history), ["-0:0 abc"]);
+        assert!(history.undo().is_none());
+    }
+
+    #[test]
+    fn typing_is_undone_word_by_word() {
+        let mut history = History::default();
+        insert(&mut history, at(0, 0), "a");
+        insert(&mut history, at(0, 1), " ");
+        insert(&mut history, at(0, 2), "b");
+        assert_eq!(undo(&mut history), ["-0:2 b"]);
+        assert_eq!(undo(&mut history), ["-0:0 a "]);
+    }
+
+    #[test]
+    fn line_breaks_and_jumps_end_a_run_of_typing() {
+        let mut history = History::default();
+        insert(&mut history, at(0, 0), "a");
+        insert(&mut history, at(0, 1), "\n");
+        insert(&mut history, at(1, 0), "b");
+        insert(&mut history, at(5, 0), "c");
+        assert_eq!(undo(&mut history), ["-5:0 c"]);
+        assert_eq!(undo(&mut history), ["-1:0 b"]);
+        assert_eq!(undo(&mut history), ["-0:1 \n"]);
+        assert_eq!(undo(&mut history), ["-0:0 a"]);
+    }
+
+    #[test]
+    fn deleting_and_backspacing_are_merged() {
+        let mut history = History::default();
+        remove(&mut history, at(0, 4), "e");
+        remove(&mut history, at(0, 4), "f");
+        assert_eq!(undo(&mut history), ["+0:4 ef"]);
+
+        remove(&mut history, at(0, 4), "e");
+        remove(&mut history, at(0, 3), "d");
+        remove(&mut history, at(0, 2), "c");
+        assert_eq!(undo(&mut history), ["+0:2 cde"]);
+    }
+
+    #[test]
+    fn redo_reapplies_what_was_undone() {
+        let mut history = History::default();
+        insert(&mut history, at(0, 0), "a");
+        insert(&mut history, at(0, 1), "\n");
+        history.undo();
+        history.undo();
+        let redone = history.redo().map(|(changes, _)| describe(&changes));
+        assert_eq!(redone.unwrap_or_default(), ["+0:0 a"]);
+        let redone = history.redo().map(|(changes, _)| describe(&changes));
+        assert_eq!(redone.unwrap_or_default(), ["+0:1 \n"]);
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn groups_are_undone_as_one_in_reverse_order() {
+        let mut history = History::default();
+        insert(&mut history, at(0, 0), "a");
+        history.begin_group();
+        remove(&mut history, at(0, 0), "a");
+        insert(&mut history, at(0, 0), "b");
+        history.end_group();
+        insert(&mut history, at(0, 1), "c");
+        assert_eq!(undo(&mut history), ["-0:1 c"]);
+        assert_eq!(undo(&mut history), ["-0:0 b", "+0:0 a"]);
+        assert_eq!(undo(&mut history), ["-0:0 a"]);
+    }
+
+    #[test]
+    fn redoing_a_group_reapplies_it_in_order() {
+        let mut history = History::default();
+        insert(&mut history, at(0, 0), "a");
+        history.begin_group();
+        insert(&mut history, at(0, 1), "\n");
+        insert(&mut history, at(1, 0), "b");
+        history.end_group();
+        history.undo();
+        history.undo();
+        let redone = history.redo().map(|(changes, _)| describe(&changes));
+        assert_eq!(redone.unwrap_or_default(), ["+0:0 a"]);
+        let redone = history.redo().map(|(changes, _)| describe(&changes));
+        assert_eq!(redone.unwrap_or_default(), ["+0:1 \n", "+1:0 b"]);
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn new_changes_clear_the_redo_stack() {
+        let mut history = History::default();
+        insert(&mut history, at(0, 0), "a");
+        history.undo();
+        insert(&mut history, at(0, 0), "b");
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn undo_restores_the_caret_before_the_change() {
+        let mut history = History::default();
+        history.record(
+            Change::Insert {
+                at: at(3, 1),
+                text: String::from("a"),
+            },
+            caret(3, 1),
+            caret(3, 2),
+        );
+        let line_and_grapheme = |caret: Location| (caret.line_index, caret.grapheme_index);
+        assert_eq!(
+            history.undo().map(|(_, caret)| line_and_grapheme(caret)),
+            Some((3, 1))
+        );
+        assert_eq!(
+            history.redo().map(|(_, caret)| line_and_grapheme(caret)),
+            Some((3, 2))
+        );
+    }
+
+    #[test]
+    fn undoing_and_redoing_returns_to_the_save_point() {
+        let mut history = History::default();
+        assert!(history.is_at_save_point());
+        insert(&mut history, at(0, 0), "a");
+        assert!(!history.is_at_save_point());
+        history.undo();
+        assert!(history.is_at_save_point());
+        history.redo();
+        history.mark_saved();
+        assert!(history.is_at_save_point());
+        history.undo();
+        assert!(!history.is_at_save_point());
+        history.redo();
+        assert!(history.is_at_save_point());
+    }
+
+    #[test]
+    fn typing_after_saving_is_undone_separately() {
+        let mut history = History::default();
+        insert(&mut history, at(0, 0), "a");
+        history.mark_saved();
+        insert(&mut history, at(0, 1), "b");
+        assert_eq!(undo(&mut history), ["-0:1 b"]);
+        assert!(history.is_at_save_point());
+    }
+
+    #[test]
+    fn the_save_point_is_lost_once_its_changes_are_replaced() {
+        let mut history = History::default();
+        insert(&mut history, at(0, 0), "a");
+        history.mark_saved();
+        history.undo();
+        insert(&mut history, at(0, 0), "b");
+        history.undo();
+        assert!(!history.is_at_save_point());
+    }
+}
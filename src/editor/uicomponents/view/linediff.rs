@@ -0,0 +1,209 @@
+use std::fmt::Write;
+
+// How many unchanged lines to show around each change.
+const CONTEXT_LINES: usize = 2;
+// Changed regions with more pairs of lines than this aren't matched up line
+// by line, to bound the time and memory spent.
+const MAX_TABLE_SIZE: usize = 1_000_000;
+
+#[derive(Clone, Copy)]
+enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Describes how to get from `old` to `new` in the style of a unified diff,
+/// showing each change with a few unchanged lines around it.
+pub fn unified_diff(old: &[&str], new: &[&str]) -> String {
+    let diff = diff_lines(old, new);
+    let is_change = |line: &DiffLine| !matches!(line, DiffLine::Same(_));
+    let mut result = String::new();
+    let mut new_line_number: usize = 1;
+    let mut last_shown: Option<usize> = None;
+    for (index, line) in diff.iter().enumerate() {
+        let near_change = diff
+            .iter()
+            .skip(index.saturating_sub(CONTEXT_LINES))
+            .take(
+                index
+                    .min(CONTEXT_LINES)
+                    .saturating_add(CONTEXT_LINES)
+                    .saturating_add(1),
+            )
+            .any(is_change);
+        if near_change {
+            if last_shown.is_none_or(|last| last.saturating_add(1) != index) {
+                let _ = writeln!(result, "@@ line {new_line_number} @@");
+            }
+            last_shown = Some(index);
+            let _ = match line {
+                DiffLine::Same(text) => writeln!(result, "  {text}"),
+                DiffLine::Removed(text) => writeln!(result, "- {text}"),
+                DiffLine::Added(text) => writeln!(result, "+ {text}"),
+            };
+        }
+        if !matches!(line, DiffLine::Removed(_)) {
+            new_line_number = new_line_number.saturating_add(1);
+        }
+    }
+    match result.strip_suffix('\n') {
+        Some(diff) => diff.to_string(),
+        None => String::from("No differences."),
+    }
+}
+
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<DiffLine<'a>> {
+    let prefix_len = old
+        .iter()
+        .zip(new)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let max_suffix_len = old.len().min(new.len()).saturating_sub(prefix_len);
+    let suffix_len = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take(max_suffix_len)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let old_changed = &old[prefix_len..old.len().saturating_sub(suffix_len)];
+    let new_changed = &new[prefix_len..new.len().saturating_sub(suffix_len)];
+
+    let mut result: Vec<DiffLine> = old[..prefix_len]
+        .iter()
+        .map(|line| DiffLine::Same(line))
+        .collect();
+    if old_changed.len().saturating_mul(new_changed.len()) <= MAX_TABLE_SIZE {
+        result.extend(diff_changed_lines(old_changed, new_changed));
+    } else {
+        result.extend(old_changed.iter().map(|line| DiffLine::Removed(line)));
+        result.extend(new_changed.iter().map(|line| DiffLine::Added(line)));
+    }
+    result.extend(
+        old[old.len().saturating_sub(suffix_len)..]
+            .iter()
+            .map(|line| DiffLine::Same(line)),
+    );
+    result
+}
+
+// Keeps the longest common subsequence of lines, found by dynamic programming.
+fn diff_changed_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<DiffLine<'a>> {
+    let width = new.len().saturating_add(1);
+    let cell = |old_index: usize, new_index: usize| {
+        old_index.saturating_mul(width).saturating_add(new_index)
+    };
+    // How many lines `old[i..]` and `new[j..]` have in common.
+    let mut common = vec![0_usize; old.len().saturating_add(1).saturating_mul(width)];
+    for (old_index, old_line) in old.iter().enumerate().rev() {
+        for (new_index, new_line) in new.iter().enumerate().rev() {
+            let value = if old_line == new_line {
+                common
+                    .get(cell(
+                        old_index.saturating_add(1),
+                        new_index.saturating_add(1),
+                    ))
+                    .map_or(0, |value| value.saturating_add(1))
+            } else {
+                let below = common.get(cell(old_index.saturating_add(1), new_index));
+                let right = common.get(cell(old_index, new_index.saturating_add(1)));
+                below.max(right).copied().unwrap_or(0)
+            };
+            if let Some(target) = common.get_mut(cell(old_index, new_index)) {
+                *target = value;
+            }
+        }
+    }
+
+    let mut result = Vec::new();
+    let (mut old_index, mut new_index) = (0, 0);
+    while let (Some(old_line), Some(new_line)) = (old.get(old_index), new.get(new_index)) {
+        if old_line == new_line {
+            result.push(DiffLine::Same(old_line));
+            old_index = old_index.saturating_add(1);
+            new_index = new_index.saturating_add(1);
+        } else if common.get(cell(old_index.saturating_add(1), new_index))
+            >= common.get(cell(old_index, new_index.saturating_add(1)))
+        {
+            result.push(DiffLine::Removed(old_line));
+            old_index = old_index.saturating_add(1);
+        } else {
+            result.push(DiffLine::Added(new_line));
+            new_index = new_index.saturating_add(1);
+        }
+    }
+    result.extend(old[old_index..].iter().map(|line| DiffLine::Removed(line)));
+    result.extend(new[new_index..].iter().map(|line| DiffLine::Added(line)));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_texts_have_no_differences() {
+        assert_eq!(unified_diff(&["a", "b"], &["a", "b"]), "No differences.");
+        assert_eq!(unified_diff(&[], &[]), "No differences.");
+    }
+
+    #[test]
+    fn shows_changes_with_the_lines_around_them() {
+        let old = ["1", "2", "3", "4", "5", "6", "7"];
+        let new = ["1", "2", "3", "four", "5", "6", "7"];
+        assert_eq!(
+            unified_diff(&old, &new),
+            "@@ line 2 @@\n  2\n  3\n- 4\n+ four\n  5\n  6"
+        );
+    }
+
+    #[test]
+    fn separates_changes_far_apart() {
+        let old = ["a", "1", "2", "3", "4", "5", "6", "b"];
+        let new = ["A", "1", "2", "3", "4", "5", "6", "B"];
+        assert_eq!(
+            unified_diff(&old, &new),
+            "@@ line 1 @@\n- a\n+ A\n  1\n  2\n@@ line 6 @@\n  5\n  6\n- b\n+ B"
+        );
+    }
+
+    #[test]
+    fn joins_changes_close_together() {
+        let old = ["a", "1", "2", "3", "b"];
+        let new = ["A", "1", "2", "3", "B"];
+        assert_eq!(
+            unified_diff(&old, &new),
+            "@@ line 1 @@\n- a\n+ A\n  1\n  2\n  3\n- b\n+ B"
+        );
+    }
+
+    #[test]
+    fn keeps_the_lines_both_have_in_common() {
+        let old = ["x", "a", "b", "c", "y"];
+        let new = ["x", "b", "z", "c", "y"];
+        assert_eq!(
+            unified_diff(&old, &new),
+            "@@ line 1 @@\n  x\n- a\n  b\n+ z\n  c\n  y"
+        );
+    }
+
+    #[test]
+    fn shows_lines_added_and_removed_at_the_ends() {
+        assert_eq!(unified_diff(&["a"], &["a", "b"]), "@@ line 1 @@\n  a\n+ b");
+        assert_eq!(unified_diff(&["a", "b"], &["b"]), "@@ line 1 @@\n- a\n  b");
+        assert_eq!(unified_diff(&[], &["a"]), "@@ line 1 @@\n+ a");
+    }
+
+    #[test]
+    fn large_changes_are_shown_as_replaced_whole() {
+        let old: Vec<String> = (0..1500).map(|index| format!("old {index}")).collect();
+        let new: Vec<String> = (0..1500).map(|index| format!("new {index}")).collect();
+        let old: Vec<&str> = old.iter().map(String::as_str).collect();
+        let new: Vec<&str> = new.iter().map(String::as_str).collect();
+        let diff = unified_diff(&old, &new);
+        assert!(diff.starts_with("@@ line 1 @@\n- old 0\n- old 1\n"));
+        assert!(diff.contains("\n- old 1499\n+ new 0\n"));
+        assert!(diff.ends_with("\n+ new 1499"));
+    }
+}
@@ -0,0 +1,30 @@
+use std::fmt::Display;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineNumbers {
+    #[default]
+    Off,
+    Absolute,
+    /// Shows the distance to the caret's line, except on that line itself.
+    Relative,
+}
+
+impl LineNumbers {
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Absolute,
+            Self::Absolute => Self::Relative,
+            Self::Relative => Self::Off,
+        }
+    }
+}
+
+impl Display for LineNumbers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Off => write!(f, "off"),
+            Self::Absolute => write!(f, "absolute"),
+            Self::Relative => write!(f, "relative"),
+        }
+    }
+}
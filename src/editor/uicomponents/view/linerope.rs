@@ -0,0 +1,426 @@
+use std::cell::{Cell, OnceCell};
+use std::io::{Error, ErrorKind};
+use std::mem::take;
+use std::ops::{Range, RangeInclusive};
+
+use crate::prelude::*;
+
+use super::{Line, LineSource};
+
+// Chunks longer than this are split, and shorter ones are merged with their
+// successor after an edit.
+const MAX_CHUNK_LEN: usize = 1024;
+const MIN_CHUNK_LEN: usize = 256;
+
+/// Stores lines in chunks of bounded length, so that inserting or removing a
+/// line only shifts the lines of its own chunk rather than all that follow.
+/// The chunks of a large file are read from it when first needed.
+#[derive(Default)]
+pub struct LineRope {
+    chunks: Vec<Chunk>,
+    // Index of the first line of every chunk, to find lines by binary search.
+    chunk_starts: Vec<LineIdx>,
+    len: usize,
+    source: Option<LineSource>,
+    // Whether any lines couldn't be read from the source, and were left empty.
+    read_failed: Cell<bool>,
+}
+
+struct Chunk {
+    lines: OnceCell<Vec<Line>>,
+    len: usize,
+    // Where in the file the lines are, until they're read.
+    unread: Option<Range<u64>>,
+}
+
+impl From<Vec<Line>> for Chunk {
+    fn from(lines: Vec<Line>) -> Self {
+        Self {
+            len: lines.len(),
+            lines: OnceCell::from(lines),
+            unread: None,
+        }
+    }
+}
+
+impl LineRope {
+    /// Returns a rope whose lines are read from `source` as they're needed.
+    pub const fn with_source(source: LineSource) -> Self {
+        Self {
+            chunks: Vec::new(),
+            chunk_starts: Vec::new(),
+            len: 0,
+            source: Some(source),
+            read_failed: Cell::new(false),
+        }
+    }
+
+    /// Appends the `len` lines found in `bytes` of the source, without
+    /// reading them yet.
+    pub fn push_unread(&mut self, bytes: Range<u64>, len: usize) {
+        self.chunk_starts.push(self.len);
+        self.chunks.push(Chunk {
+            lines: OnceCell::new(),
+            len,
+            unread: Some(bytes),
+        });
+        self.len = self.len.saturating_add(len);
+    }
+
+    pub fn has_unread_lines(&self) -> bool {
+        self.chunks.iter().any(|chunk| chunk.lines.get().is_none())
+    }
+
+    /// Reads all lines which haven't been yet, e.g. before the file they're
+    /// read from is overwritten. Fails if any lines couldn't be read, now or
+    /// before.
+    pub fn read_all(&self) -> Result<(), Error> {
+        for chunk in &self.chunks {
+            self.lines_of(chunk);
+        }
+        if self.read_failed.get() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Parts of the file couldn't be read, as it changed since it was opened",
+            ));
+        }
+        Ok(())
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, line_index: LineIdx) -> Option<&Line> {
+        let (chunk_index, offset) = self.locate(line_index)?;
+        self.lines_of(self.chunks.get(chunk_index)?).get(offset)
+    }
+
+    pub fn last(&self) -> Option<&Line> {
+        self.lines_of(self.chunks.last()?).last()
+    }
+
+    pub fn last_mut(&mut self) -> Option<&mut Line> {
+        let last_chunk = self.chunks.len().checked_sub(1)?;
+        self.lines_of_mut(last_chunk)?.last_mut()
+    }
+
+    /// Goes through the lines at `line_indices` until `visit` returns
+    /// something. Unlike `get`, it doesn't keep the lines it has to read, so
+    /// that e.g. searching a large file doesn't read it all into memory.
+    pub fn find_map<T, R>(
+        &self,
+        line_indices: impl IntoIterator<Item = (LineIdx, T)>,
+        mut visit: impl FnMut(&Line, LineIdx, T) -> Option<R>,
+    ) -> Option<R> {
+        // The chunk read last, unless it had been read before.
+        let mut scratch: Option<(usize, Vec<Line>)> = None;
+        for (line_index, context) in line_indices {
+            let Some((chunk_index, offset)) = self.locate(line_index) else {
+                continue;
+            };
+            let chunk = self.chunks.get(chunk_index)?;
+            let lines = if let Some(lines) = chunk.lines.get() {
+                lines
+            } else {
+                if scratch
+                    .as_ref()
+                    .is_none_or(|(index, _)| *index != chunk_index)
+                {
+                    scratch = Some((chunk_index, self.read(chunk)));
+                }
+                scratch.as_ref().map_or(&[][..], |(_, lines)| lines)
+            };
+            if let Some(found) = lines
+                .get(offset)
+                .and_then(|line| visit(line, line_index, context))
+            {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Line> {
+        self.chunks.iter().flat_map(|chunk| self.lines_of(chunk))
+    }
+
+    /// Iterates over the lines which were read already, leaving the rest
+    /// unread.
+    pub fn iter_read_mut(&mut self) -> impl Iterator<Item = &mut Line> {
+        self.chunks
+            .iter_mut()
+            .filter_map(|chunk| chunk.lines.get_mut())
+            .flatten()
+    }
+
+    pub fn push(&mut self, line: Line) {
+        match self
+            .chunks
+            .last_mut()
+            .and_then(|chunk| chunk.lines.get_mut())
+        {
+            Some(lines) if lines.len() < MAX_CHUNK_LEN => {
+                lines.push(line);
+                if let Some(chunk) = self.chunks.last_mut() {
+                    chunk.len = chunk.len.saturating_add(1);
+                }
+            }
+            _ => {
+                self.chunk_starts.push(self.len);
+                self.chunks.push(Chunk::from(vec![line]));
+            }
+        }
+        self.len = self.len.saturating_add(1);
+    }
+
+    /// Replaces the lines in `range` with `replace_with`.
+    pub fn splice(
+        &mut self,
+        range: RangeInclusive<LineIdx>,
+        replace_with: impl IntoIterator<Item = Line>,
+    ) {
+        let (Some((first_chunk, first_offset)), Some((last_chunk, last_offset))) =
+            (self.locate(*range.start()), self.locate(*range.end()))
+        else {
+            #[cfg(debug_assertions)]
+            {
+                panic!("Attempting to splice lines which don't exist: {range:?}");
+            }
+            #[cfg(not(debug_assertions))]
+            {
+                return;
+            }
+        };
+        let affected: Vec<Chunk> = self.chunks.drain(first_chunk..=last_chunk).collect();
+        let mut affected: Vec<Vec<Line>> = affected
+            .into_iter()
+            .map(|chunk| self.take_lines(chunk))
+            .collect();
+        let tail = affected
+            .last_mut()
+            .map(|chunk| chunk.split_off(last_offset.saturating_add(1)))
+            .unwrap_or_default();
+        let mut lines = affected
+            .first_mut()
+            .map(|chunk| {
+                chunk.truncate(first_offset);
+                take(chunk)
+            })
+            .unwrap_or_default();
+        lines.extend(replace_with);
+        lines.extend(tail);
+        if lines.len() < MIN_CHUNK_LEN && first_chunk < self.chunks.len() {
+            let next_chunk = self.chunks.remove(first_chunk);
+            lines.extend(self.take_lines(next_chunk));
+        }
+
+        // Cut full chunks off the end, so that each line is moved only once.
+        let mut new_chunks = Vec::new();
+        while lines.len() > MAX_CHUNK_LEN {
+            new_chunks.push(lines.split_off(lines.len().saturating_sub(MAX_CHUNK_LEN)));
+        }
+        if !lines.is_empty() {
+            new_chunks.push(lines);
+        }
+        new_chunks.reverse();
+        self.chunks.splice(
+            first_chunk..first_chunk,
+            new_chunks.into_iter().map(Chunk::from),
+        );
+        self.update_chunk_starts();
+    }
+
+    fn locate(&self, line_index: LineIdx) -> Option<(usize, usize)> {
+        if line_index >= self.len {
+            return None;
+        }
+        let chunk_index = self
+            .chunk_starts
+            .partition_point(|start| *start <= line_index)
+            .saturating_sub(1);
+        let offset = line_index.saturating_sub(*self.chunk_starts.get(chunk_index)?);
+        Some((chunk_index, offset))
+    }
+
+    fn update_chunk_starts(&mut self) {
+        self.chunk_starts.clear();
+        let mut start: LineIdx = 0;
+        for chunk in &self.chunks {
+            self.chunk_starts.push(start);
+            start = start.saturating_add(chunk.len);
+        }
+        self.len = start;
+    }
+
+    fn lines_of<'a>(&'a self, chunk: &'a Chunk) -> &'a [Line] {
+        chunk.lines.get_or_init(|| self.read(chunk))
+    }
+
+    fn lines_of_mut(&mut self, chunk_index: usize) -> Option<&mut Vec<Line>> {
+        self.lines_of(self.chunks.get(chunk_index)?);
+        self.chunks.get_mut(chunk_index)?.lines.get_mut()
+    }
+
+    fn take_lines(&self, chunk: Chunk) -> Vec<Line> {
+        if chunk.lines.get().is_none() {
+            return self.read(&chunk);
+        }
+        chunk.lines.into_inner().unwrap_or_default()
+    }
+
+    // Should the file fail to be read, the lines are left empty rather than
+    // shifting those which follow, and the failure is remembered.
+    fn read(&self, chunk: &Chunk) -> Vec<Line> {
+        let read = match (&self.source, &chunk.unread) {
+            (Some(source), Some(bytes)) => source.read_lines(bytes.clone(), chunk.len),
+            _ => Ok(Vec::new()),
+        };
+        let mut lines = read.unwrap_or_else(|_| {
+            self.read_failed.set(true);
+            Vec::new()
+        });
+        lines.resize_with(chunk.len, Line::default);
+        lines
+    }
+}
+
+impl FromIterator<Line> for LineRope {
+    fn from_iter<I: IntoIterator<Item = Line>>(iter: I) -> Self {
+        let mut rope = Self::default();
+        for line in iter {
+            rope.push(line);
+        }
+        rope
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Lines holding their own index, spread over several chunks.
+    fn numbered_rope(len: usize) -> LineRope {
+        (0..len)
+            .map(|index| Line::from(&index.to_string()))
+            .collect()
+    }
+
+    fn contents(rope: &LineRope) -> Vec<String> {
+        rope.iter().map(ToString::to_string).collect()
+    }
+
+    fn numbers(range: Range<usize>) -> Vec<String> {
+        range.map(|index| index.to_string()).collect()
+    }
+
+    fn assert_consistent(rope: &LineRope) {
+        assert_eq!(rope.len(), rope.iter().count());
+        let mut start = 0;
+        for (chunk, chunk_start) in rope.chunks.iter().zip(&rope.chunk_starts) {
+            assert_eq!(*chunk_start, start);
+            assert!(chunk.len > 0 && chunk.len <= MAX_CHUNK_LEN);
+            start = start.saturating_add(chunk.len);
+        }
+        assert_eq!(start, rope.len());
+    }
+
+    #[test]
+    fn pushed_lines_are_found_by_index() {
+        let rope = numbered_rope(3000);
+        assert_consistent(&rope);
+        assert_eq!(rope.len(), 3000);
+        assert!(rope.chunks.len() > 1);
+        for index in [0, 1023, 1024, 2047, 2048, 2999] {
+            assert_eq!(
+                rope.get(index).map(ToString::to_string),
+                Some(index.to_string())
+            );
+        }
+        assert!(rope.get(3000).is_none());
+        assert_eq!(
+            rope.last().map(ToString::to_string),
+            Some(String::from("2999"))
+        );
+    }
+
+    #[test]
+    fn splicing_within_a_chunk() {
+        let mut rope = numbered_rope(3000);
+        rope.splice(10..=11, [Line::from("a")]);
+        assert_consistent(&rope);
+        let mut expected = numbers(0..10);
+        expected.push(String::from("a"));
+        expected.extend(numbers(12..3000));
+        assert_eq!(contents(&rope), expected);
+    }
+
+    #[test]
+    fn removing_lines_across_chunk_boundaries() {
+        let mut rope = numbered_rope(3000);
+        rope.splice(1000..=2100, [Line::from("joined")]);
+        assert_consistent(&rope);
+        let mut expected = numbers(0..1000);
+        expected.push(String::from("joined"));
+        expected.extend(numbers(2101..3000));
+        assert_eq!(contents(&rope), expected);
+        assert_eq!(
+            rope.get(1001).map(ToString::to_string),
+            Some(String::from("2101"))
+        );
+    }
+
+    #[test]
+    fn inserting_more_lines_than_fit_in_a_chunk() {
+        let mut rope = numbered_rope(3000);
+        let inserted: Vec<Line> = (0..2500).map(|_| Line::from("new")).collect();
+        rope.splice(1023..=1024, inserted);
+        assert_consistent(&rope);
+        assert_eq!(rope.len(), 5498);
+        assert_eq!(
+            rope.get(1022).map(ToString::to_string),
+            Some(String::from("1022"))
+        );
+        assert_eq!(
+            rope.get(1023).map(ToString::to_string),
+            Some(String::from("new"))
+        );
+        assert_eq!(
+            rope.get(3522).map(ToString::to_string),
+            Some(String::from("new"))
+        );
+        assert_eq!(
+            rope.get(3523).map(ToString::to_string),
+            Some(String::from("1025"))
+        );
+    }
+
+    #[test]
+    fn small_chunks_are_merged_with_their_successor() {
+        let mut rope = numbered_rope(1500);
+        assert_eq!(rope.chunks.len(), 2);
+        rope.splice(0..=1000, [Line::from("a")]);
+        assert_consistent(&rope);
+        assert_eq!(rope.chunks.len(), 1);
+        assert_eq!(
+            rope.get(1).map(ToString::to_string),
+            Some(String::from("1001"))
+        );
+    }
+
+    #[test]
+    fn removing_the_last_line() {
+        let mut rope = numbered_rope(1025);
+        rope.splice(1023..=1024, [Line::from("end")]);
+        assert_consistent(&rope);
+        assert_eq!(rope.len(), 1024);
+        assert_eq!(
+            rope.last().map(ToString::to_string),
+            Some(String::from("end"))
+        );
+    }
+}
@@ -1,51 +1,152 @@
-use std::{cmp::min, io::Error};
+use std::{
+    cell::RefCell,
+    cmp::{min, Ordering},
+    io::Error,
+    ops::Range,
+    rc::Rc,
+};
 
 use crate::prelude::*;
 
 use super::UIComponent;
 
+use super::super::annotatedstring::AnnotatedString;
 use super::super::command::{Edit, Move};
 use super::super::DocumentStatus;
 use super::super::Line;
 use super::super::Terminal;
+use super::super::{AnnotationType, Encoding, FileType, LineEnding, UiElement};
+use super::super::{SearchOptions, SearchPattern};
 
+mod atomicwrite;
+use atomicwrite::write_atomically;
 mod buffer;
-use buffer::Buffer;
+pub use buffer::Buffer;
+mod linerope;
+use linerope::LineRope;
 mod fileinfo;
 use fileinfo::FileInfo;
 mod highlighter;
-use highlighter::Highlighter;
+pub use highlighter::Highlighter;
+use highlighter::{create_syntax_highlighter, SyntaxHighlighter};
+mod history;
+use history::{Change, History, TextPosition};
+mod linediff;
+use linediff::unified_diff;
+mod linenumbers;
+use linenumbers::LineNumbers;
+mod readonly;
+pub use readonly::ReadOnly;
 mod searchdirection;
 use searchdirection::SearchDirection;
 mod searchinfo;
-use searchinfo::SearchInfo;
+use searchinfo::{PendingSearch, SearchInfo};
+mod swapfile;
+use swapfile::{is_swap_file, read_swap_file, remove_swap_file, swap_path_for, write_swap_file};
+mod textfile;
+use textfile::{LineSource, TextFile};
+mod wrapmode;
+use wrapmode::{WrapMode, WrappedRow};
+
+// How many lines a search goes through before the editor gets to react to
+// input again.
+const SEARCH_BATCH_LINES: usize = 100_000;
 
 #[derive(Default)]
 pub struct View {
-    buffer: Buffer,
+    // Shared with the views of other panes showing the same buffer.
+    buffer: Rc<RefCell<Buffer>>,
     needs_redraw: bool,
     size: Size,
     text_location: Location,
+    selection_anchor: Option<Location>,
+    // The first line shown, and how far the view is scrolled to the right.
     scroll_offset: Position,
+    // When wrapping, which of the first line's rows is shown first.
+    scroll_row_index: usize,
     search_info: Option<SearchInfo>,
+    pending_search: Option<PendingSearch>,
+    search_options: SearchOptions,
+    rendered_revision: usize,
+    wrap_mode: WrapMode,
+    line_numbers: LineNumbers,
+    rendered_caret_line: LineIdx,
 }
 
 impl View {
     pub fn get_status(&self) -> DocumentStatus {
-        let file_info = self.buffer.get_file_info();
+        let buffer = self.buffer.borrow();
+        let file_info = buffer.get_file_info();
         DocumentStatus {
-            total_lines: self.buffer.height(),
+            total_lines: buffer.height(),
             current_line_index: self.text_location.line_index,
             file_name: format!("{file_info}"),
-            is_modified: self.buffer.is_dirty(),
+            is_modified: buffer.is_dirty(),
+            is_read_only: buffer.read_only().is_some(),
             file_type: file_info.get_file_type(),
+            encoding: file_info.get_encoding(),
+            line_ending: file_info.get_line_ending(),
+            has_final_newline: file_info.has_final_newline(),
+            // Only the editor knows the buffer list, and fills these in.
+            buffer_index: 0,
+            buffer_count: 1,
         }
     }
 
+    /// Returns another view on the same buffer, e.g. for a new pane.
+    pub fn split(&self) -> Self {
+        Self {
+            buffer: Rc::clone(&self.buffer),
+            size: self.size,
+            text_location: self.text_location,
+            scroll_offset: self.scroll_offset,
+            scroll_row_index: self.scroll_row_index,
+            search_options: self.search_options,
+            wrap_mode: self.wrap_mode,
+            line_numbers: self.line_numbers,
+            needs_redraw: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn shares_buffer_with(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.buffer, &other.buffer)
+    }
+
+    pub fn is_only_view_on_buffer(&self) -> bool {
+        Rc::strong_count(&self.buffer) == 1
+    }
+
+    pub fn blur(&mut self) {
+        self.clear_selection();
+    }
+
+    /// Gets the caret back onto the text, in case the buffer was edited
+    /// through another view in the meantime.
+    pub fn focus(&mut self) {
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.scroll_location_into_view();
+    }
+
     fn scroll_location_into_view(&mut self) {
-        let Position { row, col } = self.text_location_to_position();
-        self.scroll_vertically(row);
-        self.scroll_horizontally(col);
+        if self.wrap_mode == WrapMode::Off {
+            let Position { row, col } = self.text_location_to_position();
+            self.scroll_vertically(row);
+            self.scroll_horizontally(col);
+            return;
+        }
+        let (caret_row, _) = self.caret_row();
+        let last_row = self.size.height.saturating_sub(1);
+        let top_row = if caret_row < self.top_row() {
+            caret_row
+        } else if self.rows_below_top(caret_row, last_row).is_none() {
+            self.step_rows_up(caret_row, last_row)
+        } else {
+            return;
+        };
+        self.set_top_row(top_row);
+        self.mark_redraw(true);
     }
 
     fn scroll_vertically(&mut self, to: RowIdx) {
@@ -66,7 +167,10 @@ impl View {
     }
 
     fn scroll_horizontally(&mut self, to: ColIdx) {
-        let Size { width, .. } = self.size;
+        if self.wrap_mode != WrapMode::Off {
+            return;
+        }
+        let width = self.text_area_width();
         let offset_changed = if to < self.scroll_offset.col {
             self.scroll_offset.col = to;
             true
@@ -81,8 +185,13 @@ impl View {
         }
     }
 
-    pub fn render_line(at: RowIdx, line_text: &str) -> Result<(), Error> {
-        Terminal::print_row(at, line_text)
+    pub fn render_line(
+        origin: Position,
+        width: usize,
+        line_text: &str,
+        element: UiElement,
+    ) -> Result<(), Error> {
+        Terminal::print_at(origin, width, line_text, element)
     }
 
     fn build_welcome_message(width: usize) -> String {
@@ -100,50 +209,569 @@ impl View {
 
     pub fn load(&mut self, file_name: &str) -> Result<(), Error> {
         let buffer = Buffer::load(file_name)?;
-        self.buffer = buffer;
+        self.buffer = Rc::new(RefCell::new(buffer));
+        self.selection_anchor = None;
+        self.mark_redraw(true);
+        Ok(())
+    }
+
+    pub fn save(&mut self, make_backup: bool) -> Result<(), Error> {
+        self.buffer.borrow_mut().save(make_backup)?;
         self.mark_redraw(true);
         Ok(())
     }
 
-    pub fn save(&mut self) -> Result<(), Error> {
-        self.buffer.save()?;
+    pub fn save_as(&mut self, file_name: &str, make_backup: bool) -> Result<(), Error> {
+        self.buffer.borrow_mut().save_as(file_name, make_backup)?;
         self.mark_redraw(true);
         Ok(())
     }
 
-    pub fn save_as(&mut self, file_name: &str) -> Result<(), Error> {
-        self.buffer.save_as(file_name)?;
+    /// Reads the file again, keeping the caret as close to where it was as
+    /// the new contents allow.
+    pub fn reload(&mut self) -> Result<(), Error> {
+        self.buffer.borrow_mut().reload()?;
+        self.selection_anchor = None;
+        self.focus();
         self.mark_redraw(true);
         Ok(())
     }
 
+    pub fn is_large_file(&self) -> bool {
+        self.buffer.borrow().is_large()
+    }
+
+    pub fn read_only(&self) -> Option<ReadOnly> {
+        self.buffer.borrow().read_only()
+    }
+
+    pub fn set_read_only(&self) {
+        self.buffer.borrow_mut().set_read_only();
+    }
+
+    pub fn is_changed_on_disk(&self) -> bool {
+        self.buffer.borrow().is_changed_on_disk()
+    }
+
+    /// Returns a view on a buffer holding `text` which isn't backed by any
+    /// file, e.g. to show a report.
+    pub fn from_text(text: &str) -> Self {
+        Self {
+            buffer: Rc::new(RefCell::new(Buffer::from_text(text))),
+            needs_redraw: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn has_leftover_swap_file(&self) -> bool {
+        self.buffer.borrow().has_leftover_swap_file()
+    }
+
+    pub fn update_swap_file(&self) -> Result<(), Error> {
+        self.buffer.borrow_mut().update_swap_file()
+    }
+
+    pub fn remove_swap_file(&self) -> Result<(), Error> {
+        self.buffer.borrow_mut().remove_swap_file()
+    }
+
+    pub fn recover_from_swap_file(&mut self) -> Result<(), Error> {
+        self.buffer.borrow_mut().recover_from_swap_file()?;
+        self.selection_anchor = None;
+        self.focus();
+        self.mark_redraw(true);
+        Ok(())
+    }
+
+    pub fn discard_swap_file(&self) -> Result<(), Error> {
+        self.buffer.borrow_mut().discard_swap_file()
+    }
+
+    pub fn diff_against_swap_file(&self) -> Result<String, Error> {
+        self.buffer.borrow().diff_against_swap_file()
+    }
+
     pub fn caret_position(&self) -> Position {
-        self.text_location_to_position()
-            .saturating_sub(self.scroll_offset)
+        let Position { col, row } = if self.wrap_mode == WrapMode::Off {
+            self.text_location_to_position()
+                .saturating_sub(self.scroll_offset)
+        } else {
+            let (caret_row, col) = self.caret_row();
+            Position {
+                col,
+                row: self
+                    .rows_below_top(caret_row, self.size.height)
+                    .unwrap_or(0),
+            }
+        };
+        Position {
+            col: col.saturating_add(self.gutter_width()),
+            row,
+        }
+    }
+
+    pub fn cycle_line_numbers(&mut self) -> LineNumbers {
+        self.line_numbers = self.line_numbers.next();
+        self.scroll_location_into_view();
+        self.mark_redraw(true);
+        self.line_numbers
+    }
+
+    // Enough for the highest line number, plus a space to separate it from the text.
+    fn gutter_width(&self) -> ColIdx {
+        if self.line_numbers == LineNumbers::Off {
+            return 0;
+        }
+        let height = self.buffer.borrow().height();
+        height.max(1).to_string().len().saturating_add(1)
+    }
+
+    fn text_area_width(&self) -> ColIdx {
+        self.size.width.saturating_sub(self.gutter_width())
+    }
+
+    fn gutter_label(&self, line_index: Option<LineIdx>, gutter_width: ColIdx) -> AnnotatedString {
+        let Some(line_index) = line_index else {
+            return AnnotatedString::from(&" ".repeat(gutter_width));
+        };
+        let number_width = gutter_width.saturating_sub(1);
+        let caret_line_index = self.text_location.line_index;
+        let number = if self.line_numbers == LineNumbers::Relative && line_index != caret_line_index
+        {
+            line_index.abs_diff(caret_line_index)
+        } else {
+            line_index.saturating_add(1)
+        };
+        let annotation_type = if line_index == caret_line_index {
+            AnnotationType::CurrentLineNumber
+        } else {
+            AnnotationType::LineNumber
+        };
+        let mut label = AnnotatedString::from(&format!("{number:>number_width$} "));
+        label.add_annotation(annotation_type, 0, number_width);
+        label
     }
 
+    // Where the caret is when lines aren't wrapped.
     fn text_location_to_position(&self) -> Position {
-        let row = self.text_location.line_index;
-        debug_assert!(row.saturating_sub(1) <= self.buffer.height());
-        let col = self
+        let Location {
+            line_index,
+            grapheme_index,
+        } = self.text_location;
+        debug_assert!(line_index.saturating_sub(1) <= self.buffer.borrow().height());
+        Position {
+            col: self.buffer.borrow().width_until(line_index, grapheme_index),
+            row: line_index,
+        }
+    }
+
+    pub fn convert_line_endings(&mut self) -> LineEnding {
+        let line_ending = self
             .buffer
-            .width_until(row, self.text_location.grapheme_index);
-        Position { col, row }
+            .borrow_mut()
+            .convert_line_endings(self.text_location);
+        // Removing carriage returns may have shortened the caret's line.
+        self.snap_to_valid_grapheme();
+        self.mark_redraw(true);
+        line_ending
+    }
+
+    pub fn set_file_type(&mut self, file_type: FileType) {
+        self.buffer.borrow_mut().set_file_type(file_type);
+        self.mark_redraw(true);
+    }
+
+    pub fn reset_line_layout(&mut self) {
+        self.buffer.borrow_mut().reset_line_layout();
+        self.snap_to_valid_grapheme();
+        self.scroll_location_into_view();
+        self.mark_redraw(true);
+    }
+
+    /// Moves the caret to the start of the line, or the last line if there
+    /// aren't as many.
+    pub fn go_to_line(&mut self, line_index: LineIdx) {
+        self.clear_selection();
+        self.text_location = Location {
+            line_index,
+            grapheme_index: 0,
+        };
+        self.snap_to_valid_line();
+        self.scroll_location_into_view();
+        self.mark_redraw(true);
+    }
+
+    pub fn cycle_wrap_mode(&mut self) -> WrapMode {
+        self.wrap_mode = self.wrap_mode.next();
+        // Stays on the same first line, but only scrolls sideways when not
+        // wrapping.
+        self.scroll_offset.col = 0;
+        self.scroll_row_index = 0;
+        self.scroll_location_into_view();
+        self.mark_redraw(true);
+        self.wrap_mode
+    }
+
+    fn wrapped_rows(&self, line_index: LineIdx) -> Vec<Range<GraphemeIdx>> {
+        self.buffer.borrow().wrap_line(
+            line_index,
+            self.text_area_width(),
+            self.wrap_mode == WrapMode::Words,
+        )
+    }
+
+    fn wrapped_row_count(&self, line_index: LineIdx) -> usize {
+        self.wrapped_rows(line_index).len().max(1)
+    }
+
+    // Which wrapped row the caret is on, and in which column of it.
+    fn caret_row(&self) -> (WrappedRow, ColIdx) {
+        let Location {
+            line_index,
+            grapheme_index,
+        } = self.text_location;
+        let rows = self.wrapped_rows(line_index);
+        let row_index = rows
+            .iter()
+            .rposition(|row| row.start <= grapheme_index)
+            .unwrap_or(0);
+        let row_start = rows.get(row_index).map_or(0, |row| row.start);
+        let buffer = self.buffer.borrow();
+        let col = buffer
+            .width_until(line_index, grapheme_index)
+            .saturating_sub(buffer.width_until(line_index, row_start));
+        (
+            WrappedRow {
+                line_index,
+                row_index,
+            },
+            col,
+        )
+    }
+
+    fn top_row(&self) -> WrappedRow {
+        let line_index = self.scroll_offset.row;
+        WrappedRow {
+            line_index,
+            // Editing may have left the line with fewer rows.
+            row_index: self
+                .scroll_row_index
+                .min(self.wrapped_row_count(line_index).saturating_sub(1)),
+        }
+    }
+
+    fn set_top_row(&mut self, row: WrappedRow) {
+        self.scroll_offset.row = row.line_index;
+        self.scroll_row_index = row.row_index;
+    }
+
+    // How many rows `row` is below the first one shown, if it's no more than
+    // `limit`. Only the lines in between are wrapped.
+    fn rows_below_top(&self, row: WrappedRow, limit: usize) -> Option<usize> {
+        let top_row = self.top_row();
+        if row < top_row {
+            return None;
+        }
+        let mut count = row.row_index;
+        for line_index in top_row.line_index..row.line_index {
+            count = count.saturating_add(self.wrapped_row_count(line_index));
+            if count.saturating_sub(top_row.row_index) > limit {
+                return None;
+            }
+        }
+        let count = count.saturating_sub(top_row.row_index);
+        (count <= limit).then_some(count)
+    }
+
+    fn step_rows_up(&self, from: WrappedRow, step: usize) -> WrappedRow {
+        let mut row = from;
+        let mut remaining = step;
+        while remaining > row.row_index {
+            if row.line_index == 0 {
+                return WrappedRow::default();
+            }
+            remaining = remaining.saturating_sub(row.row_index.saturating_add(1));
+            row.line_index = row.line_index.saturating_sub(1);
+            row.row_index = self.wrapped_row_count(row.line_index).saturating_sub(1);
+        }
+        row.row_index = row.row_index.saturating_sub(remaining);
+        row
+    }
+
+    // Stops at the line just past the end of the document.
+    fn step_rows_down(&self, from: WrappedRow, step: usize) -> WrappedRow {
+        let height = self.buffer.borrow().height();
+        let mut row = from;
+        let mut remaining = step;
+        loop {
+            let last_row_index = self.wrapped_row_count(row.line_index).saturating_sub(1);
+            let rows_left = last_row_index.saturating_sub(row.row_index);
+            if remaining <= rows_left || row.line_index >= height {
+                row.row_index = row.row_index.saturating_add(remaining).min(last_row_index);
+                return row;
+            }
+            remaining = remaining.saturating_sub(rows_left.saturating_add(1));
+            row = WrappedRow {
+                line_index: row.line_index.saturating_add(1),
+                row_index: 0,
+            };
+        }
+    }
+
+    // Returns the location shown closest to column `col` of the wrapped row.
+    fn location_in_row(&self, row: WrappedRow, col: ColIdx) -> Location {
+        let WrappedRow {
+            line_index,
+            row_index,
+        } = row;
+        let rows = self.wrapped_rows(line_index);
+        let Some(range) = rows.get(row_index) else {
+            return Location {
+                grapheme_index: 0,
+                line_index,
+            };
+        };
+        // Except on the last row, the end of a row is the start of the next.
+        let last = if row_index.saturating_add(1) == rows.len() {
+            range.end
+        } else {
+            range.end.saturating_sub(1).max(range.start)
+        };
+        let buffer = self.buffer.borrow();
+        let row_start_col = buffer.width_until(line_index, range.start);
+        let grapheme_index = (range.start..=last)
+            .take_while(|index| {
+                buffer
+                    .width_until(line_index, *index)
+                    .saturating_sub(row_start_col)
+                    <= col
+            })
+            .last()
+            .unwrap_or(range.start);
+        Location {
+            grapheme_index,
+            line_index,
+        }
+    }
+
+    // Returns the line and the range of columns to show in each row.
+    fn visible_rows(&self) -> Vec<Option<(LineIdx, Range<ColIdx>)>> {
+        let height = self.size.height;
+        let width = self.text_area_width();
+        let buffer_height = self.buffer.borrow().height();
+        if self.wrap_mode == WrapMode::Off {
+            let left = self.scroll_offset.col;
+            return (0..height)
+                .map(|row| {
+                    let line_index = row.saturating_add(self.scroll_offset.row);
+                    (line_index < buffer_height)
+                        .then(|| (line_index, left..left.saturating_add(width)))
+                })
+                .collect();
+        }
+        let mut visible_rows = Vec::with_capacity(height);
+        let top_row = self.top_row();
+        for line_index in top_row.line_index..buffer_height {
+            let rows_to_skip = if line_index == top_row.line_index {
+                top_row.row_index
+            } else {
+                0
+            };
+            for row in self.wrapped_rows(line_index).into_iter().skip(rows_to_skip) {
+                if visible_rows.len() == height {
+                    return visible_rows;
+                }
+                let buffer = self.buffer.borrow();
+                visible_rows.push(Some((
+                    line_index,
+                    buffer.width_until(line_index, row.start)
+                        ..buffer.width_until(line_index, row.end),
+                )));
+            }
+        }
+        visible_rows.resize(height, None);
+        visible_rows
     }
 
     pub fn handle_edit_command(&mut self, command: Edit) {
+        self.pending_search = None;
         match command {
             Edit::Insert(character) => self.insert_char(character),
+            Edit::InsertText(text) => self.insert_text(&text),
             Edit::Delete => self.delete(),
             Edit::Backspace => self.backspace(),
             Edit::InsertNewLine => self.insert_newline(),
+            Edit::Undo => self.undo(),
+            Edit::Redo => self.redo(),
+        }
+    }
+
+    fn selection(&self) -> Option<Range<Location>> {
+        let anchor = self.selection_anchor?;
+        let caret = self.text_location;
+        match anchor.cmp(&caret) {
+            Ordering::Less => Some(anchor..caret),
+            Ordering::Greater => Some(caret..anchor),
+            Ordering::Equal => None,
+        }
+    }
+
+    fn clear_selection(&mut self) {
+        if self.selection_anchor.take().is_some() {
+            self.mark_redraw(true);
+        }
+    }
+
+    fn take_selection(&mut self) -> Option<Range<Location>> {
+        let selection = self.selection();
+        self.clear_selection();
+        selection
+    }
+
+    fn replace_selection(&mut self, text: &str) -> bool {
+        let Some(Range { start, end }) = self.take_selection() else {
+            return false;
+        };
+        self.text_location = self.buffer.borrow_mut().replace_range(start, end, text);
+        self.scroll_location_into_view();
+        self.mark_redraw(true);
+        true
+    }
+
+    fn delete_selection(&mut self) -> bool {
+        let Some(Range { start, end }) = self.take_selection() else {
+            return false;
+        };
+        self.buffer.borrow_mut().delete_range(start, end);
+        self.text_location = start;
+        self.scroll_location_into_view();
+        self.mark_redraw(true);
+        true
+    }
+
+    pub fn insert_text(&mut self, text: &str) {
+        let Range { start, end } = self
+            .take_selection()
+            .unwrap_or(self.text_location..self.text_location);
+        self.text_location = self.buffer.borrow_mut().replace_range(start, end, text);
+        self.scroll_location_into_view();
+        self.mark_redraw(true);
+    }
+
+    // Without a selection, the whole line the caret is on gets copied.
+    fn range_to_copy(&self) -> Option<Range<Location>> {
+        self.selection().or_else(|| {
+            let line_index = self.text_location.line_index;
+            (line_index < self.buffer.borrow().height()).then_some(
+                Location {
+                    grapheme_index: 0,
+                    line_index,
+                }..Location {
+                    grapheme_index: 0,
+                    line_index: line_index.saturating_add(1),
+                },
+            )
+        })
+    }
+
+    pub fn copy(&self) -> Option<String> {
+        self.range_to_copy().map(|Range { start, end }| {
+            let mut text = self.buffer.borrow().text_in_range(start, end);
+            if self.selection().is_none() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            text
+        })
+    }
+
+    pub fn cut(&mut self) -> Option<String> {
+        let text = self.copy()?;
+        if !self.delete_selection() {
+            let line_index = self.text_location.line_index;
+            let (start, end) = if line_index.saturating_add(1) < self.buffer.borrow().height() {
+                (
+                    Location {
+                        grapheme_index: 0,
+                        line_index,
+                    },
+                    Location {
+                        grapheme_index: 0,
+                        line_index: line_index.saturating_add(1),
+                    },
+                )
+            } else if line_index > 0 {
+                // The last line has no line break of its own to remove, so
+                // take the one before it instead.
+                let previous_line_index = line_index.saturating_sub(1);
+                (
+                    Location {
+                        grapheme_index: self.buffer.borrow().grapheme_count(previous_line_index),
+                        line_index: previous_line_index,
+                    },
+                    Location {
+                        grapheme_index: self.buffer.borrow().grapheme_count(line_index),
+                        line_index,
+                    },
+                )
+            } else {
+                (
+                    Location::default(),
+                    Location {
+                        grapheme_index: self.buffer.borrow().grapheme_count(line_index),
+                        line_index,
+                    },
+                )
+            };
+            self.buffer.borrow_mut().delete_range(start, end);
+            self.text_location = Location {
+                grapheme_index: 0,
+                line_index: line_index.min(self.buffer.borrow().height().saturating_sub(1)),
+            };
+            self.scroll_location_into_view();
+            self.mark_redraw(true);
         }
+        Some(text)
+    }
+
+    fn undo(&mut self) {
+        self.clear_selection();
+        let location = self.buffer.borrow_mut().undo();
+        if let Some(location) = location {
+            self.move_caret_after_history_change(location);
+        }
+    }
+
+    fn redo(&mut self) {
+        self.clear_selection();
+        let location = self.buffer.borrow_mut().redo();
+        if let Some(location) = location {
+            self.move_caret_after_history_change(location);
+        }
+    }
+
+    fn move_caret_after_history_change(&mut self, location: Location) {
+        self.text_location = location;
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.scroll_location_into_view();
+        self.mark_redraw(true);
     }
 
     fn insert_char(&mut self, character: char) {
-        let old_len = self.buffer.grapheme_count(self.text_location.line_index);
-        self.buffer.insert_char(character, self.text_location);
-        let new_len = self.buffer.grapheme_count(self.text_location.line_index);
+        if self.replace_selection(&character.to_string()) {
+            return;
+        }
+        let old_len = self
+            .buffer
+            .borrow()
+            .grapheme_count(self.text_location.line_index);
+        self.buffer
+            .borrow_mut()
+            .insert_char(character, self.text_location);
+        let new_len = self
+            .buffer
+            .borrow()
+            .grapheme_count(self.text_location.line_index);
         let grapheme_delta = new_len.saturating_sub(old_len);
         if grapheme_delta > 0 {
             self.handle_move_command(Move::Right);
@@ -152,27 +780,47 @@ impl View {
     }
 
     fn insert_newline(&mut self) {
-        self.buffer.insert_newline(self.text_location);
+        if self.replace_selection("\n") {
+            return;
+        }
+        self.buffer.borrow_mut().insert_newline(self.text_location);
         self.handle_move_command(Move::Right);
         self.mark_redraw(true);
     }
 
     fn backspace(&mut self) {
+        if self.delete_selection() {
+            return;
+        }
         if self.text_location.line_index != 0 || self.text_location.grapheme_index != 0 {
             self.handle_move_command(Move::Left);
             self.delete();
         }
     }
 
+    pub fn handle_select_command(&mut self, command: Move) {
+        if self.selection_anchor.is_none() {
+            self.selection_anchor = Some(self.text_location);
+        }
+        self.move_caret(command);
+        self.mark_redraw(true);
+    }
+
     pub fn handle_move_command(&mut self, command: Move) {
+        self.clear_selection();
+        self.move_caret(command);
+    }
+
+    fn move_caret(&mut self, command: Move) {
+        self.pending_search = None;
         let Size { height, .. } = self.size;
         match command {
-            Move::Up => self.move_up(1),
-            Move::Down => self.move_down(1),
+            Move::Up => self.move_up_by_rows(1),
+            Move::Down => self.move_down_by_rows(1),
             Move::Left => self.move_left(),
             Move::Right => self.move_right(),
-            Move::PageUp => self.move_up(height.saturating_sub(1)),
-            Move::PageDown => self.move_down(height.saturating_sub(1)),
+            Move::PageUp => self.move_up_by_rows(height.saturating_sub(1)),
+            Move::PageDown => self.move_down_by_rows(height.saturating_sub(1)),
             Move::Home => {
                 self.move_to_start_of_line();
                 self.snap_to_valid_grapheme();
@@ -186,10 +834,32 @@ impl View {
     }
 
     fn delete(&mut self) {
-        self.buffer.delete(self.text_location);
+        if self.delete_selection() {
+            return;
+        }
+        self.buffer.borrow_mut().delete(self.text_location);
         self.mark_redraw(true);
     }
 
+    // Unlike `move_up` and `move_down`, these move by screen row when wrapping.
+    fn move_up_by_rows(&mut self, step: usize) {
+        if self.wrap_mode == WrapMode::Off {
+            self.move_up(step);
+            return;
+        }
+        let (caret_row, col) = self.caret_row();
+        self.text_location = self.location_in_row(self.step_rows_up(caret_row, step), col);
+    }
+
+    fn move_down_by_rows(&mut self, step: usize) {
+        if self.wrap_mode == WrapMode::Off {
+            self.move_down(step);
+            return;
+        }
+        let (caret_row, col) = self.caret_row();
+        self.text_location = self.location_in_row(self.step_rows_down(caret_row, step), col);
+    }
+
     fn move_up(&mut self, step: usize) {
         self.text_location.line_index = self.text_location.line_index.saturating_sub(step);
         self.snap_to_valid_grapheme();
@@ -213,7 +883,10 @@ impl View {
 
     #[allow(clippy::arithmetic_side_effects)]
     fn move_right(&mut self) {
-        let grapheme_count = self.buffer.grapheme_count(self.text_location.line_index);
+        let grapheme_count = self
+            .buffer
+            .borrow()
+            .grapheme_count(self.text_location.line_index);
         if self.text_location.grapheme_index < grapheme_count {
             self.text_location.grapheme_index += 1;
         } else {
@@ -223,34 +896,43 @@ impl View {
     }
 
     fn snap_to_valid_grapheme(&mut self) {
-        self.text_location.grapheme_index = self
-            .text_location
-            .grapheme_index
-            .min(self.buffer.grapheme_count(self.text_location.line_index));
+        self.text_location.grapheme_index = self.text_location.grapheme_index.min(
+            self.buffer
+                .borrow()
+                .grapheme_count(self.text_location.line_index),
+        );
     }
 
     fn snap_to_valid_line(&mut self) {
-        self.text_location.line_index = self.text_location.line_index.min(self.buffer.height());
+        self.text_location.line_index = self
+            .text_location
+            .line_index
+            .min(self.buffer.borrow().height());
     }
 
     fn move_to_end_of_line(&mut self) {
-        self.text_location.grapheme_index =
-            self.buffer.grapheme_count(self.text_location.line_index);
+        self.text_location.grapheme_index = self
+            .buffer
+            .borrow()
+            .grapheme_count(self.text_location.line_index);
     }
 
     fn move_to_start_of_line(&mut self) {
         self.text_location.grapheme_index = 0;
     }
 
-    pub const fn is_file_loaded(&self) -> bool {
-        self.buffer.is_file_loaded()
+    pub fn is_file_loaded(&self) -> bool {
+        self.buffer.borrow().is_file_loaded()
     }
 
     pub fn enter_search(&mut self) {
+        self.clear_selection();
+        self.pending_search = None;
         self.search_info = Some(SearchInfo {
             prev_location: self.text_location,
             prev_scroll_offset: self.scroll_offset,
-            query: None,
+            prev_scroll_row_index: self.scroll_row_index,
+            pattern: None,
         });
     }
 
@@ -260,28 +942,49 @@ impl View {
     }
 
     pub fn dismiss_search(&mut self) {
+        self.pending_search = None;
         if let Some(info) = &self.search_info {
             self.text_location = info.prev_location;
             self.scroll_offset = info.prev_scroll_offset;
+            self.scroll_row_index = info.prev_scroll_row_index;
             self.scroll_location_into_view();
         }
         self.exit_search();
     }
 
-    pub fn search(&mut self, query: &str) {
+    pub const fn search_options(&self) -> SearchOptions {
+        self.search_options
+    }
+
+    pub fn set_search_options(&mut self, options: SearchOptions) {
+        self.search_options = options;
+    }
+
+    /// Searches for `query`, and returns why it isn't a valid pattern if so.
+    pub fn search(&mut self, query: &str) -> Result<(), String> {
+        let pattern = if query.is_empty() {
+            Ok(None)
+        } else {
+            SearchPattern::new(query, self.search_options).map(Some)
+        };
+        let result = pattern.as_ref().map(|_| ()).map_err(Clone::clone);
         if let Some(search_info) = &mut self.search_info {
-            search_info.query = Some(Line::from(query));
+            search_info.pattern = pattern.ok().flatten();
         }
         self.search_in_direction(self.text_location, SearchDirection::default());
+        self.mark_redraw(true);
+        result
     }
 
     pub fn search_next(&mut self) {
-        let step_right = self
-            .get_search_query()
-            .map_or(1, |query| min(query.grapheme_count(), 1));
         let location = Location {
             line_index: self.text_location.line_index,
-            grapheme_index: self.text_location.grapheme_index.saturating_add(step_right),
+            grapheme_index: min(
+                self.text_location.grapheme_index.saturating_add(1),
+                self.buffer
+                    .borrow()
+                    .grapheme_count(self.text_location.line_index),
+            ),
         };
         self.search_in_direction(location, SearchDirection::Forward);
     }
@@ -290,41 +993,154 @@ impl View {
         self.search_in_direction(self.text_location, SearchDirection::Backward);
     }
 
+    /// Moves the caret to the first match at or after where it was before
+    /// the query was typed. Returns whether there is one.
+    pub fn start_replace(&mut self) -> bool {
+        self.pending_search = None;
+        let from = self
+            .search_info
+            .as_ref()
+            .map_or(self.text_location, |info| info.prev_location);
+        self.find_replace_match(from)
+    }
+
+    // Moves the caret to the next match at or after `from`. Unlike searching,
+    // this doesn't wrap around, so that every match is visited only once.
+    fn find_replace_match(&mut self, from: Location) -> bool {
+        let Some(location) = self
+            .get_search_pattern()
+            .and_then(|pattern| self.buffer.borrow().search_forward(pattern, from))
+        else {
+            return false;
+        };
+        if location < from {
+            return false;
+        }
+        self.text_location = location;
+        self.center_text_location();
+        true
+    }
+
+    /// Replaces the match at the caret, if there is one, and moves the caret
+    /// right after the replacement. Returns whether anything was replaced.
+    pub fn replace_current_match(&mut self, replacement: &str) -> bool {
+        let Some(pattern) = self
+            .search_info
+            .as_ref()
+            .and_then(|info| info.pattern.as_ref())
+        else {
+            return false;
+        };
+        let Some(next) =
+            self.buffer
+                .borrow_mut()
+                .replace_match(self.text_location, pattern, replacement)
+        else {
+            return false;
+        };
+        self.text_location = next;
+        self.mark_redraw(true);
+        true
+    }
+
+    pub fn find_next_replace_match(&mut self) -> bool {
+        self.find_replace_match(self.text_location)
+    }
+
+    pub fn skip_current_match(&mut self) -> bool {
+        let from = Location {
+            grapheme_index: self.text_location.grapheme_index.saturating_add(1),
+            line_index: self.text_location.line_index,
+        };
+        self.find_replace_match(from)
+    }
+
+    pub fn replace_remaining_matches(&mut self, replacement: &str) -> usize {
+        let Some(pattern) = self
+            .search_info
+            .as_ref()
+            .and_then(|info| info.pattern.as_ref())
+        else {
+            return 0;
+        };
+        let count = self
+            .buffer
+            .borrow_mut()
+            .replace_all(pattern, replacement, self.text_location);
+        self.mark_redraw(true);
+        count
+    }
+
     fn search_in_direction(&mut self, from: Location, direction: SearchDirection) {
-        if let Some(location) = self.get_search_query().and_then(|query| {
-            if query.is_empty() {
-                None
-            } else if direction == SearchDirection::Forward {
-                self.buffer.search_forward(query, from)
-            } else {
-                self.buffer.search_backward(query, from)
-            }
-        }) {
+        self.pending_search = self
+            .get_search_pattern()
+            .cloned()
+            .map(|pattern| PendingSearch {
+                pattern,
+                from,
+                direction,
+                searched: 0,
+            });
+        self.continue_search();
+    }
+
+    pub const fn is_searching(&self) -> bool {
+        self.pending_search.is_some()
+    }
+
+    /// Searches the next batch of lines, and returns how far the search got
+    /// in percent if it isn't done yet.
+    pub fn continue_search(&mut self) -> Option<usize> {
+        let search = self.pending_search.as_mut()?;
+        let buffer = self.buffer.borrow();
+        let length = buffer.search_length();
+        let end = search
+            .searched
+            .saturating_add(SEARCH_BATCH_LINES)
+            .min(length);
+        let offsets = search.searched..end;
+        let found = if search.direction == SearchDirection::Forward {
+            buffer.search_lines_forward(&search.pattern, search.from, offsets)
+        } else {
+            buffer.search_lines_backward(&search.pattern, search.from, offsets)
+        };
+        drop(buffer);
+        search.searched = end;
+        if let Some(location) = found {
+            self.pending_search = None;
             self.text_location = location;
             self.center_text_location();
+            return None;
+        }
+        if end >= length {
+            self.pending_search = None;
+            return None;
         }
+        Some(end.saturating_mul(100).checked_div(length).unwrap_or(0))
     }
 
     fn center_text_location(&mut self) {
-        let Size { height, width } = self.size;
-        let Position { row, col } = self.text_location_to_position();
-        let vertical_mid = height.div_ceil(2);
-        let horizontal_mid = width.div_ceil(2);
-        self.scroll_offset.row = row.saturating_sub(vertical_mid);
-        self.scroll_offset.col = col.saturating_sub(horizontal_mid);
+        let vertical_mid = self.size.height.div_ceil(2);
+        if self.wrap_mode == WrapMode::Off {
+            let horizontal_mid = self.text_area_width().div_ceil(2);
+            let Position { row, col } = self.text_location_to_position();
+            self.scroll_offset.row = row.saturating_sub(vertical_mid);
+            self.scroll_offset.col = col.saturating_sub(horizontal_mid);
+        } else {
+            let (caret_row, _) = self.caret_row();
+            self.set_top_row(self.step_rows_up(caret_row, vertical_mid));
+        }
         self.mark_redraw(true);
     }
 
-    pub fn get_search_query(&self) -> Option<&Line> {
-        let query = self
-            .search_info
-            .as_ref()
-            .and_then(|info| info.query.as_ref());
+    pub fn get_search_pattern(&self) -> Option<&SearchPattern> {
         debug_assert!(
-            query.is_some(),
+            self.search_info.is_some(),
             "Attempting to search with malformed search info"
         );
-        query
+        self.search_info
+            .as_ref()
+            .and_then(|info| info.pattern.as_ref())
     }
 }
 
@@ -334,7 +1150,12 @@ impl UIComponent for View {
     }
 
     fn needs_redraw(&self) -> bool {
+        // The buffer might have been edited through another view, and line
+        // numbers depend on which line the caret is on.
         self.needs_redraw
+            || self.buffer.borrow().revision() != self.rendered_revision
+            || (self.line_numbers != LineNumbers::Off
+                && self.text_location.line_index != self.rendered_caret_line)
     }
 
     fn set_size(&mut self, to: Size) {
@@ -342,44 +1163,232 @@ impl UIComponent for View {
         self.scroll_location_into_view();
     }
 
-    fn draw(&mut self, origin_y: RowIdx) -> Result<(), std::io::Error> {
+    fn draw(&mut self, origin: Position) -> Result<(), std::io::Error> {
         let Size { height, width } = self.size;
-        let end_y = origin_y.saturating_add(height);
         let top_third = height.div_ceil(3);
-        let scroll_top = self.scroll_offset.row;
 
-        let query = self
+        let pattern = self
             .search_info
             .as_ref()
-            .and_then(|info| info.query.as_deref());
-        let selected_match = query.is_some().then_some(self.text_location);
-        let mut highlighter = Highlighter::new(
-            query,
-            selected_match,
-            self.buffer.get_file_info().get_file_type(),
-        );
+            .and_then(|info| info.pattern.as_ref());
+        let selected_match = pattern.is_some().then_some(self.text_location);
+        let mut highlighter = Highlighter::new(pattern, selected_match, self.selection());
 
-        for current_row in 0..end_y.saturating_add(scroll_top) {
-            self.buffer.highlight(current_row, &mut highlighter);
-        }
+        let visible_rows = self.visible_rows();
+        let gutter_width = self.gutter_width();
+        let text_width = width.saturating_sub(gutter_width);
+        let mut visible_lines = visible_rows
+            .iter()
+            .flatten()
+            .map(|(line_index, _)| *line_index);
+        let first_visible_line = visible_lines.next().unwrap_or(0);
+        let end_of_visible_lines = visible_lines
+            .next_back()
+            .unwrap_or(first_visible_line)
+            .saturating_add(1);
+        let mut buffer = self.buffer.borrow_mut();
+        buffer.highlight(first_visible_line..end_of_visible_lines, &mut highlighter);
 
-        for current_row in origin_y..end_y {
-            let line_index = current_row
-                .saturating_sub(origin_y)
-                .saturating_add(scroll_top);
-            let left = self.scroll_offset.col;
-            let right = self.scroll_offset.col.saturating_add(width);
-            if let Some(annotated_string) =
-                self.buffer
-                    .get_highlighted_substring(line_index, left..right, &highlighter)
-            {
-                Terminal::print_annotated_row(current_row, &annotated_string)?;
-            } else if current_row == top_third && self.buffer.is_empty() {
-                Self::render_line(current_row, &Self::build_welcome_message(width))?;
-            } else {
-                Self::render_line(current_row, "~")?;
+        for (current_row, visible_row) in visible_rows.into_iter().enumerate() {
+            let line_origin = Position {
+                col: origin.col,
+                row: origin.row.saturating_add(current_row),
+            };
+            match visible_row.and_then(|(line_index, columns)| {
+                // Rows continuing a wrapped line get no number of their own.
+                let is_first_row = self.wrap_mode == WrapMode::Off || columns.start == 0;
+                buffer
+                    .get_highlighted_substring(line_index, columns, &highlighter)
+                    .map(|annotated_string| (is_first_row.then_some(line_index), annotated_string))
+            }) {
+                Some((numbered_line, annotated_string)) => {
+                    if gutter_width > 0 {
+                        let label = self.gutter_label(numbered_line, gutter_width);
+                        Terminal::print_annotated_at(
+                            line_origin,
+                            gutter_width,
+                            &label,
+                            UiElement::Gutter,
+                        )?;
+                    }
+                    let text_origin = Position {
+                        col: line_origin.col.saturating_add(gutter_width),
+                        row: line_origin.row,
+                    };
+                    Terminal::print_annotated_at(
+                        text_origin,
+                        text_width,
+                        &annotated_string,
+                        UiElement::Text,
+                    )?;
+                }
+                None if current_row == top_third && buffer.is_empty() => {
+                    Self::render_line(
+                        line_origin,
+                        width,
+                        &Self::build_welcome_message(width),
+                        UiElement::Welcome,
+                    )?;
+                }
+                None => Self::render_line(line_origin, width, "~", UiElement::Text)?,
             }
         }
+        self.rendered_revision = buffer.revision();
+        self.rendered_caret_line = self.text_location.line_index;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(view: &View) -> String {
+        let buffer = view.buffer.borrow();
+        buffer.text_in_range(Location::default(), location(buffer.height(), 0))
+    }
+
+    fn location(line_index: LineIdx, grapheme_index: GraphemeIdx) -> Location {
+        Location {
+            grapheme_index,
+            line_index,
+        }
+    }
+
+    // Sets up replacing `query`, with the caret at `caret` beforehand.
+    fn start_replace(text: &str, caret: Location, query: &str) -> (View, bool) {
+        let mut view = View::from_text(text);
+        view.text_location = caret;
+        view.enter_search();
+        view.search(query).unwrap();
+        let found = view.start_replace();
+        (view, found)
+    }
+
+    #[test]
+    fn replacing_starts_at_the_caret() {
+        let (view, found) = start_replace("ab ab\nab", location(0, 1), "ab");
+        assert!(found);
+        assert_eq!(view.text_location, location(0, 3));
+
+        let (_, found) = start_replace("ab ab\nab", location(1, 1), "ab");
+        assert!(!found);
+    }
+
+    #[test]
+    fn replacing_one_by_one_moves_to_the_next_match() {
+        let (mut view, _) = start_replace("ab ab\nab", location(0, 0), "ab");
+        assert!(view.replace_current_match("xyz"));
+        assert_eq!(view.text_location, location(0, 3));
+        assert!(view.find_next_replace_match());
+        assert_eq!(view.text_location, location(0, 4));
+        assert!(view.skip_current_match());
+        assert_eq!(view.text_location, location(1, 0));
+        assert!(view.replace_current_match(""));
+        assert!(!view.find_next_replace_match());
+        assert_eq!(text(&view), "xyz ab\n");
+    }
+
+    #[test]
+    fn nothing_is_replaced_away_from_a_match() {
+        let (mut view, _) = start_replace("ab ab", location(0, 0), "ab");
+        view.text_location = location(0, 1);
+        assert!(!view.replace_current_match("x"));
+        assert_eq!(text(&view), "ab ab");
+        assert!(!view.buffer.borrow().is_dirty());
+    }
+
+    #[test]
+    fn replacing_the_rest_counts_what_was_replaced() {
+        let (mut view, _) = start_replace("ab ab\nabab\nb", location(0, 2), "ab");
+        assert_eq!(view.replace_remaining_matches("a"), 3);
+        assert_eq!(text(&view), "ab a\naa\nb");
+        assert_eq!(view.replace_remaining_matches("a"), 0);
+    }
+
+    fn wrapped_view(text: &str, width: usize, height: usize) -> View {
+        let mut view = View::from_text(text);
+        view.wrap_mode = WrapMode::Anywhere;
+        view.set_size(Size { height, width });
+        view
+    }
+
+    fn caret_row(view: &View) -> (LineIdx, usize, ColIdx) {
+        let (row, col) = view.caret_row();
+        (row.line_index, row.row_index, col)
+    }
+
+    #[test]
+    fn the_caret_is_placed_on_its_wrapped_row() {
+        let mut view = wrapped_view("abcdefghij\n世界世界", 4, 3);
+        view.text_location = location(0, 4);
+        assert_eq!(caret_row(&view), (0, 1, 0));
+        view.text_location = location(0, 10);
+        assert_eq!(caret_row(&view), (0, 2, 2));
+        view.text_location = location(1, 3);
+        assert_eq!(caret_row(&view), (1, 1, 2));
+        view.text_location = location(1, 4);
+        assert_eq!(caret_row(&view), (1, 2, 0));
+    }
+
+    #[test]
+    fn moving_by_rows_keeps_the_column_where_possible() {
+        let mut view = wrapped_view("abcdefghij\nx\n世界世界\n\nyz", 4, 3);
+        view.text_location = location(0, 5);
+        let mut visited = Vec::new();
+        for _ in 0..6 {
+            view.move_down_by_rows(1);
+            visited.push(view.text_location);
+        }
+        assert_eq!(
+            visited,
+            vec![
+                location(0, 9),
+                location(1, 1),
+                location(2, 0),
+                location(2, 2),
+                location(2, 4),
+                location(3, 0),
+            ]
+        );
+        view.move_up_by_rows(4);
+        assert_eq!(view.text_location, location(1, 0));
+    }
+
+    #[test]
+    fn scrolling_keeps_the_caret_row_in_view() {
+        let mut view = wrapped_view("abcdefghij\nx\n世界世界\n\nyz", 4, 3);
+        view.text_location = location(2, 0);
+        view.scroll_location_into_view();
+        assert_eq!((view.scroll_offset.row, view.scroll_row_index), (0, 2));
+        view.text_location = location(0, 5);
+        view.scroll_location_into_view();
+        assert_eq!((view.scroll_offset.row, view.scroll_row_index), (0, 1));
+        view.text_location = location(4, 2);
+        view.scroll_location_into_view();
+        assert_eq!((view.scroll_offset.row, view.scroll_row_index), (2, 2));
+    }
+
+    #[test]
+    fn one_column_fits_a_grapheme_per_row() {
+        let mut view = wrapped_view("世界\n\nab", 1, 2);
+        view.text_location = location(0, 0);
+        let mut visited = Vec::new();
+        for _ in 0..5 {
+            view.move_down_by_rows(1);
+            visited.push(view.text_location);
+        }
+        assert_eq!(
+            visited,
+            vec![
+                location(0, 1),
+                location(0, 2),
+                location(1, 0),
+                location(2, 0),
+                location(2, 1),
+            ]
+        );
+        view.scroll_location_into_view();
+        assert_eq!((view.scroll_offset.row, view.scroll_row_index), (2, 0));
+    }
+}
@@ -0,0 +1,22 @@
+use std::fmt::Display;
+
+/// Why a buffer can't be edited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadOnly {
+    /// The file was opened for viewing only.
+    Requested,
+    /// The file can't be written to, e.g. for lack of permissions.
+    NotWritable,
+    /// Binary files are shown as a hex dump, which mustn't be written back.
+    Binary,
+}
+
+impl Display for ReadOnly {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Requested => write!(f, "the file was opened read-only"),
+            Self::NotWritable => write!(f, "the file isn't writable"),
+            Self::Binary => write!(f, "binary files are shown read-only"),
+        }
+    }
+}
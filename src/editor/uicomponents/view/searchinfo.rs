@@ -1,8 +1,21 @@
-use crate::editor::line::Line;
+use crate::editor::SearchPattern;
 use crate::prelude::*;
 
+use super::SearchDirection;
+
+/// A search which goes through a batch of lines at a time, so that searching
+/// a large file doesn't hold up the editor.
+pub struct PendingSearch {
+    pub pattern: SearchPattern,
+    pub from: Location,
+    pub direction: SearchDirection,
+    // How many lines were searched so far.
+    pub searched: usize,
+}
+
 pub struct SearchInfo {
     pub prev_location: Location,
     pub prev_scroll_offset: Position,
-    pub query: Option<Line>,
+    pub prev_scroll_row_index: usize,
+    pub pattern: Option<SearchPattern>,
 }
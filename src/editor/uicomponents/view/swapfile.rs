@@ -0,0 +1,93 @@
+use std::fs::{self, File};
+use std::io::{Error, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+use super::write_atomically;
+
+// Starts every swap file, so that those of other editors are left alone.
+const SWAP_FILE_HEADER: &str = "hecto swap file\n";
+
+/// Where the unsaved changes to the file at `path` are kept, so that they
+/// can be recovered if the editor doesn't get to exit cleanly.
+pub fn swap_path_for(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{name}.hecto.swp"))
+}
+
+/// Returns whether there's a swap file at `swap_path` which was written by
+/// this editor.
+pub fn is_swap_file(swap_path: &Path) -> bool {
+    let mut header = [0; SWAP_FILE_HEADER.len()];
+    File::open(swap_path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|()| header == SWAP_FILE_HEADER.as_bytes())
+}
+
+/// Returns the unsaved contents kept in the swap file.
+pub fn read_swap_file(swap_path: &Path) -> Result<String, Error> {
+    fs::read_to_string(swap_path)?
+        .strip_prefix(SWAP_FILE_HEADER)
+        .map(String::from)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Not a swap file of this editor"))
+}
+
+/// Writes the unsaved contents such that only the user can read them, as
+/// the file they belong to may be private.
+pub fn write_swap_file(swap_path: &Path, text: &str) -> Result<(), Error> {
+    write_atomically(swap_path, false, true, |file| {
+        file.write_all(SWAP_FILE_HEADER.as_bytes())?;
+        file.write_all(text.as_bytes())
+    })
+}
+
+/// Removes the swap file, which may not exist.
+pub fn remove_swap_file(swap_path: &Path) -> Result<(), Error> {
+    match fs::remove_file(swap_path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::process;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        temp_dir().join(format!("hecto-test-{}-{name}", process::id()))
+    }
+
+    #[test]
+    fn swap_files_are_named_after_their_file() {
+        assert_eq!(
+            swap_path_for(Path::new("/tmp/notes.txt")),
+            Path::new("/tmp/.notes.txt.hecto.swp")
+        );
+    }
+
+    #[test]
+    fn reads_back_what_was_written() {
+        let path = temp_path("swap-roundtrip");
+        write_swap_file(&path, "a\nb").unwrap();
+        assert!(is_swap_file(&path));
+        assert_eq!(read_swap_file(&path).unwrap(), "a\nb");
+        remove_swap_file(&path).unwrap();
+        assert!(!path.exists());
+        assert!(remove_swap_file(&path).is_ok());
+    }
+
+    #[test]
+    fn other_files_are_not_taken_for_swap_files() {
+        let path = temp_path("swap-foreign");
+        fs::write(&path, b"b0VIM 9.0\0\0").unwrap();
+        assert!(!is_swap_file(&path));
+        assert!(read_swap_file(&path).is_err());
+        let _ = fs::remove_file(&path);
+        assert!(!is_swap_file(&path));
+    }
+}
@@ -0,0 +1,288 @@
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Seek, SeekFrom};
+use std::ops::Range;
+
+use super::{Encoding, Line, LineEnding, LineRope};
+
+// Files larger than this (64 MiB) are only read as far as needed to find
+// their lines, which are then read as they're needed, and aren't highlighted.
+const LARGE_FILE_SIZE: u64 = 67_108_864;
+// How much of a large file is looked at to tell whether it's text.
+const SAMPLE_SIZE: u64 = 65_536;
+const READ_BUFFER_SIZE: usize = 1_048_576;
+// How many bytes of a large file's lines are read at once, when any of them
+// is needed.
+const CHUNK_SIZE: u64 = 65_536;
+
+pub struct TextFile {
+    pub lines: LineRope,
+    pub encoding: Encoding,
+    pub line_ending: LineEnding,
+    pub has_final_newline: bool,
+    pub is_large: bool,
+}
+
+impl TextFile {
+    pub fn read(file_name: &str) -> Result<Self, Error> {
+        if fs::metadata(file_name)?.len() > LARGE_FILE_SIZE {
+            Self::read_large(file_name)
+        } else {
+            Self::read_whole(file_name)
+        }
+    }
+
+    fn read_whole(file_name: &str) -> Result<Self, Error> {
+        let (encoding, contents) = Encoding::decode(&fs::read(file_name)?);
+        let line_ending = LineEnding::detect(&contents);
+        let separator = line_ending.as_str();
+        let lines = if contents.is_empty() {
+            LineRope::default()
+        } else if line_ending == LineEnding::Mixed {
+            let mut lines: Vec<Line> = Line::split_text(&contents).collect();
+            // The final line break leaves an empty line behind it.
+            if contents.ends_with('\n') {
+                lines.pop();
+            }
+            lines.into_iter().collect()
+        } else {
+            contents
+                .strip_suffix(separator)
+                .unwrap_or(&contents)
+                .split(separator)
+                .map(Line::from)
+                .collect()
+        };
+        Ok(Self {
+            lines,
+            encoding,
+            line_ending,
+            has_final_newline: contents.is_empty() || contents.ends_with('\n'),
+            is_large: false,
+        })
+    }
+
+    // Goes through the file once to find where its lines are and how they
+    // are encoded, without holding more than a line in memory. The lines
+    // themselves are read when first needed.
+    fn read_large(file_name: &str) -> Result<Self, Error> {
+        let file = File::open(file_name)?;
+        let mut sample = Vec::new();
+        (&file).take(SAMPLE_SIZE).read_to_end(&mut sample)?;
+        let mut encoding = match Encoding::detect(&sample) {
+            // Their lines can't be found without decoding them.
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    "UTF-16 files this large can't be opened",
+                ));
+            }
+            Encoding::Binary => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    "Binary files this large can't be opened",
+                ));
+            }
+            encoding => encoding,
+        };
+        (&file).seek(SeekFrom::Start(0))?;
+
+        let mut chunks: Vec<(Range<u64>, usize)> = Vec::new();
+        let mut chunk_start: u64 = 0;
+        let mut chunk_len: usize = 0;
+        let mut offset: u64 = 0;
+        let mut line_break_count: usize = 0;
+        let mut crlf_count: usize = 0;
+        let mut has_final_newline = true;
+        let mut reader = BufReader::with_capacity(READ_BUFFER_SIZE, &file);
+        let mut bytes = Vec::new();
+        loop {
+            bytes.clear();
+            let read = reader.read_until(b'\n', &mut bytes)?;
+            if read == 0 {
+                break;
+            }
+            offset = offset.saturating_add(u64::try_from(read).unwrap_or(u64::MAX));
+            if bytes.ends_with(b"\n") {
+                line_break_count = line_break_count.saturating_add(1);
+                if bytes.ends_with(b"\r\n") {
+                    crlf_count = crlf_count.saturating_add(1);
+                }
+            } else {
+                has_final_newline = false;
+            }
+            if encoding != Encoding::Latin1 && std::str::from_utf8(&bytes).is_err() {
+                encoding = Encoding::Latin1;
+            }
+            chunk_len = chunk_len.saturating_add(1);
+            if offset.saturating_sub(chunk_start) >= CHUNK_SIZE {
+                chunks.push((chunk_start..offset, chunk_len));
+                chunk_start = offset;
+                chunk_len = 0;
+            }
+        }
+        if chunk_len > 0 {
+            chunks.push((chunk_start..offset, chunk_len));
+        }
+        // The byte order mark isn't part of the first line, unless it turned
+        // out not to be one.
+        if let Some((first_chunk, _)) = chunks.first_mut() {
+            first_chunk.start = u64::try_from(encoding.bom().len()).unwrap_or_default();
+        }
+
+        let line_ending = if crlf_count == 0 {
+            LineEnding::Lf
+        } else if crlf_count == line_break_count {
+            LineEnding::Crlf
+        } else {
+            LineEnding::Mixed
+        };
+        let mut lines = LineRope::with_source(LineSource {
+            file,
+            encoding,
+            line_ending,
+        });
+        for (bytes, len) in chunks {
+            lines.push_unread(bytes, len);
+        }
+        Ok(Self {
+            lines,
+            encoding,
+            line_ending,
+            has_final_newline,
+            is_large: true,
+        })
+    }
+}
+
+/// Where the lines of a large file are read from once they're needed. The
+/// file is kept open, so that it's still read from if it's replaced by
+/// another, but changes to the file itself show in the lines not read yet.
+pub struct LineSource {
+    file: File,
+    encoding: Encoding,
+    line_ending: LineEnding,
+}
+
+impl LineSource {
+    /// Reads the `len` lines stored in `bytes` of the file. Fails if they
+    /// aren't there anymore, e.g. as the file was cut short.
+    pub fn read_lines(&self, bytes: Range<u64>, len: usize) -> Result<Vec<Line>, Error> {
+        let mut contents =
+            vec![0; usize::try_from(bytes.end.saturating_sub(bytes.start)).unwrap_or(0)];
+        (&self.file).seek(SeekFrom::Start(bytes.start))?;
+        (&self.file).read_exact(&mut contents)?;
+        let lines: Vec<Line> = contents
+            .split_inclusive(|byte| *byte == b'\n')
+            .map(|bytes| self.to_line(bytes))
+            .collect();
+        if lines.len() != len {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "The file's lines moved since it was opened",
+            ));
+        }
+        Ok(lines)
+    }
+
+    fn to_line(&self, bytes: &[u8]) -> Line {
+        let (bytes, carriage_return) = match bytes.strip_suffix(b"\n") {
+            Some(bytes) => bytes
+                .strip_suffix(b"\r")
+                .map_or((bytes, false), |bytes| (bytes, true)),
+            None => (bytes, false),
+        };
+        let text = self
+            .encoding
+            .decode_line(bytes.to_vec())
+            .unwrap_or_else(|| String::from_utf8_lossy(bytes).into_owned());
+        let mut line = Line::from(&text);
+        // In CRLF files, it's the usual line ending.
+        line.set_carriage_return(carriage_return && self.line_ending != LineEnding::Crlf);
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::process;
+
+    use super::*;
+
+    fn read_both(name: &str, contents: &[u8]) -> (TextFile, TextFile) {
+        let path = temp_dir().join(format!("hecto-test-{}-{name}", process::id()));
+        let path_str = path.to_string_lossy().to_string();
+        fs::write(&path, contents).unwrap();
+        let whole = TextFile::read_whole(&path_str).unwrap();
+        let large = TextFile::read_large(&path_str).unwrap();
+        large.lines.read_all().unwrap();
+        let _ = fs::remove_file(&path);
+        (whole, large)
+    }
+
+    fn lines(file: &TextFile) -> Vec<(String, bool)> {
+        file.lines
+            .iter()
+            .map(|line| (line.to_string(), line.has_carriage_return()))
+            .collect()
+    }
+
+    #[test]
+    fn large_files_read_like_small_ones() {
+        let long_lines = "x".repeat(100_000) + "\n" + &"y\r\n".repeat(50_000);
+        for (name, contents) in [
+            ("lf", "a\nb\n".as_bytes()),
+            ("crlf", b"a\r\nb\r\n"),
+            ("mixed", b"a\r\nb\nc"),
+            ("bom", b"\xEF\xBB\xBFa\nb"),
+            ("latin1", b"a\nb\xE9\n"),
+            ("chunks", long_lines.as_bytes()),
+        ] {
+            let (whole, large) = read_both(name, contents);
+            assert_eq!(lines(&large), lines(&whole), "{name}");
+            assert_eq!(large.encoding, whole.encoding, "{name}");
+            assert_eq!(large.line_ending, whole.line_ending, "{name}");
+            assert_eq!(large.has_final_newline, whole.has_final_newline, "{name}");
+        }
+    }
+
+    #[test]
+    fn large_binary_and_utf16_files_are_refused() {
+        for (name, contents) in [
+            ("binary", "\0\u{1}\u{2}".as_bytes()),
+            ("utf16", b"\xFF\xFEa\x00"),
+        ] {
+            let path = temp_dir().join(format!("hecto-test-{}-{name}", process::id()));
+            fs::write(&path, contents).unwrap();
+            let result = TextFile::read_large(&path.to_string_lossy());
+            let _ = fs::remove_file(&path);
+            assert!(result.is_err_and(|err| err.kind() == ErrorKind::Unsupported));
+        }
+    }
+
+    #[test]
+    fn lines_cut_from_a_large_file_are_reported() {
+        let path = temp_dir().join(format!("hecto-test-{}-cut", process::id()));
+        fs::write(&path, "line\n".repeat(100_000)).unwrap();
+        let large = TextFile::read_large(&path.to_string_lossy()).unwrap();
+        fs::write(&path, "line\n").unwrap();
+        let result = large.lines.read_all();
+        let _ = fs::remove_file(&path);
+        assert!(result.is_err_and(|err| err.kind() == ErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn looking_through_lines_keeps_none_of_them() {
+        let path = temp_dir().join(format!("hecto-test-{}-find", process::id()));
+        fs::write(&path, "line\n".repeat(100_000)).unwrap();
+        let large = TextFile::read_large(&path.to_string_lossy()).unwrap();
+        let _ = fs::remove_file(&path);
+        let last = large.lines.len().saturating_sub(2);
+        let found = large.lines.find_map(
+            (0..large.lines.len()).map(|index| (index, ())),
+            |line, index, ()| (index == last).then(|| line.to_string()),
+        );
+        assert_eq!(found.as_deref(), Some("line"));
+        assert!(large.lines.has_unread_lines());
+    }
+}
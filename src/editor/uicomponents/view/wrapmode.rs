@@ -0,0 +1,39 @@
+use std::fmt::Display;
+
+use crate::prelude::*;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum WrapMode {
+    #[default]
+    Off,
+    Words,
+    Anywhere,
+}
+
+impl WrapMode {
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Words,
+            Self::Words => Self::Anywhere,
+            Self::Anywhere => Self::Off,
+        }
+    }
+}
+
+/// One of the rows of the screen a line is wrapped into.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct WrappedRow {
+    pub line_index: LineIdx,
+    // Counted from the line's first row.
+    pub row_index: usize,
+}
+
+impl Display for WrapMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Off => write!(f, "off"),
+            Self::Words => write!(f, "at word boundaries"),
+            Self::Anywhere => write!(f, "anywhere"),
+        }
+    }
+}
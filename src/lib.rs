@@ -0,0 +1,25 @@
+#![warn(
+    clippy::all,
+    clippy::pedantic,
+    clippy::print_stdout,
+    clippy::arithmetic_side_effects,
+    clippy::as_conversions,
+    clippy::integer_division
+)]
+// The library only exists so that the benchmarks can reach the editor's
+// internals, it isn't meant to be used as an API.
+#![allow(
+    clippy::must_use_candidate,
+    clippy::missing_errors_doc,
+    clippy::missing_panics_doc
+)]
+mod editor;
+pub use editor::Editor;
+mod prelude;
+
+/// Internals used by the benchmarks, which aren't part of the public API.
+#[doc(hidden)]
+pub mod bench {
+    pub use crate::editor::{Buffer, Highlighter, SearchOptions, SearchPattern};
+    pub use crate::prelude::Location;
+}
@@ -6,9 +6,7 @@
     clippy::as_conversions,
     clippy::integer_division
 )]
-mod editor;
-use editor::Editor;
-mod prelude;
+use hecto::Editor;
 
 fn main() {
     Editor::new().unwrap().run();
@@ -1,7 +1,23 @@
+use std::cmp::Ordering;
+
 use super::{GraphemeIdx, LineIdx};
 
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct Location {
     pub grapheme_index: GraphemeIdx,
     pub line_index: LineIdx,
 }
+
+impl Ord for Location {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.line_index
+            .cmp(&other.line_index)
+            .then(self.grapheme_index.cmp(&other.grapheme_index))
+    }
+}
+
+impl PartialOrd for Location {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
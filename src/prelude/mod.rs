@@ -8,6 +8,8 @@ mod location;
 pub use location::Location;
 mod position;
 pub use position::Position;
+mod rect;
+pub use rect::Rect;
 mod size;
 pub use size::Size;
 
@@ -7,6 +7,13 @@ pub struct Position {
 }
 
 impl Position {
+    pub const fn saturating_add(&self, other: Self) -> Self {
+        Self {
+            row: self.row.saturating_add(other.row),
+            col: self.col.saturating_add(other.col),
+        }
+    }
+
     pub const fn saturating_sub(&self, other: Self) -> Self {
         Self {
             row: self.row.saturating_sub(other.row),
@@ -0,0 +1,7 @@
+use super::{Position, Size};
+
+#[derive(Clone, Copy, Default)]
+pub struct Rect {
+    pub origin: Position,
+    pub size: Size,
+}